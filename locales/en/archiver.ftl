@@ -0,0 +1,70 @@
+# messages shown to users in the terminal, see src/i18n.rs
+
+close = press enter to close
+
+conflict-prompt = { $path } exists already. overwrite it (o), back it up (b) or merge into it (m)?
+
+## setup on the first launch
+
+setup-welcome =
+    welcome to the completionist archiver! answer a few questions to set it up,
+    or press enter to keep the default shown in brackets.
+setup-saved-to = the answers are saved to { $path } and can be changed there later.
+setup-interfaces = network interfaces:
+setup-captured-by-default = (captured by default)
+setup-ask-interfaces = interfaces to capture on, e.g. 1,3
+setup-all-connected = all connected
+setup-ask-output = folder to write the exports to
+setup-current-folder = current folder
+setup-formats = formats:
+setup-ask-formats = formats to export in, e.g. 1,2
+setup-invalid-numbers = expected numbers from 1 to { $len } separated by commas
+setup-saved = saved the setup to { $path }
+
+## formats of numbers and dates, see https://docs.rs/chrono/latest/chrono/format/strftime
+
+number-group-separator = ,
+percent = { $value }%
+date-time-format = %Y-%m-%d %H:%M
+
+## diff and db commands
+
+achievements = achievements
+books = books
+keys = keys
+ids = ids
+jade = jade
+diff-unchanged = no items were added or removed
+diff-category = { $category }: { $added } added, { $removed } removed
+diff-earned = { $jade } jade earned
+
+## stats command
+
+stats-unclaimed = { $count } unclaimed achievements
+stats-achievement-series = achievements by series:
+stats-book-series = books by series:
+stats-other = other
+
+## steps printed while capturing
+
+guide-intro = in the game:
+guide-login = log in, or log out to the title screen and log in again
+guide-achievements = open the achievements screen
+guide-books = open the inventory
+
+## summary at the end of a capture
+
+summary = summary
+finished-at = finished
+uid = uid
+unclaimed-jade = unclaimed jade
+# books in the inventory, the packets do not tell whether they were read
+books-obtained = books obtained
+written = written
+not-written = would write
+missing = missing
+
+## summary written with --run-summary
+
+run-summary-database = game data
+run-summary-warnings = warnings
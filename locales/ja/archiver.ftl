@@ -0,0 +1,69 @@
+# messages shown to users in the terminal, see src/i18n.rs
+
+close = Enter キーを押すと終了します
+
+conflict-prompt = { $path } は既に存在します。上書き (o)、バックアップ (b)、マージ (m) のどれにしますか？
+
+## setup on the first launch
+
+setup-welcome =
+    completionist archiver へようこそ！いくつかの質問に答えて設定してください。
+    Enter キーを押すと括弧内のデフォルト値が使われます。
+setup-saved-to = 回答は { $path } に保存され、後から変更できます。
+setup-interfaces = ネットワークインターフェース：
+setup-captured-by-default = （デフォルトでキャプチャ）
+setup-ask-interfaces = キャプチャするインターフェース（例：1,3）
+setup-all-connected = 接続中のすべて
+setup-ask-output = エクスポートを保存するフォルダー
+setup-current-folder = 現在のフォルダー
+setup-formats = 形式：
+setup-ask-formats = エクスポートする形式（例：1,2）
+setup-invalid-numbers = 1 から { $len } までの数字をカンマ区切りで入力してください
+setup-saved = 設定を { $path } に保存しました
+
+## formats of numbers and dates, see https://docs.rs/chrono/latest/chrono/format/strftime
+
+number-group-separator = ,
+percent = { $value }%
+date-time-format = %Y年%m月%d日 %H:%M
+
+## diff and db commands
+
+achievements = アチーブメント
+books = 書籍
+keys = キー
+ids = ID
+jade = 星玉
+diff-unchanged = 追加または削除された項目はありません
+diff-category = { $category }：{ $added } 件追加、{ $removed } 件削除
+diff-earned = 星玉 { $jade } 個獲得
+
+## stats command
+
+stats-unclaimed = 報酬未受取のアチーブメント { $count } 件
+stats-achievement-series = シリーズ別アチーブメント：
+stats-book-series = シリーズ別書籍：
+stats-other = その他
+
+## steps printed while capturing
+
+guide-intro = ゲーム内で：
+guide-login = ログインするか、タイトル画面に戻って再度ログインしてください
+guide-achievements = アチーブメント画面を開いてください
+guide-books = 所持品を開いてください
+
+## summary at the end of a capture
+
+summary = まとめ
+finished-at = 完了日時
+uid = UID
+unclaimed-jade = 未受取の星玉
+books-obtained = 所持している書籍
+written = 書き込み先
+not-written = 書き込み予定
+missing = 不足
+
+## summary written with --run-summary
+
+run-summary-database = ゲームデータ
+run-summary-warnings = 警告
@@ -0,0 +1,69 @@
+# messages shown to users in the terminal, see src/i18n.rs
+
+close = 按回车键关闭
+
+conflict-prompt = { $path } 已存在。覆盖 (o)、备份 (b) 还是合并 (m)？
+
+## setup on the first launch
+
+setup-welcome =
+    欢迎使用 completionist archiver！请回答几个问题来完成设置，
+    或直接按回车键使用方括号中的默认值。
+setup-saved-to = 答案将保存到 { $path }，之后可以在该文件中修改。
+setup-interfaces = 网络接口：
+setup-captured-by-default = （默认抓取）
+setup-ask-interfaces = 要抓取的接口，例如 1,3
+setup-all-connected = 所有已连接的接口
+setup-ask-output = 导出文件的保存文件夹
+setup-current-folder = 当前文件夹
+setup-formats = 格式：
+setup-ask-formats = 要导出的格式，例如 1,2
+setup-invalid-numbers = 请输入 1 到 { $len } 之间的数字，用逗号分隔
+setup-saved = 设置已保存到 { $path }
+
+## formats of numbers and dates, see https://docs.rs/chrono/latest/chrono/format/strftime
+
+number-group-separator = ,
+percent = { $value }%
+date-time-format = %Y年%m月%d日 %H:%M
+
+## diff and db commands
+
+achievements = 成就
+books = 书籍
+keys = 密钥
+ids = ID
+jade = 星琼
+diff-unchanged = 没有新增或移除的项目
+diff-category = { $category }：新增 { $added } 个，移除 { $removed } 个
+diff-earned = 获得 { $jade } 星琼
+
+## stats command
+
+stats-unclaimed = { $count } 个成就奖励未领取
+stats-achievement-series = 各系列成就：
+stats-book-series = 各系列书籍：
+stats-other = 其他
+
+## steps printed while capturing
+
+guide-intro = 请在游戏中：
+guide-login = 登录游戏，或退出到标题画面后重新登录
+guide-achievements = 打开成就界面
+guide-books = 打开背包
+
+## summary at the end of a capture
+
+summary = 总结
+finished-at = 完成时间
+uid = UID
+unclaimed-jade = 未领取星琼
+books-obtained = 已获得的书籍
+written = 已写入
+not-written = 将写入
+missing = 缺失
+
+## summary written with --run-summary
+
+run-summary-database = 游戏数据
+run-summary-warnings = 警告
@@ -0,0 +1,258 @@
+//! Replay of packets from capture files or streams, in either pcap or pcapng format,
+//! and recording of packets to pcapng files.
+//!
+//! Unlike libpcap, pcapng files containing multiple interfaces with different link types
+//! (as written by Wireshark when capturing on "any" interface) are supported.
+//!
+//! Captures made on Android with [PCAPdroid] are supported as well, both with raw IP packets
+//! and with the PCAPdroid trailer enabled.
+//!
+//! [PCAPdroid]: https://github.com/emanuele-f/PCAPdroid
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Cursor, Read};
+use std::path::Path;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use pcap_file::DataLink;
+use pcap_file::pcap::PcapReader;
+use pcap_file::pcapng::{Block, PcapNgReader, PcapNgWriter};
+use pcap_file::pcapng::blocks::enhanced_packet::EnhancedPacketBlock;
+use pcap_file::pcapng::blocks::interface_description::InterfaceDescriptionBlock;
+use tracing::{debug, trace, warn};
+
+use crate::capture::{ETHERTYPE_IPV4, ETHERTYPE_IPV6, ip_to_ethernet, is_udp_packet, with_ethernet_header};
+use crate::error::{PathContext, Result};
+
+const PCAPNG_MAGIC: [u8; 4] = [0x0a, 0x0d, 0x0d, 0x0a];
+
+/// Magic of the trailer PCAPdroid appends to every packet when "PCAPdroid trailer" is enabled
+const PCAPDROID_TRAILER_MAGIC: [u8; 4] = [0x01, 0x07, 0x20, 0x21];
+/// Size of the PCAPdroid trailer: magic, app uid, app name and frame check sequence
+const PCAPDROID_TRAILER_LEN: usize = 4 + 4 + 20 + 4;
+
+/// Packets read from a capture file, or a stream in the same format
+/// like the output of `tcpdump -w -`.
+///
+/// Iterating yields the ethernet frames of all UDP packets, which the game traffic is part of.
+/// Packets of other protocols are skipped.
+pub struct CaptureFile {
+    reader: Reader,
+    /// Capture time of the last packet read, if the format records it
+    timestamp: Option<Duration>,
+    /// Replay speed relative to the time the packets were captured, see [`CaptureFile::set_speed`]
+    speed: Option<f64>,
+    /// Capture time of the first replayed packet and when it was replayed
+    replay_start: Option<(Duration, Instant)>,
+}
+
+enum Reader {
+    Pcap {
+        reader: PcapReader<Box<dyn Read + Send>>,
+        datalink: DataLink,
+    },
+    PcapNg {
+        reader: PcapNgReader<Box<dyn Read + Send>>,
+        /// Link types of the interfaces of the current section, indexed by interface id
+        interfaces: Vec<DataLink>,
+    },
+}
+
+impl CaptureFile {
+    /// Opens a capture file, detecting whether it is in pcap or pcapng format
+    pub fn open(path: &Path) -> Result<CaptureFile> {
+        CaptureFile::from_reader(File::open(path).with_path("open", path)?).with_path("read", path)
+    }
+
+    /// Reads packets from a stream, detecting whether it is in pcap or pcapng format
+    pub fn from_reader(reader: impl Read + Send + 'static) -> Result<CaptureFile> {
+        let mut reader = BufReader::new(reader);
+
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+
+        // streams cannot seek back, so the magic is put back in front instead
+        let file: Box<dyn Read + Send> = Box::new(Cursor::new(magic).chain(reader));
+
+        let reader = if magic == PCAPNG_MAGIC {
+            debug!("detected pcapng format");
+            Reader::PcapNg {
+                reader: PcapNgReader::new(file)?,
+                interfaces: vec![],
+            }
+        } else {
+            debug!("detected pcap format");
+            let reader = PcapReader::new(file)?;
+            let datalink = reader.header().datalink;
+            Reader::Pcap { reader, datalink }
+        };
+
+        Ok(CaptureFile {
+            reader,
+            timestamp: None,
+            speed: None,
+            replay_start: None,
+        })
+    }
+
+    /// Replays packets at the pace they were captured, sped up by `speed`,
+    /// instead of as fast as possible. Only affects packets with a capture time
+    pub fn set_speed(&mut self, speed: f64) {
+        self.speed = Some(speed);
+    }
+
+    /// Waits until the packet captured at `timestamp` is due to be replayed
+    fn pace(&mut self, timestamp: Duration) {
+        let Some(speed) = self.speed else {
+            return;
+        };
+
+        let (first, start) = *self.replay_start.get_or_insert((timestamp, Instant::now()));
+        let due = start + timestamp.saturating_sub(first).div_f64(speed);
+        std::thread::sleep(due.saturating_duration_since(Instant::now()));
+    }
+
+    /// Next block in the file, converted to an ethernet frame if it is a packet.
+    ///
+    /// Returns `None` once the end of the file is reached, and `Some(None)` for blocks that
+    /// are not packets or packets of unsupported link types.
+    fn next_frame(&mut self) -> Option<Option<Vec<u8>>> {
+        match &mut self.reader {
+            Reader::Pcap { reader, datalink } => {
+                match reader.next_packet()? {
+                    Ok(packet) => {
+                        self.timestamp = Some(packet.timestamp);
+                        Some(to_ethernet(*datalink, &packet.data))
+                    }
+                    Err(e) => {
+                        warn!(%e, "could not read packet, stop reading capture file");
+                        None
+                    }
+                }
+            }
+            Reader::PcapNg { reader, interfaces } => {
+                match reader.next_block()? {
+                    Ok(Block::SectionHeader(_)) => {
+                        // interface ids are scoped to their section
+                        interfaces.clear();
+                        Some(None)
+                    }
+                    Ok(Block::InterfaceDescription(interface)) => {
+                        debug!(id = interfaces.len(), datalink = ?interface.linktype, "found interface");
+                        interfaces.push(interface.linktype);
+                        Some(None)
+                    }
+                    Ok(Block::EnhancedPacket(packet)) => {
+                        self.timestamp = Some(packet.timestamp);
+                        let datalink = interfaces.get(packet.interface_id as usize).copied();
+                        Some(datalink.and_then(|datalink| to_ethernet(datalink, &packet.data)))
+                    }
+                    Ok(Block::SimplePacket(packet)) => {
+                        // simple packets always belong to the first interface and have no timestamp
+                        self.timestamp = None;
+                        let datalink = interfaces.first().copied();
+                        Some(datalink.and_then(|datalink| to_ethernet(datalink, &packet.data)))
+                    }
+                    Ok(_) => Some(None),
+                    Err(e) => {
+                        warn!(%e, "could not read block, stop reading capture file");
+                        None
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Iterator for CaptureFile {
+    type Item = Vec<u8>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(frame) = self.next_frame()? {
+                if is_udp_packet(&frame) {
+                    if let Some(timestamp) = self.timestamp {
+                        self.pace(timestamp);
+                    }
+                    return Some(frame);
+                }
+            }
+        }
+    }
+}
+
+/// Writer of captured ethernet frames to a pcapng file
+pub struct CaptureFileWriter {
+    writer: PcapNgWriter<BufWriter<File>>,
+}
+
+impl CaptureFileWriter {
+    /// Creates a pcapng file containing a single ethernet interface
+    pub fn create(path: &Path) -> Result<CaptureFileWriter> {
+        let file = File::create(path).with_path("create", path)?;
+        let mut writer = PcapNgWriter::new(BufWriter::new(file)).with_path("write", path)?;
+
+        writer.write_pcapng_block(InterfaceDescriptionBlock {
+            linktype: DataLink::ETHERNET,
+            snaplen: 0,
+            options: vec![],
+        }).with_path("write", path)?;
+
+        Ok(CaptureFileWriter { writer })
+    }
+
+    /// Writes an ethernet frame, timestamped with the current time
+    pub fn write(&mut self, frame: &[u8]) -> Result<()> {
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+
+        self.writer.write_pcapng_block(EnhancedPacketBlock {
+            interface_id: 0,
+            timestamp,
+            original_len: frame.len() as u32,
+            data: Cow::Borrowed(frame),
+            options: vec![],
+        })?;
+
+        Ok(())
+    }
+}
+
+/// Converts a packet of the given link type to an ethernet frame,
+/// returns `None` for unsupported link types
+fn to_ethernet(datalink: DataLink, data: &[u8]) -> Option<Vec<u8>> {
+    match datalink {
+        DataLink::ETHERNET => Some(strip_pcapdroid_trailer(data).to_vec()),
+        DataLink::RAW | DataLink::IPV4 | DataLink::IPV6 => ip_to_ethernet(data),
+        DataLink::LINUX_SLL => {
+            // 16 byte header, protocol type in the last two bytes
+            let protocol = data.get(14..16)?;
+            let protocol = u16::from_be_bytes([protocol[0], protocol[1]]);
+            match protocol {
+                ETHERTYPE_IPV4 | ETHERTYPE_IPV6 => Some(with_ethernet_header(protocol, data.get(16..)?)),
+                _ => None,
+            }
+        }
+        DataLink::NULL | DataLink::LOOP => ip_to_ethernet(data.get(4..)?),
+        _ => {
+            debug!(?datalink, "unsupported link type");
+            None
+        }
+    }
+}
+
+/// Removes the trailer PCAPdroid appends to ethernet frames, which would otherwise
+/// be read as part of the UDP payload
+fn strip_pcapdroid_trailer(frame: &[u8]) -> &[u8] {
+    let Some(start) = frame.len().checked_sub(PCAPDROID_TRAILER_LEN) else {
+        return frame;
+    };
+
+    // the ethernet header of these frames is made up, so the trailer can only be told apart
+    // from packet data by its magic
+    if start >= 14 && frame[start..start + 4] == PCAPDROID_TRAILER_MAGIC {
+        trace!("stripped pcapdroid trailer");
+        &frame[..start]
+    } else {
+        frame
+    }
+}
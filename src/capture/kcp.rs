@@ -0,0 +1,382 @@
+//! Cleanup of the KCP stream the game communicates over, before it reaches the sniffer.
+//!
+//! KCP itself already puts segments back in order, but on lossy networks (mostly Wi-Fi)
+//! the same segment is often retransmitted several times, and captures on multiple
+//! interfaces can see the same datagram twice. Datagrams that only contain segments
+//! that were already seen are dropped, and the health of the stream is counted
+//! so it can be reported at the end of a capture.
+//!
+//! Handshake datagrams carry no sequence number, so copies of a handshake seen again right after
+//! it are dropped as well, which would otherwise make the sniffer start the connection over.
+//!
+//! Every segment acknowledges the segments of the other direction below its `una`, which are
+//! only remembered in case they were never seen, so long captures do not keep every sequence number.
+use std::collections::{HashMap, HashSet};
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tracing::trace;
+
+use crate::capture::udp_datagram;
+
+/// Size of the handshake datagrams, which are not KCP segments
+const HANDSHAKE_LEN: usize = 20;
+/// Size of a segment header: conv, token, cmd, frg, wnd, ts, sn, una and len
+const SEGMENT_HEADER_LEN: usize = 28;
+/// Command of segments carrying data, all other commands are acks and window probes
+const CMD_PUSH: u8 = 81;
+/// How long after a handshake datagram the same datagram is a copy captured on another interface,
+/// e.g. on Wi-Fi and a virtual adapter, instead of the game sending it again. Copies arrive
+/// within a few milliseconds, while the game waits for a reply longer than this before retrying
+const DUPLICATE_HANDSHAKE_WINDOW: Duration = Duration::from_millis(100);
+
+/// Counters of the segments seen by a [`KcpFilter`]
+#[derive(Serialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct KcpStats {
+    /// Data segments seen, including retransmissions
+    pub segments: u64,
+    /// Data segments that were seen before
+    pub retransmitted: u64,
+    /// Data segments that arrived after a segment that was sent later
+    pub reordered: u64,
+    /// Data segments that were never seen, even though a segment sent later was
+    pub lost: u64,
+    /// Handshake datagrams that were captured twice
+    pub duplicate_handshakes: u64,
+}
+
+/// Drops retransmitted datagrams of the game traffic and counts the health of the stream
+#[derive(Default)]
+pub struct KcpFilter {
+    /// Streams of the current connection, by direction (`true` if sent by the server) and conv
+    streams: HashMap<(bool, u32), Stream>,
+    /// When each handshake datagram of the current connection was last seen, by its source and payload
+    handshakes: HashMap<(SocketAddr, Vec<u8>), Instant>,
+    /// Counters of previous connections, see [`KcpFilter::reset`]
+    stats: KcpStats,
+}
+
+struct Stream {
+    /// Sequence numbers seen, from `acked` on
+    seen: HashSet<u32>,
+    /// Sequence numbers below `acked` that were never seen, which a retransmission may still bring
+    missing: HashSet<u32>,
+    /// Lowest sequence number seen, capturing may start in the middle of a stream
+    first: u32,
+    /// Sequence numbers below this were acknowledged by the other side, see [`Stream::acknowledge`]
+    acked: u32,
+    /// One past the highest sequence number seen
+    next: u32,
+}
+
+impl Stream {
+    fn new(sn: u32) -> Stream {
+        Stream {
+            seen: HashSet::new(),
+            missing: HashSet::new(),
+            first: sn,
+            acked: sn,
+            next: sn,
+        }
+    }
+
+    /// Whether the segment was not seen before, remembering it
+    fn insert(&mut self, sn: u32) -> bool {
+        if sn >= self.acked {
+            self.seen.insert(sn)
+        } else if self.acked == self.first {
+            // sent before capturing started, nothing was acknowledged yet
+            self.first = sn;
+            self.acked = sn;
+            self.seen.insert(sn)
+        } else {
+            self.missing.remove(&sn)
+        }
+    }
+
+    /// Forgets the segments below `una`, which the other side received. Segments sent after the
+    /// highest one seen are left alone, a `una` beyond it only means they were not captured yet
+    fn acknowledge(&mut self, una: u32) {
+        let una = una.min(self.next);
+        for sn in self.acked..una {
+            if !self.seen.remove(&sn) {
+                self.missing.insert(sn);
+            }
+        }
+        self.acked = self.acked.max(una);
+    }
+
+    /// Segments that were never seen, even though a segment sent later was
+    fn lost(&self) -> u64 {
+        self.missing.len() as u64 + (self.next - self.acked) as u64 - self.seen.len() as u64
+    }
+}
+
+/// The header fields of a KCP segment the filter looks at
+struct Segment {
+    conv: u32,
+    cmd: u8,
+    sn: u32,
+    una: u32,
+}
+
+impl KcpFilter {
+    pub fn new() -> KcpFilter {
+        KcpFilter::default()
+    }
+
+    /// Whether an ethernet frame sent from or to `server` should be passed on to the sniffer.
+    ///
+    /// Only frames containing nothing but already seen data segments are rejected,
+    /// anything that cannot be parsed as KCP is passed on untouched.
+    pub fn accept(&mut self, frame: &[u8], server: SocketAddr) -> bool {
+        self.accept_at(frame, server, Instant::now())
+    }
+
+    /// [`KcpFilter::accept`] for a frame captured at `now`
+    fn accept_at(&mut self, frame: &[u8], server: SocketAddr, now: Instant) -> bool {
+        let Some((src, _, payload)) = udp_datagram(frame) else {
+            return true;
+        };
+        if payload.len() == HANDSHAKE_LEN {
+            let seen = self.handshakes.insert((src, payload.to_vec()), now);
+            if seen.is_some_and(|seen| now.duration_since(seen) < DUPLICATE_HANDSHAKE_WINDOW) {
+                trace!("dropped duplicate handshake datagram");
+                self.stats.duplicate_handshakes += 1;
+                return false;
+            }
+            return true;
+        }
+
+        let Some(segments) = segments(payload) else {
+            return true;
+        };
+
+        let from_server = src == server;
+        // datagrams of only acks are passed on
+        let mut new = !segments.iter().any(|segment| segment.cmd == CMD_PUSH);
+
+        for Segment { conv, cmd, sn, una } in segments {
+            if let Some(stream) = self.streams.get_mut(&(!from_server, conv)) {
+                stream.acknowledge(una);
+            }
+            if cmd != CMD_PUSH {
+                continue;
+            }
+
+            let stream = self.streams.entry((from_server, conv)).or_insert_with(|| Stream::new(sn));
+            self.stats.segments += 1;
+
+            if !stream.insert(sn) {
+                self.stats.retransmitted += 1;
+                continue;
+            }
+
+            new = true;
+            if sn < stream.next {
+                self.stats.reordered += 1;
+            } else {
+                stream.next = sn.saturating_add(1);
+            }
+        }
+
+        if !new {
+            trace!("dropped retransmitted datagram");
+        }
+        new
+    }
+
+    /// Forgets the streams of the current connection, to be called when the game reconnects
+    pub fn reset(&mut self) {
+        self.stats = self.stats();
+        self.streams.clear();
+        self.handshakes.clear();
+    }
+
+    /// Counters of all connections so far
+    pub fn stats(&self) -> KcpStats {
+        let mut stats = self.stats;
+        stats.lost += self.streams.values().map(Stream::lost).sum::<u64>();
+        stats
+    }
+}
+
+/// Every segment of a datagram, `None` if the datagram is not made up of KCP segments
+fn segments(payload: &[u8]) -> Option<Vec<Segment>> {
+    let mut segments = vec![];
+    let mut rest = payload;
+
+    while !rest.is_empty() {
+        let header = rest.get(..SEGMENT_HEADER_LEN)?;
+        let len = u32::from_le_bytes(header[24..28].try_into().ok()?) as usize;
+        segments.push(Segment {
+            conv: u32::from_le_bytes(header[0..4].try_into().ok()?),
+            cmd: header[8],
+            sn: u32::from_le_bytes(header[16..20].try_into().ok()?),
+            una: u32::from_le_bytes(header[20..24].try_into().ok()?),
+        });
+
+        rest = rest.get(SEGMENT_HEADER_LEN + len..)?;
+    }
+
+    Some(segments)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::SocketAddrV4;
+
+    use crate::capture::udp_frame;
+
+    const CMD_ACK: u8 = 82;
+    const CONV: u32 = 1;
+
+    fn client() -> SocketAddrV4 {
+        "192.168.1.2:50000".parse().unwrap()
+    }
+
+    fn server() -> SocketAddrV4 {
+        "10.0.0.1:23301".parse().unwrap()
+    }
+
+    fn segment(cmd: u8, sn: u32, una: u32, data: &[u8]) -> Vec<u8> {
+        let mut segment = CONV.to_le_bytes().to_vec();
+        segment.extend_from_slice(&[0; 4]);
+        segment.extend_from_slice(&[cmd, 0, 0, 0]);
+        segment.extend_from_slice(&[0; 4]);
+        segment.extend_from_slice(&sn.to_le_bytes());
+        segment.extend_from_slice(&una.to_le_bytes());
+        segment.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        segment.extend_from_slice(data);
+        segment
+    }
+
+    fn push(sn: u32) -> Vec<u8> {
+        segment(CMD_PUSH, sn, 0, b"data")
+    }
+
+    fn from_client(filter: &mut KcpFilter, payload: &[u8]) -> bool {
+        filter.accept(&udp_frame(client(), server(), payload), server().into())
+    }
+
+    fn from_server(filter: &mut KcpFilter, payload: &[u8]) -> bool {
+        filter.accept(&udp_frame(server(), client(), payload), server().into())
+    }
+
+    #[test]
+    fn drops_retransmitted_datagrams() {
+        let mut filter = KcpFilter::new();
+        assert!(from_client(&mut filter, &push(0)));
+        assert!(from_client(&mut filter, &push(1)));
+        assert!(!from_client(&mut filter, &push(1)));
+        // the same sequence number in the other direction is another stream
+        assert!(from_server(&mut filter, &push(1)));
+
+        let stats = filter.stats();
+        assert_eq!(stats.segments, 4);
+        assert_eq!(stats.retransmitted, 1);
+        assert_eq!(stats.lost, 0);
+    }
+
+    #[test]
+    fn counts_lost_and_reordered_segments() {
+        let mut filter = KcpFilter::new();
+        // capturing started in the middle of the stream
+        assert!(from_client(&mut filter, &push(10)));
+        assert!(from_client(&mut filter, &push(13)));
+        assert_eq!(filter.stats().lost, 2);
+
+        assert!(from_client(&mut filter, &push(12)));
+        assert!(from_client(&mut filter, &push(9)));
+        let stats = filter.stats();
+        assert_eq!(stats.reordered, 2);
+        assert_eq!(stats.lost, 1);
+    }
+
+    #[test]
+    fn accepts_datagrams_with_any_new_segment() {
+        let mut filter = KcpFilter::new();
+        assert!(from_client(&mut filter, &[push(0), push(1)].concat()));
+        assert!(from_client(&mut filter, &[push(1), segment(CMD_ACK, 5, 0, &[]), push(2)].concat()));
+        assert!(!from_client(&mut filter, &[push(2), push(0)].concat()));
+        // acks are passed on, they carry no data to be retransmitted
+        assert!(from_client(&mut filter, &segment(CMD_ACK, 0, 0, &[])));
+
+        let stats = filter.stats();
+        assert_eq!(stats.segments, 6);
+        assert_eq!(stats.retransmitted, 3);
+    }
+
+    #[test]
+    fn passes_on_what_is_not_kcp() {
+        let mut filter = KcpFilter::new();
+        let truncated_header = &push(0)[..SEGMENT_HEADER_LEN - 1];
+        let truncated_data = &push(0)[..SEGMENT_HEADER_LEN + 2];
+        let trailing_bytes = [push(0), vec![0; 3]].concat();
+
+        for payload in [truncated_header, truncated_data, &trailing_bytes[..]] {
+            assert!(from_client(&mut filter, payload));
+            assert!(from_client(&mut filter, payload));
+        }
+        assert!(filter.accept(&[0; 10], server()));
+        assert_eq!(filter.stats(), KcpStats::default());
+    }
+
+    #[test]
+    fn forgets_acknowledged_segments() {
+        let mut filter = KcpFilter::new();
+        for sn in [0, 1, 3, 4] {
+            assert!(from_client(&mut filter, &push(sn)));
+        }
+        assert!(from_server(&mut filter, &segment(CMD_ACK, 0, 5, &[])));
+
+        let stream = &filter.streams[&(false, CONV)];
+        assert!(stream.seen.is_empty());
+        assert_eq!(stream.missing, HashSet::from([2]));
+        assert_eq!(filter.stats().lost, 1);
+
+        // acknowledged segments that were seen are still retransmissions
+        assert!(!from_client(&mut filter, &push(1)));
+        assert!(from_client(&mut filter, &push(2)));
+        assert!(!from_client(&mut filter, &push(2)));
+        assert!(from_client(&mut filter, &push(5)));
+
+        let stats = filter.stats();
+        assert_eq!(stats.retransmitted, 2);
+        assert_eq!(stats.lost, 0);
+    }
+
+    #[test]
+    fn ignores_acknowledgements_beyond_the_stream() {
+        let mut filter = KcpFilter::new();
+        assert!(from_client(&mut filter, &push(0)));
+        assert!(from_server(&mut filter, &push(0)));
+        // the ack of the server covers segments of the client that were not captured yet
+        assert!(from_server(&mut filter, &segment(CMD_ACK, 0, 1000, &[])));
+
+        assert!(from_client(&mut filter, &push(1)));
+        assert_eq!(filter.streams[&(false, CONV)].acked, 1);
+        assert_eq!(filter.stats().lost, 0);
+    }
+
+    #[test]
+    fn drops_copies_of_handshakes_only() {
+        let mut filter = KcpFilter::new();
+        let connect = udp_frame(client(), server(), &[0xff; HANDSHAKE_LEN]);
+        let start = Instant::now();
+
+        assert!(filter.accept_at(&connect, server().into(), start));
+        // captured on a second interface
+        assert!(!filter.accept_at(&connect, server().into(), start + Duration::from_millis(2)));
+        // the game sent it again, as the server did not answer
+        assert!(filter.accept_at(&connect, server().into(), start + Duration::from_millis(500)));
+        assert!(filter.accept_at(&connect, server().into(), start + Duration::from_millis(900)));
+        assert_eq!(filter.stats().duplicate_handshakes, 1);
+
+        filter.reset();
+        assert!(filter.accept_at(&connect, server().into(), start + Duration::from_millis(901)));
+        assert_eq!(filter.stats().duplicate_handshakes, 1);
+    }
+}
@@ -0,0 +1,119 @@
+//! Sources of captured network packets.
+//!
+//! Packets are handed out as raw ethernet frames, ready to be passed to
+//! [`GameSniffer::receive_packet`](reliquary::network::GameSniffer::receive_packet).
+use std::net::{IpAddr, SocketAddr};
+#[cfg(test)]
+use std::net::SocketAddrV4;
+use std::ops::RangeInclusive;
+
+pub mod file;
+pub mod kcp;
+pub mod process;
+pub mod server;
+pub mod source;
+pub mod stats;
+#[cfg(all(windows, feature = "windivert"))]
+pub mod windivert;
+
+/// UDP ports the game server of the global region communicates on.
+/// Servers of other regions are detected by [`server::ServerFilter`]
+pub const GAME_PORTS: RangeInclusive<u16> = 23301..=23302;
+
+const ETHERTYPE_IPV4: u16 = 0x0800;
+const ETHERTYPE_IPV6: u16 = 0x86dd;
+const IP_PROTOCOL_UDP: u8 = 17;
+
+/// Whether an ethernet frame contains a UDP datagram sent from or to one of the [`GAME_PORTS`]
+pub fn is_game_packet(frame: &[u8]) -> bool {
+    game_server(frame).is_some()
+}
+
+/// Whether an ethernet frame contains a UDP datagram
+pub fn is_udp_packet(frame: &[u8]) -> bool {
+    udp_datagram(frame).is_some()
+}
+
+/// Address of the game server an ethernet frame was sent from or to,
+/// `None` if the frame is not a UDP datagram on one of the [`GAME_PORTS`]
+pub fn game_server(frame: &[u8]) -> Option<SocketAddr> {
+    let (src, dst, _) = udp_datagram(frame)?;
+
+    if GAME_PORTS.contains(&src.port()) {
+        Some(src)
+    } else if GAME_PORTS.contains(&dst.port()) {
+        Some(dst)
+    } else {
+        None
+    }
+}
+
+/// Source, destination and payload of the UDP datagram contained in an ethernet frame
+fn udp_datagram(frame: &[u8]) -> Option<(SocketAddr, SocketAddr, &[u8])> {
+    let ethertype = frame.get(12..14)?;
+
+    let (src, dst, udp): (IpAddr, IpAddr, &[u8]) = match u16::from_be_bytes([ethertype[0], ethertype[1]]) {
+        ETHERTYPE_IPV4 => {
+            let ip = frame.get(14..)?;
+            if *ip.get(9)? != IP_PROTOCOL_UDP {
+                return None;
+            }
+            let header_len = (ip[0] & 0x0f) as usize * 4;
+            let src: [u8; 4] = ip.get(12..16)?.try_into().ok()?;
+            let dst: [u8; 4] = ip.get(16..20)?.try_into().ok()?;
+            (src.into(), dst.into(), ip.get(header_len..)?)
+        }
+        ETHERTYPE_IPV6 => {
+            let ip = frame.get(14..)?;
+            // extension headers are not supported, the game does not use them
+            if *ip.get(6)? != IP_PROTOCOL_UDP {
+                return None;
+            }
+            let src: [u8; 16] = ip.get(8..24)?.try_into().ok()?;
+            let dst: [u8; 16] = ip.get(24..40)?.try_into().ok()?;
+            (src.into(), dst.into(), ip.get(40..)?)
+        }
+        _ => return None,
+    };
+
+    let ports = udp.get(0..4)?;
+    Some((
+        SocketAddr::new(src, u16::from_be_bytes([ports[0], ports[1]])),
+        SocketAddr::new(dst, u16::from_be_bytes([ports[2], ports[3]])),
+        udp.get(8..)?,
+    ))
+}
+
+/// Prepends a fake ethernet header to a raw IP packet
+fn ip_to_ethernet(ip: &[u8]) -> Option<Vec<u8>> {
+    let ethertype = match ip.first()? >> 4 {
+        4 => ETHERTYPE_IPV4,
+        6 => ETHERTYPE_IPV6,
+        _ => return None,
+    };
+
+    Some(with_ethernet_header(ethertype, ip))
+}
+
+fn with_ethernet_header(ethertype: u16, payload: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(14 + payload.len());
+    // source and destination mac addresses are irrelevant to the sniffer
+    frame.extend_from_slice(&[0; 12]);
+    frame.extend_from_slice(&ethertype.to_be_bytes());
+    frame.extend_from_slice(payload);
+    frame
+}
+
+/// An ethernet frame of a UDP datagram over IPv4, as the sources hand them out
+#[cfg(test)]
+pub(crate) fn udp_frame(src: SocketAddrV4, dst: SocketAddrV4, payload: &[u8]) -> Vec<u8> {
+    // version and header length, the length fields are not checked
+    let mut packet = vec![0x45, 0, 0, 0, 0, 0, 0, 0, 64, IP_PROTOCOL_UDP, 0, 0];
+    packet.extend_from_slice(&src.ip().octets());
+    packet.extend_from_slice(&dst.ip().octets());
+    packet.extend_from_slice(&src.port().to_be_bytes());
+    packet.extend_from_slice(&dst.port().to_be_bytes());
+    packet.extend_from_slice(&[0; 4]);
+    packet.extend_from_slice(payload);
+    with_ethernet_header(ETHERTYPE_IPV4, &packet)
+}
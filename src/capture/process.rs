@@ -0,0 +1,60 @@
+//! Detection of the running game client.
+use std::time::{Duration, Instant};
+
+use sysinfo::System;
+
+/// Names of the game client process. The windows executable keeps its name when run through wine
+const GAME_PROCESS_NAMES: [&str; 1] = ["StarRail.exe"];
+/// How often [`GameExitWatch`] looks for the game process
+const EXIT_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Whether the game client is currently running on this machine
+pub fn is_game_running() -> bool {
+    let mut system = System::new();
+    system.refresh_processes();
+    game_running(&system)
+}
+
+fn game_running(system: &System) -> bool {
+    GAME_PROCESS_NAMES.iter()
+        .any(|name| system.processes_by_exact_name(name).next().is_some())
+}
+
+/// Notices the game client exiting after it was seen running
+pub struct GameExitWatch {
+    system: System,
+    seen_running: bool,
+    last_check: Option<Instant>,
+}
+
+impl GameExitWatch {
+    pub fn new() -> GameExitWatch {
+        GameExitWatch {
+            system: System::new(),
+            seen_running: false,
+            last_check: None,
+        }
+    }
+
+    /// Whether the game was running before, but is not anymore.
+    /// Cheap to call often, the processes are only looked at every few seconds
+    pub fn has_exited(&mut self) -> bool {
+        if self.last_check.is_some_and(|last_check| last_check.elapsed() < EXIT_CHECK_INTERVAL) {
+            return false;
+        }
+        self.last_check = Some(Instant::now());
+
+        self.system.refresh_processes();
+        let running = game_running(&self.system);
+
+        let exited = self.seen_running && !running;
+        self.seen_running |= running;
+        exited
+    }
+}
+
+impl Default for GameExitWatch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
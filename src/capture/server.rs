@@ -0,0 +1,249 @@
+//! Detection of the game server among all captured UDP traffic.
+//!
+//! Servers of other regions do not necessarily use the [`GAME_PORTS`], so instead of relying
+//! on the ports, the server is identified from the handshake the game starts every
+//! connection with.
+use std::collections::HashSet;
+use std::net::SocketAddr;
+use std::ops::RangeInclusive;
+
+use tracing::{debug, info};
+
+use crate::capture::{udp_datagram, GAME_PORTS};
+
+/// Size of the handshake datagrams
+const HANDSHAKE_LEN: usize = 20;
+/// Magic at the start and end of the handshake the client sends to connect
+const CONNECT_MAGIC: ([u8; 4], [u8; 4]) = ([0x00, 0x00, 0x00, 0xff], [0xff, 0xff, 0xff, 0xff]);
+/// Magic at the start and end of the handshake the server answers a connect with
+const ESTABLISH_MAGIC: ([u8; 4], [u8; 4]) = ([0x00, 0x00, 0x01, 0x45], [0x14, 0x51, 0x45, 0x45]);
+
+/// Server ports of the known regions
+const REGIONS: &[(&str, RangeInclusive<u16>)] = &[("global", GAME_PORTS)];
+
+/// Name of the region a game server belongs to, judging by its port
+pub fn region(server: SocketAddr) -> Option<&'static str> {
+    REGIONS.iter()
+        .find(|(_, ports)| ports.contains(&server.port()))
+        .map(|(name, _)| *name)
+}
+
+/// Keeps track of the game server the game is connected to
+#[derive(Default)]
+pub struct ServerFilter {
+    server: Option<SocketAddr>,
+}
+
+impl ServerFilter {
+    pub fn new() -> ServerFilter {
+        ServerFilter::default()
+    }
+
+    /// Game server the game is connected to, if any was detected yet
+    pub fn server(&self) -> Option<SocketAddr> {
+        self.server
+    }
+
+    /// Address of the game server if an ethernet frame was sent from or to it,
+    /// `None` if the frame is other traffic.
+    ///
+    /// A handshake switches to the server it was sent from or to. Until the first
+    /// handshake is seen, traffic on the [`GAME_PORTS`] is assumed to be from the game.
+    pub fn filter(&mut self, frame: &[u8]) -> Option<SocketAddr> {
+        let (src, dst, payload) = udp_datagram(frame)?;
+
+        if let Some(server) = handshake_server(src, dst, payload) {
+            if self.server != Some(server) {
+                debug!("detected handshake with {server}");
+                self.server = Some(server);
+            }
+            return Some(server);
+        }
+
+        match self.server {
+            Some(server) if src == server || dst == server => Some(server),
+            Some(_) => None,
+            None if GAME_PORTS.contains(&src.port()) => Some(src),
+            None if GAME_PORTS.contains(&dst.port()) => Some(dst),
+            None => None,
+        }
+    }
+}
+
+/// Address of the game server if a UDP payload is a handshake
+fn handshake_server(src: SocketAddr, dst: SocketAddr, payload: &[u8]) -> Option<SocketAddr> {
+    if payload.len() != HANDSHAKE_LEN {
+        return None;
+    }
+
+    let magic = (payload[..4].try_into().ok()?, payload[16..].try_into().ok()?);
+    if magic == CONNECT_MAGIC {
+        Some(dst)
+    } else if magic == ESTABLISH_MAGIC {
+        Some(src)
+    } else {
+        None
+    }
+}
+
+/// Connection between a game client and a game server
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Connection {
+    pub server: SocketAddr,
+    pub client: SocketAddr,
+}
+
+/// Keeps track of the connections of all game clients, for multiple clients running at the same time.
+///
+/// Unlike [`ServerFilter`], a handshake adds a connection instead of replacing the previous one.
+#[derive(Default)]
+pub struct ConnectionTracker {
+    connections: HashSet<Connection>,
+}
+
+impl ConnectionTracker {
+    pub fn new() -> ConnectionTracker {
+        ConnectionTracker::default()
+    }
+
+    /// Connection an ethernet frame belongs to, `None` if the frame is other traffic.
+    ///
+    /// Traffic on the [`GAME_PORTS`] is assumed to be from the game even without a handshake.
+    pub fn track(&mut self, frame: &[u8]) -> Option<Connection> {
+        let (src, dst, payload) = udp_datagram(frame)?;
+
+        if let Some(server) = handshake_server(src, dst, payload) {
+            let client = if server == src { dst } else { src };
+            let connection = Connection { server, client };
+            if self.connections.insert(connection) {
+                debug!("detected handshake between {client} and {server}");
+            }
+            return Some(connection);
+        }
+
+        let known = self.connections.iter()
+            .find(|c| (c.server == src && c.client == dst) || (c.server == dst && c.client == src));
+        if let Some(connection) = known {
+            return Some(*connection);
+        }
+
+        if GAME_PORTS.contains(&src.port()) {
+            Some(Connection { server: src, client: dst })
+        } else if GAME_PORTS.contains(&dst.port()) {
+            Some(Connection { server: dst, client: src })
+        } else {
+            None
+        }
+    }
+}
+
+/// Logs the game server that was detected, with its region if known
+pub fn log_server(server: SocketAddr) {
+    match region(server) {
+        Some(region) => info!("detected game server {server} ({region} region)"),
+        None => info!("detected game server {server} (unknown region)"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddrV4;
+
+    use super::*;
+    use crate::capture::udp_frame;
+
+    fn address(address: &str) -> SocketAddrV4 {
+        address.parse().unwrap()
+    }
+
+    fn handshake((start, end): ([u8; 4], [u8; 4])) -> Vec<u8> {
+        [&start[..], &[0x12; 12][..], &end[..]].concat()
+    }
+
+    #[test]
+    fn detects_server_of_handshakes() {
+        let client = address("192.168.1.2:50000").into();
+        let server = address("10.0.0.1:22101").into();
+
+        assert_eq!(handshake_server(client, server, &handshake(CONNECT_MAGIC)), Some(server));
+        assert_eq!(handshake_server(server, client, &handshake(ESTABLISH_MAGIC)), Some(server));
+
+        // other datagrams of the same size, and handshakes of the wrong size
+        assert_eq!(handshake_server(client, server, &[0; HANDSHAKE_LEN]), None);
+        assert_eq!(handshake_server(client, server, &handshake(CONNECT_MAGIC)[..HANDSHAKE_LEN - 1]), None);
+        assert_eq!(handshake_server(client, server, &[handshake(CONNECT_MAGIC), vec![0]].concat()), None);
+    }
+
+    #[test]
+    fn filters_by_game_ports_until_a_handshake() {
+        let mut filter = ServerFilter::new();
+        let client = address("192.168.1.2:50000");
+        let game = address("10.0.0.1:23301");
+        let other = address("10.0.0.2:443");
+
+        assert_eq!(filter.filter(&udp_frame(client, game, b"data")), Some(game.into()));
+        assert_eq!(filter.filter(&udp_frame(game, client, b"data")), Some(game.into()));
+        assert_eq!(filter.filter(&udp_frame(client, other, b"data")), None);
+        assert_eq!(filter.filter(&[0; 10]), None);
+        assert_eq!(filter.server(), None);
+    }
+
+    #[test]
+    fn switches_server_on_handshakes() {
+        let mut filter = ServerFilter::new();
+        let client = address("192.168.1.2:50000");
+        let first = address("10.0.0.1:22101");
+        let second = address("10.0.0.3:22102");
+        let game_ports = address("10.0.0.4:23301");
+
+        assert_eq!(filter.filter(&udp_frame(client, first, &handshake(CONNECT_MAGIC))), Some(first.into()));
+        assert_eq!(filter.server(), Some(first.into()));
+        assert_eq!(filter.filter(&udp_frame(first, client, b"data")), Some(first.into()));
+        // once the server is known, the game ports do not matter anymore
+        assert_eq!(filter.filter(&udp_frame(client, game_ports, b"data")), None);
+
+        assert_eq!(filter.filter(&udp_frame(second, client, &handshake(ESTABLISH_MAGIC))), Some(second.into()));
+        assert_eq!(filter.server(), Some(second.into()));
+        assert_eq!(filter.filter(&udp_frame(first, client, b"data")), None);
+        assert_eq!(filter.filter(&udp_frame(client, second, b"data")), Some(second.into()));
+    }
+
+    #[test]
+    fn tracks_connections_of_every_client() {
+        let mut tracker = ConnectionTracker::new();
+        let (first_server, first_client) = (address("10.0.0.1:22101"), address("192.168.1.2:50000"));
+        let (second_server, second_client) = (address("10.0.0.3:22102"), address("192.168.1.3:50001"));
+        let first = Connection {
+            server: first_server.into(),
+            client: first_client.into(),
+        };
+        let second = Connection {
+            server: second_server.into(),
+            client: second_client.into(),
+        };
+
+        assert_eq!(tracker.track(&udp_frame(first_client, first_server, &handshake(CONNECT_MAGIC))), Some(first));
+        assert_eq!(tracker.track(&udp_frame(second_server, second_client, &handshake(ESTABLISH_MAGIC))), Some(second));
+
+        // both stay tracked, unlike with a server filter
+        assert_eq!(tracker.track(&udp_frame(first_server, first_client, b"data")), Some(first));
+        assert_eq!(tracker.track(&udp_frame(second_client, second_server, b"data")), Some(second));
+        // a client talking to the server of another connection is not part of it
+        assert_eq!(tracker.track(&udp_frame(first_client, second_server, b"data")), None);
+    }
+
+    #[test]
+    fn tracks_game_ports_without_handshake() {
+        let mut tracker = ConnectionTracker::new();
+        let client = address("192.168.1.2:50000");
+        let server = address("10.0.0.1:23302");
+
+        let connection = Connection {
+            server: server.into(),
+            client: client.into(),
+        };
+        assert_eq!(tracker.track(&udp_frame(client, server, b"data")), Some(connection));
+        assert_eq!(tracker.track(&udp_frame(server, client, b"data")), Some(connection));
+        assert_eq!(tracker.track(&udp_frame(client, address("10.0.0.2:443"), b"data")), None);
+    }
+}
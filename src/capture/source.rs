@@ -0,0 +1,49 @@
+//! Sources a [`Session`] reads from, so capture backends do not need to know about exporters.
+//!
+//! Every iterator of ethernet frames is a source, which covers live captures, replayed capture
+//! files and remote streams (see [`CaptureFile`]). Commands that were decrypted before, like
+//! the commands of a fixture or a debug dump, are fed in with a [`CommandSource`].
+//!
+//! [`Session`]: crate::session::Session
+//! [`CaptureFile`]: crate::capture::file::CaptureFile
+use reliquary::network::GameCommand;
+
+/// Data handed out by a [`PacketSource`]
+pub enum Captured {
+    /// Ethernet frame captured on the network, still to be decrypted
+    Frame(Vec<u8>),
+    /// Command that was decrypted already
+    Command(GameCommand),
+}
+
+/// Source of the data of a capture
+pub trait PacketSource {
+    /// Next captured frame or command, waiting until one is available.
+    /// `None` once the source is exhausted or stopped capturing
+    fn next_captured(&mut self) -> Option<Captured>;
+}
+
+impl<I: Iterator<Item = Vec<u8>>> PacketSource for I {
+    fn next_captured(&mut self) -> Option<Captured> {
+        self.next().map(Captured::Frame)
+    }
+}
+
+/// Source of commands that were decrypted before, e.g. loaded from a fixture or a debug dump
+pub struct CommandSource {
+    commands: std::vec::IntoIter<GameCommand>,
+}
+
+impl From<Vec<GameCommand>> for CommandSource {
+    fn from(commands: Vec<GameCommand>) -> Self {
+        CommandSource {
+            commands: commands.into_iter(),
+        }
+    }
+}
+
+impl PacketSource for CommandSource {
+    fn next_captured(&mut self) -> Option<Captured> {
+        self.commands.next().map(Captured::Command)
+    }
+}
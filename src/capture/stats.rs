@@ -0,0 +1,63 @@
+//! Statistics of a capture session, to diagnose captures that did not collect anything.
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use serde::Serialize;
+use tracing::{debug, info};
+
+use crate::capture::kcp::KcpStats;
+
+/// Counters of what happened to the packets of a capture session
+#[derive(Serialize, Debug, Default, Clone)]
+pub struct SessionStats {
+    /// How long the session took in seconds
+    pub duration: f64,
+    /// Packets captured, including other traffic
+    pub packets: u64,
+    /// Packets sent from or to the game server
+    pub game_packets: u64,
+    /// Game packets that were decrypted and contained commands
+    pub decrypted: u64,
+    /// Game packets that could not be decrypted, usually because capturing started too late
+    pub undecryptable: u64,
+    /// Connections to the game server
+    pub connections: u64,
+    /// Parsed commands by command id
+    pub commands: BTreeMap<u16, u64>,
+    pub kcp: KcpStats,
+}
+
+impl SessionStats {
+    pub fn set_duration(&mut self, duration: Duration) {
+        self.duration = duration.as_secs_f64();
+    }
+
+    pub fn add_command(&mut self, command_id: u16) {
+        *self.commands.entry(command_id).or_default() += 1;
+    }
+
+    /// Logs a summary, with the counts of every command on debug level
+    pub fn log(&self) {
+        info!(
+            duration = format!("{:.0}s", self.duration),
+            packets = self.packets,
+            game_packets = self.game_packets,
+            decrypted = self.decrypted,
+            undecryptable = self.undecryptable,
+            connections = self.connections,
+            commands = self.commands.values().sum::<u64>(),
+            "session statistics"
+        );
+        info!(
+            segments = self.kcp.segments,
+            retransmitted = self.kcp.retransmitted,
+            reordered = self.kcp.reordered,
+            lost = self.kcp.lost,
+            duplicate_handshakes = self.kcp.duplicate_handshakes,
+            "packet statistics"
+        );
+        for (command_id, count) in &self.commands {
+            debug!(command_id, count, "parsed commands");
+        }
+    }
+}
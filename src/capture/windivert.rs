@@ -0,0 +1,50 @@
+//! Live capture using [WinDivert], an alternative to Npcap on Windows.
+//!
+//! WinDivert ships its driver alongside the program, so nothing has to be installed,
+//! but the program has to be run as administrator to load it.
+//!
+//! [WinDivert]: https://reqrypt.org/windivert.html
+use std::sync::mpsc;
+
+use tracing::{debug, trace, warn};
+use windivert::prelude::*;
+
+use crate::capture::ip_to_ethernet;
+use crate::error::Result;
+
+/// Captures the UDP traffic of all interfaces, which the game traffic is part of, sending the packets as ethernet frames to `tx`
+/// until the receiving end goes away or capturing fails.
+///
+/// Packets are only sniffed, they still reach the game unchanged.
+/// Fails if the WinDivert driver cannot be loaded, e.g. when not running as administrator.
+pub fn capture(tx: mpsc::Sender<Vec<u8>>) -> Result<()> {
+    // the server port depends on the region, see the server module
+    let handle = WinDivert::network("udp", 0, WinDivertFlags::new().set_sniff().set_recv_only())?;
+
+    debug!("listening");
+
+    let mut buffer = vec![0; 65535];
+    loop {
+        let packet = match handle.recv(Some(&mut buffer)) {
+            Ok(packet) => packet,
+            Err(e) => {
+                warn!(%e, "could not receive packet");
+                break;
+            }
+        };
+
+        trace!("captured packet");
+        // the network layer hands out IP packets without a link layer header
+        let Some(frame) = ip_to_ethernet(&packet.data) else {
+            continue;
+        };
+
+        if let Err(e) = tx.send(frame) {
+            debug!("channel closed: {e}");
+            break;
+        }
+    }
+
+    debug!("stop listening");
+    Ok(())
+}
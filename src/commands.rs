@@ -0,0 +1,437 @@
+//! The commands that capture or write exports, shared by the terminal, the tray icon and the
+//! dashboard, with the options given on the command line.
+use std::fs::File;
+use std::io::{BufWriter, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::sync::{mpsc, Arc};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use serde_json::Value;
+use tracing::{error, info, warn};
+
+use completionist_archiver::capture::source::CommandSource;
+use completionist_archiver::database::Database;
+use completionist_archiver::error::{ArchiverError, PathContext};
+use completionist_archiver::export::boxed::BoxedExporter;
+use completionist_archiver::export::command_ids::CommandIds;
+use completionist_archiver::export::dump::{self, DumpWriter};
+use completionist_archiver::export::field_mappings::FieldMapping;
+use completionist_archiver::export::fixture;
+use completionist_archiver::export::fribbels;
+use completionist_archiver::export::merge;
+use completionist_archiver::export::output::{self, OnConflict};
+use completionist_archiver::export::registry::{ExporterContext, ExporterRegistry};
+use completionist_archiver::export::{Event, Exporter};
+use completionist_archiver::filter::ItemFilter;
+use completionist_archiver::server::{http, websocket, CaptureState, SharedCaptureState};
+use completionist_archiver::session::{run_instances, FinishReason, Session, SessionOptions, SessionReport};
+
+use crate::i18n::t;
+use crate::source::{file_source, live_source};
+use crate::summary::Summary;
+use crate::{guide, read_line, report_error, report_suppressed_warnings, stall, warning_totals, Args, Command, CAPTURING, STOP_REQUESTED};
+#[cfg(feature = "notify")]
+use crate::notify;
+
+/// What [`capture_and_export`] wrote
+pub struct Captured {
+    pub written: Vec<PathBuf>,
+    /// Why the capture did not collect everything, although the exports were written
+    pub problem: Option<ArchiverError>,
+    pub summary: Summary,
+}
+
+/// Captures and writes the export, returning the paths of the written files.
+///
+/// The progress of the capture is published to `shared_state`, and the detected items are sent
+/// to `events` or the websocket, if set.
+pub fn capture_and_export(
+    args: &Args,
+    database: Database,
+    shared_state: Option<SharedCaptureState>,
+    events: Option<mpsc::Sender<Event>>,
+) -> Result<Captured, ArchiverError> {
+    let command_ids = load_command_ids(args)?;
+    let previous = load_previous_export(args)?;
+
+    let events = events.or_else(|| args.session().websocket.and_then(|address| match websocket::spawn(address) {
+        Ok(events) => Some(events),
+        Err(e) => {
+            error!(%e, "could not start websocket server on {address}");
+            None
+        }
+    }));
+
+    let mut options = SessionOptions {
+        finish: args.finish_criteria(),
+        shared_state,
+        command_ids,
+        stop: Some(&STOP_REQUESTED),
+        stream: open_stream(args)?,
+        ..Default::default()
+    };
+
+    if let Some(path) = &args.session().debug_dump {
+        match DumpWriter::create(path, args.session().redact) {
+            Ok(writer) => options.dump = Some(writer),
+            Err(e) => error!(%e, "could not create debug dump {}", path.display()),
+        }
+    }
+
+    if let Some(address) = args.session().http {
+        let state = options.shared_state.get_or_insert_with(SharedCaptureState::default);
+        if let Err(e) = http::spawn(address, state.clone()) {
+            error!(%e, "could not start http server on {address}");
+        }
+    }
+
+    let (file, speed) = match &args.command {
+        Some(Command::Replay { file, speed, .. }) => (Some(file), *speed),
+        _ => (args.live().pcap.as_ref(), None),
+    };
+
+    let packets: Box<dyn Iterator<Item = Vec<u8>> + Send> = match file {
+        Some(file) => Box::new(file_source(file, speed)?),
+        None => Box::new(live_source(args, &mut options)?),
+    };
+    // the output is only rewritten while capturing live
+    let watch = (file.is_none() && args.live().watch).then(|| {
+        // the uid is not known yet, and the exports of all formats are written together
+        let rendered = output::render(&args.output().output, None);
+        Watch {
+            output: output::formatted(&rendered, "all").unwrap_or(rendered),
+            interval: Duration::from_secs(args.live().watch_interval * 60),
+            last_write: None,
+            last_json: None,
+        }
+    });
+
+    let keys = database.keys().clone();
+    let context = ExporterContext {
+        database: Arc::new(database),
+        command_ids,
+        field_mappings: load_field_mappings(args)?,
+        completed: args.completed_statuses(),
+        filters: args.export().only.clone(),
+        events,
+        uid: args.export().uid,
+    };
+    let registry = ExporterRegistry::with_builtin();
+
+    let alerted_state = (args.notifies() || args.session().sound)
+        .then(|| options.shared_state.get_or_insert_with(SharedCaptureState::default).clone());
+    let guided_state = (file.is_none() && args.guides())
+        .then(|| options.shared_state.get_or_insert_with(SharedCaptureState::default).clone());
+
+    CAPTURING.store(true, Ordering::Relaxed);
+    if let Some(state) = guided_state {
+        guide::spawn(state);
+    }
+    if let Some(state) = &alerted_state {
+        let (notify, sound) = (args.notifies(), args.session().sound);
+        stall::spawn_watch(state.clone(), move |state| alert_stalled(notify, sound, state));
+    }
+    let mut report = None;
+    let exports = if args.session().multi_instance {
+        let create = || create_exporter(&registry, &args.output().format, &context);
+        run_instances(create, &keys, packets, options)
+    } else {
+        let mut session = Session::new(create_exporter(&registry, &args.output().format, &context), keys).with_options(options);
+        if let Some(mut watch) = watch {
+            session = session.on_update(move |exporter| {
+                if let Err(e) = watch.update(exporter) {
+                    // the next update tries again
+                    report_error(&e);
+                }
+            });
+        }
+
+        let outcome = session.run(packets);
+        if let Some(path) = &args.session().stats {
+            output::write_json(path, &outcome.report.stats)?;
+            info!("wrote statistics to {}", path.display());
+        }
+        if let Some(path) = &args.export().report {
+            output::write_json(path, &outcome.report)?;
+            info!("wrote report to {}", path.display());
+        }
+        report = Some(outcome.report);
+        outcome.exports
+    };
+    CAPTURING.store(false, Ordering::Relaxed);
+    report_suppressed_warnings();
+
+    let summary = Summary::new(&exports, &args.output().format, &context.database, report.as_ref());
+    let uid = exports.first().and_then(output::export_uid);
+    let written = write_exports(args, &context.database, exports, previous.as_ref());
+    if let Some(state) = &alerted_state {
+        alert_exported(args, &written, state);
+    }
+    let written = written?;
+    let summary = summary.with_written(&written, args.output().write.dry_run).with_warnings(warning_totals());
+    if let Some(path) = &args.session().run_summary {
+        let path = output::render(path, uid);
+        summary.write(&path)?;
+        info!("wrote run summary to {}", path.display());
+    }
+    Ok(Captured {
+        summary,
+        problem: report.as_ref().and_then(capture_problem),
+        written,
+    })
+}
+
+/// Why a session did not collect everything, most fundamental problem first
+fn capture_problem(report: &SessionReport) -> Option<ArchiverError> {
+    if report.stats.game_packets == 0 {
+        Some(ArchiverError::NoGameTraffic)
+    } else if report.finish_reason == FinishReason::MissingKeys {
+        Some(ArchiverError::OutdatedKeys)
+    } else if report.stats.decrypted == 0 && report.stats.undecryptable > 0 {
+        Some(ArchiverError::MissingKeys)
+    } else if !report.missing.is_empty() {
+        Some(ArchiverError::Incomplete(report.missing.clone()))
+    } else {
+        None
+    }
+}
+
+/// Alerts that nothing new was collected for a while, with the alerts enabled on the command line
+fn alert_stalled(notify: bool, sound: bool, state: &CaptureState) {
+    #[cfg(feature = "notify")]
+    if notify {
+        notify::stalled(state);
+    }
+    #[cfg(not(feature = "notify"))]
+    let _ = (notify, state);
+
+    if sound {
+        chime();
+    }
+}
+
+/// Alerts that the exports were written, with the alerts enabled on the command line
+fn alert_exported(args: &Args, written: &Result<Vec<PathBuf>, ArchiverError>, state: &SharedCaptureState) {
+    #[cfg(feature = "notify")]
+    if args.session().notify {
+        notify::exported(written, state);
+    }
+    #[cfg(not(feature = "notify"))]
+    let _ = (written, state);
+
+    if args.session().sound {
+        chime();
+    }
+}
+
+/// Rings the bell of the terminal, which plays the alert sound of the system on windows.
+/// Written to stderr, as stdout is kept for the export with --quiet
+fn chime() {
+    let mut stderr = std::io::stderr();
+    let _ = stderr.write_all(b"\x07").and_then(|()| stderr.flush());
+}
+
+/// Reads the commands of a fixture into an exporter and writes the export
+pub fn inject(args: &Args, database: Database, fixture: &Path) -> Result<(), ArchiverError> {
+    let commands = if fixture.extension().is_some_and(|extension| extension == "bin") {
+        dump::load(fixture)?
+    } else {
+        fixture::load(fixture)?
+    };
+
+    info!(num = commands.len(), "injecting commands from {}", fixture.display());
+
+    let command_ids = load_command_ids(args)?;
+    let previous = load_previous_export(args)?;
+
+    let keys = database.keys().clone();
+    let context = ExporterContext {
+        command_ids,
+        field_mappings: load_field_mappings(args)?,
+        completed: args.completed_statuses(),
+        filters: args.export().only.clone(),
+        uid: args.export().uid,
+        ..ExporterContext::new(database)
+    };
+    let exporter = create_exporter(&ExporterRegistry::with_builtin(), &args.output().format, &context);
+    // every command of the fixture is read, even once the exporter is finished
+    let options = SessionOptions {
+        keep_going: true,
+        command_ids,
+        stream: open_stream(args)?,
+        ..Default::default()
+    };
+
+    let outcome = Session::new(exporter, keys).with_options(options).run(CommandSource::from(commands));
+    report_suppressed_warnings();
+    if let Some(path) = &args.export().report {
+        output::write_json(path, &outcome.report)?;
+        info!("wrote report to {}", path.display());
+    }
+    write_exports(args, &context.database, outcome.exports, previous.as_ref()).map(|_| ())
+}
+
+/// File given with --stream to flush the exporters to
+fn open_stream(args: &Args) -> Result<Option<Box<dyn Write + Send>>, ArchiverError> {
+    let Some(path) = &args.export().stream else {
+        return Ok(None);
+    };
+    if !args.output().format.iter().any(|format| format == "ndjson") {
+        warn!("nothing is streamed to {}, as --format does not include ndjson", path.display());
+    }
+
+    let file = File::create(path).with_path("create", path)?;
+    Ok(Some(Box::new(BufWriter::new(file))))
+}
+
+/// Converts an earlier export to the formats given on the command line and writes it
+pub fn convert(args: &Args, database: Database, path: &Path, only: &[ItemFilter]) -> Result<(), ArchiverError> {
+    let export = fribbels::Export::load(path)?;
+    info!(version = export.version, uid = export.metadata.uid, "converting {}", path.display());
+    let export = export.upgrade(&database);
+
+    let context = ExporterContext {
+        filters: only.to_vec(),
+        ..ExporterContext::new(database)
+    };
+    let account = export.to_account(&context.database);
+    let mut exporter = create_exporter(&ExporterRegistry::with_builtin(), &args.output().format, &context);
+    exporter.restore(&account);
+
+    write_exports(args, &context.database, vec![exporter.export()], None).map(|_| ())
+}
+
+/// Merges exports into one, see [`merge::merge_files`]
+pub fn merge(args: &Args, database: &Database, paths: &[PathBuf]) -> Result<(), ArchiverError> {
+    let merged = merge::merge_files(paths, database)?;
+
+    let outputs = args.outputs();
+    let path = outputs.path("fribbels", merged.metadata.uid.map(u64::from));
+    outputs.write(database, &path, &serde_json::to_value(&merged)?, "fribbels", &format!("{} merged exports", paths.len()))?;
+    print_written(args, &[path]);
+    Ok(())
+}
+
+/// Command ids with the overrides given on the command line
+fn load_command_ids(args: &Args) -> Result<CommandIds, ArchiverError> {
+    match &args.export().command_ids {
+        Some(path) => CommandIds::load_overrides(path),
+        None => Ok(CommandIds::default()),
+    }
+}
+
+/// Field numbers of other game versions given on the command line
+fn load_field_mappings(args: &Args) -> Result<Vec<FieldMapping>, ArchiverError> {
+    match &args.export().field_mappings {
+        Some(path) => FieldMapping::load(path),
+        None => Ok(vec![]),
+    }
+}
+
+/// Export given to merge into on the command line, if any
+fn load_previous_export(args: &Args) -> Result<Option<fribbels::Export>, ArchiverError> {
+    let Some(path) = &args.export().merge else {
+        return Ok(None);
+    };
+
+    if !args.output().format.iter().any(|format| format == "fribbels") {
+        warn!("--merge only applies to the fribbels format, nothing will be merged");
+    }
+
+    let previous = fribbels::Export::load(path)?;
+    info!(uid = previous.metadata.uid, "merging into {}", path.display());
+    Ok(Some(previous))
+}
+
+/// Creates the exporter for the formats given on the command line,
+/// which are checked to be registered when parsing the arguments
+fn create_exporter(registry: &ExporterRegistry, formats: &[String], context: &ExporterContext) -> BoxedExporter {
+    BoxedExporter(registry.create_all(formats, context).expect("formats are validated by clap"))
+}
+
+/// Writes the exports of all accounts where the command line says, returning the paths of
+/// the written files. With --quiet the paths are printed instead, as they are not logged
+fn write_exports(
+    args: &Args,
+    database: &Database,
+    exports: Vec<Value>,
+    previous: Option<&fribbels::Export>,
+) -> Result<Vec<PathBuf>, ArchiverError> {
+    let written = args.outputs().write_all(database, exports, previous)?;
+    print_written(args, &written);
+    Ok(written)
+}
+
+/// Prints the paths of the written files with --quiet, one per line, for scripts
+fn print_written(args: &Args, written: &[PathBuf]) {
+    if args.quiet {
+        for path in written {
+            println!("{}", path.display());
+        }
+    }
+}
+
+/// Shows the first of the written files in the file manager, the others are next to it
+pub fn reveal(written: &[PathBuf]) {
+    let Some(path) = written.first() else {
+        return;
+    };
+    if let Err(e) = opener::reveal(path) {
+        warn!(%e, "could not show {} in the file manager", path.display());
+    }
+}
+
+/// Whether the user can be asked what to do about an existing output
+pub fn can_prompt(args: &Args) -> bool {
+    std::io::stdin().is_terminal() && !args.live().unattended && !args.quiet && !args.shows_dashboard()
+}
+
+pub fn prompt_conflict(path: &Path) -> OnConflict {
+    loop {
+        print!("{} [b] ", t!("conflict-prompt", path = path.display().to_string()));
+        let _ = std::io::stdout().flush();
+
+        match read_line().as_deref() {
+            Some("o") => return OnConflict::Overwrite,
+            Some("b" | "") | None => return OnConflict::Backup,
+            Some("m") => return OnConflict::Merge,
+            Some(_) => {}
+        }
+    }
+}
+
+/// Rewrites the output file in watch mode whenever the collected data changes
+struct Watch {
+    output: PathBuf,
+    /// Minimum time between rewrites
+    interval: Duration,
+    last_write: Option<Instant>,
+    last_json: Option<String>,
+}
+
+impl Watch {
+    fn update<E>(&mut self, exporter: &E) -> Result<(), ArchiverError>
+    where
+        E: Exporter,
+        E::Export: Serialize,
+    {
+        if self.last_write.is_some_and(|last| last.elapsed() < self.interval) {
+            return Ok(());
+        }
+
+        let json = serde_json::to_string_pretty(&exporter.export_partial())?;
+        if self.last_json.as_ref() == Some(&json) {
+            return Ok(());
+        }
+
+        output::create_parent_dirs(&self.output)?;
+        std::fs::write(&self.output, &json).with_path("update", &self.output)?;
+        info!("updated output at {}", self.output.display());
+
+        self.last_write = Some(Instant::now());
+        self.last_json = Some(json);
+        Ok(())
+    }
+}
@@ -0,0 +1,238 @@
+//! Defaults for the command line options, read from `archiver.toml` in the config directory
+//! of the platform, or from the file given with `--config`.
+//!
+//! Keys are named like the options they set, for example:
+//!
+//! ```toml
+//! output = "exports/archive.json"
+//! format = ["fribbels", "ndjson"]
+//! interface = ["Ethernet"]
+//! timeout = 600
+//! log-path = "archiver.log"
+//!
+//! [profile.alt]
+//! uid = 123456789
+//! output = "exports/alt_{date}.json"
+//! ```
+//!
+//! The options of the profile given with `--profile` replace the ones outside of it, and options
+//! given on the command line or in `ARCHIVER_*` environment variables take precedence over the file.
+use std::collections::BTreeMap;
+use std::fs;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+
+use clap::parser::ValueSource;
+use clap::ArgMatches;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use completionist_archiver::error::{ArchiverError, PathContext};
+use completionist_archiver::export::output::{self, OnConflict};
+use completionist_archiver::model;
+
+use crate::source::select_devices;
+use crate::{parse_format, Args, Backend, LogFormat};
+
+const FILE_NAME: &str = "archiver.toml";
+
+/// Content of the config file, every option being optional
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Config {
+    pub output: Option<PathBuf>,
+    pub format: Option<Vec<String>>,
+    pub interface: Option<Vec<String>>,
+    pub timeout: Option<u64>,
+    #[serde(alias = "idle-finish")]
+    pub quiet_period: Option<u64>,
+    pub connect_timeout: Option<u64>,
+    pub read_timeout: Option<u64>,
+    pub database_timeout: Option<u64>,
+    pub log_path: Option<PathBuf>,
+    pub log_format: Option<LogFormat>,
+    pub on_conflict: Option<OnConflict>,
+    pub websocket: Option<SocketAddr>,
+    pub http: Option<SocketAddr>,
+    pub uid: Option<u32>,
+    /// Options of every profile by name, e.g. of an alt account
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub profile: BTreeMap<String, Config>,
+}
+
+impl Config {
+    /// Options of the profile, falling back to the options outside of it
+    fn with_profile(self, profile: Config) -> Config {
+        Config {
+            output: profile.output.or(self.output),
+            format: profile.format.or(self.format),
+            interface: profile.interface.or(self.interface),
+            timeout: profile.timeout.or(self.timeout),
+            quiet_period: profile.quiet_period.or(self.quiet_period),
+            connect_timeout: profile.connect_timeout.or(self.connect_timeout),
+            read_timeout: profile.read_timeout.or(self.read_timeout),
+            database_timeout: profile.database_timeout.or(self.database_timeout),
+            log_path: profile.log_path.or(self.log_path),
+            log_format: profile.log_format.or(self.log_format),
+            on_conflict: profile.on_conflict.or(self.on_conflict),
+            websocket: profile.websocket.or(self.websocket),
+            http: profile.http.or(self.http),
+            uid: profile.uid.or(self.uid),
+            profile: BTreeMap::new(),
+        }
+    }
+}
+
+/// Path the config file is read from unless `--config` is given
+pub fn default_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("completionist-archiver").join(FILE_NAME))
+}
+
+/// Sets the options of `args` that were not given on the command line to the values of the
+/// config file. A missing file is only an error if it was given with `--config`, or if
+/// a profile was selected
+pub fn apply(args: &mut Args, matches: &ArgMatches) -> Result<(), ArchiverError> {
+    let path = match &args.config {
+        Some(path) => path.clone(),
+        None => match default_path() {
+            Some(path) if path.exists() || args.profile.is_some() => path,
+            _ => return Ok(()),
+        },
+    };
+    let mut config = load(&path)?;
+
+    if let Some(name) = &args.profile {
+        let profile = config.profile.remove(name).ok_or_else(|| {
+            let names: Vec<&str> = config.profile.keys().map(String::as_str).collect();
+            ArchiverError::InvalidData(format!("there is no profile {name} in {}, only {}", path.display(), names.join(", ")))
+        })?;
+        config = config.with_profile(profile);
+    }
+
+    if let Some(uid) = config.uid.filter(|&uid| !model::is_plausible_uid(uid)) {
+        return Err(ArchiverError::InvalidData(format!("invalid uid {uid} in {}, uids have 9 or 10 digits", path.display())));
+    }
+    if let Some(formats) = &config.format {
+        for format in formats {
+            parse_format(format).map_err(|e| ArchiverError::InvalidData(format!("invalid format {format} in {}: {e}", path.display())))?;
+        }
+    }
+
+    // options given on the command line or in ARCHIVER_* environment variables take precedence.
+    // the options of a command are in the matches of the command
+    let command_matches = matches.subcommand().map_or(matches, |(_, command_matches)| command_matches);
+    let unset = |id: &str| {
+        ![matches, command_matches].into_iter().any(|matches| {
+            matches.ids().any(|known| known == id)
+                && matches!(matches.value_source(id), Some(ValueSource::CommandLine | ValueSource::EnvVariable))
+        })
+    };
+    if unset("connect_timeout") {
+        set(&mut args.connect_timeout, config.connect_timeout);
+    }
+    if unset("read_timeout") {
+        set(&mut args.read_timeout, config.read_timeout);
+    }
+    if unset("database_timeout") {
+        set(&mut args.database_timeout, config.database_timeout);
+    }
+    if unset("log_format") {
+        set(&mut args.log_format, config.log_format);
+    }
+    args.log_path = args.log_path.take().or(config.log_path);
+
+    let (output, export, session, live) = args.groups_mut();
+    if unset("output") {
+        set(&mut output.output, config.output);
+    }
+    if unset("format") {
+        set(&mut output.format, config.format);
+    }
+    if unset("on_conflict") {
+        set(&mut output.write.on_conflict, config.on_conflict);
+    }
+    if unset("interface") {
+        set(&mut live.interface, config.interface);
+    }
+    if unset("timeout") {
+        set(&mut live.timeout, config.timeout);
+    }
+    session.quiet_period = session.quiet_period.or(config.quiet_period);
+    session.websocket = session.websocket.or(config.websocket);
+    session.http = session.http.or(config.http);
+    export.uid = export.uid.or(config.uid);
+
+    Ok(())
+}
+
+/// Checks the options once the config file was applied, which fails on invalid files already,
+/// and prints them as the config file they amount to
+pub fn check(args: &Args) -> Result<(), ArchiverError> {
+    match args.config.clone().or_else(default_path) {
+        Some(path) if path.exists() => info!("read config from {}", path.display()),
+        Some(path) => info!("there is no config at {}, using the defaults", path.display()),
+        None => info!("there is no config directory, using the defaults"),
+    }
+
+    let (output, export, session, live) = (args.output(), args.export(), args.session(), args.live());
+    let mut problems = vec![];
+    if live.pcap.is_none() && live.remote.is_none() && live.backend == Backend::Pcap {
+        match select_devices(&live.interface) {
+            Ok(devices) if devices.is_empty() => problems.push("none of the interfaces is connected".to_string()),
+            Ok(devices) => info!("captures on {}", devices.iter().map(|device| device.name.as_str()).collect::<Vec<_>>().join(", ")),
+            Err(e) => problems.push(e.to_string()),
+        }
+    }
+
+    // files that are read have to exist, folders that are written to are created
+    let read = [&live.pcap, &export.command_ids, &export.field_mappings, &export.merge];
+    for path in read.into_iter().flatten().filter(|path| !path.exists()) {
+        problems.push(format!("{} does not exist", path.display()));
+    }
+    let written = [Some(output::render(&output.output, None)), args.log_path.clone(), export.report.clone(), session.stats.clone()];
+    for path in written.iter().flatten() {
+        match path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            Some(parent) if parent.is_file() => problems.push(format!("{} is a file, not a folder", parent.display())),
+            Some(parent) if !parent.exists() => warn!("{} does not exist yet and is created", parent.display()),
+            _ => {}
+        }
+    }
+
+    let effective = Config {
+        output: Some(output.output.clone()),
+        format: Some(output.format.clone()),
+        interface: (!live.interface.is_empty()).then(|| live.interface.clone()),
+        timeout: Some(live.timeout),
+        quiet_period: session.quiet_period,
+        connect_timeout: Some(args.connect_timeout),
+        read_timeout: Some(args.read_timeout),
+        database_timeout: Some(args.database_timeout),
+        log_path: args.log_path.clone(),
+        log_format: Some(args.log_format),
+        on_conflict: Some(output.write.on_conflict),
+        websocket: session.websocket,
+        http: session.http,
+        uid: export.uid,
+        profile: BTreeMap::new(),
+    };
+    let content = toml::to_string(&effective).map_err(|e| ArchiverError::InvalidData(format!("could not write config: {e}")))?;
+    print!("{content}");
+
+    if problems.is_empty() {
+        info!("the config is fine");
+        Ok(())
+    } else {
+        Err(ArchiverError::InvalidData(format!("the config has problems: {}", problems.join(", "))))
+    }
+}
+
+fn load(path: &Path) -> Result<Config, ArchiverError> {
+    let content = fs::read_to_string(path).with_path("read", path)?;
+    toml::from_str(&content).map_err(|e| ArchiverError::InvalidData(format!("invalid config {}: {e}", path.display())))
+}
+
+fn set<T>(field: &mut T, value: Option<T>) {
+    if let Some(value) = value {
+        *field = value;
+    }
+}
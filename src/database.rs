@@ -0,0 +1,466 @@
+//! Game data loaded from online sources, used to recognize which items in captured
+//! packets are relevant for completionists.
+use std::collections::HashMap;
+use std::ops::RangeInclusive;
+use std::sync::mpsc;
+use std::time::Duration;
+
+use base64::Engine;
+use base64::prelude::BASE64_STANDARD;
+use serde::de::DeserializeOwned;
+use tracing::{debug, info, instrument, warn};
+use ureq::{Agent, AgentBuilder};
+
+use crate::error::{ArchiverError, Result};
+
+const BASE_RESOURCE_URL: &str = "https://raw.githubusercontent.com/Dimbreath/StarRailData/master";
+const KEYS_URL: &str = "https://raw.githubusercontent.com/tamilpp25/Iridium-SR/main/data/Keys.json";
+
+/// Timeouts applied to every request made while loading the [`Database`]
+#[derive(Debug, Clone, Copy)]
+pub struct HttpTimeouts {
+    /// How long to wait for a connection to be established
+    pub connect: Duration,
+    /// How long to wait for data once connected
+    pub read: Duration,
+}
+
+impl HttpTimeouts {
+    /// Agent applying the timeouts to its requests, through the proxy of the `HTTPS_PROXY`
+    /// or `ALL_PROXY` environment variables if set
+    pub fn agent(&self) -> Agent {
+        AgentBuilder::new()
+            .try_proxy_from_env(true)
+            .timeout_connect(self.connect)
+            .timeout_read(self.read)
+            .build()
+    }
+}
+
+/// Options for loading the [`Database`]
+#[derive(Debug, Clone)]
+pub struct DatabaseOptions {
+    pub timeouts: HttpTimeouts,
+    /// Whether to load the names of achievements and books,
+    /// which requires downloading the rather large text map
+    pub names: bool,
+}
+
+/// What the [`Database`] knows about an achievement
+#[derive(Debug, Clone)]
+pub struct AchievementInfo {
+    pub id: u32,
+    /// Amount of stellar jade rewarded for completing the achievement
+    pub jade: u32,
+    /// Progress needed to complete the achievement, if known
+    pub target: Option<u32>,
+    /// Series the achievement belongs to
+    pub series: Option<u32>,
+    /// Title of the achievement, if names were loaded
+    pub name: Option<String>,
+}
+
+/// What the [`Database`] knows about a readable book
+#[derive(Debug, Clone)]
+pub struct BookInfo {
+    pub id: u32,
+    /// Series the book belongs to
+    pub series: Option<u32>,
+    /// World the series of the book is found in, e.g. the Xianzhou Luofu
+    pub world: Option<u32>,
+    /// Title of the book, if names were loaded
+    pub name: Option<String>,
+}
+
+type TextMap = HashMap<String, String>;
+
+/// What the game data says about a quest, which achievements are completed as
+#[derive(Debug, Clone, Copy, Default)]
+struct QuestConfig {
+    /// Stellar jade rewarded for completing it, if any
+    jade: Option<u32>,
+    /// Progress needed to complete it
+    target: Option<u32>,
+}
+
+/// Game data needed to recognize completionist items in captured packets,
+/// together with the keys needed to decrypt them.
+#[derive(Clone)]
+pub struct Database {
+    achievement_list: Vec<u32>,
+    achievements: HashMap<u32, AchievementInfo>,
+    book_list: Vec<u32>,
+    books: HashMap<u32, BookInfo>,
+    keys: HashMap<u32, Vec<u8>>,
+    /// Names of the achievement series by id, if names were loaded
+    achievement_series: HashMap<u32, String>,
+    /// Names of the book series by id, if names were loaded
+    book_series: HashMap<u32, String>,
+}
+
+impl Database {
+    /// Loads the database from [Dimbreath's StarRailData] and the keys from [Iridium-SR].
+    ///
+    /// Fails if any of the resources fail to load.
+    ///
+    /// [Dimbreath's StarRailData]: https://github.com/Dimbreath/StarRailData
+    /// [Iridium-SR]: https://github.com/tamilpp25/Iridium-SR
+    #[instrument(name = "config_map", skip_all)]
+    pub fn new_from_online(options: &DatabaseOptions) -> Result<Self> {
+        info!("initializing database from online sources, this might take a while...");
+        let agent = options.timeouts.agent();
+
+        // the text map is only needed while loading, it is dropped afterwards to save memory
+        let text_map = if options.names {
+            Some(Self::load_online_text_map(&agent)?)
+        } else {
+            None
+        };
+
+        let achievements = Self::load_online_achievements(&agent, text_map.as_ref())?;
+        let books = Self::load_online_books(&agent, text_map.as_ref())?;
+        let database = Database::new(achievements, books, Self::load_online_keys(&agent)?);
+
+        Ok(match &text_map {
+            Some(text_map) => database.with_series_names(
+                Self::load_online_series_names(&agent, text_map, "AchievementSeries", "SeriesID", "SeriesTitle")?,
+                Self::load_online_series_names(&agent, text_map, "BookSeriesConfig", "BookSeriesID", "BookSeries")?,
+            ),
+            None => database,
+        })
+    }
+
+    /// Database of the given game data, e.g. to feed exporters with commands without a capture
+    pub fn new(achievements: Vec<AchievementInfo>, books: Vec<BookInfo>, keys: HashMap<u32, Vec<u8>>) -> Self {
+        Database {
+            achievement_list: achievements.iter().map(|a| a.id).collect(),
+            achievements: achievements.into_iter().map(|a| (a.id, a)).collect(),
+            book_list: books.iter().map(|b| b.id).collect(),
+            books: books.into_iter().map(|b| (b.id, b)).collect(),
+            keys,
+            achievement_series: HashMap::new(),
+            book_series: HashMap::new(),
+        }
+    }
+
+    /// Names the achievement and book series by id
+    pub fn with_series_names(mut self, achievement_series: HashMap<u32, String>, book_series: HashMap<u32, String>) -> Self {
+        self.achievement_series = achievement_series;
+        self.book_series = book_series;
+        self
+    }
+
+    /// Same as [`Database::new_from_online`], but gives up if loading takes longer than `deadline`.
+    ///
+    /// Fails if the deadline expired or if loading failed.
+    pub fn new_from_online_with_deadline(options: &DatabaseOptions, deadline: Duration) -> Result<Self> {
+        let (tx, rx) = mpsc::channel();
+        let options = options.clone();
+
+        // the loading thread is detached on timeout, it will be cleaned up when the process exits
+        std::thread::spawn(move || {
+            let _ = tx.send(Self::new_from_online(&options));
+        });
+
+        match rx.recv_timeout(deadline) {
+            Ok(database) => database,
+            Err(mpsc::RecvTimeoutError::Timeout) => Err(ArchiverError::DatabaseTimeout(deadline)),
+            // only happens if loading panicked, which was reported already
+            Err(mpsc::RecvTimeoutError::Disconnected) => {
+                Err(ArchiverError::InvalidData("loading the database stopped unexpectedly".to_string()))
+            }
+        }
+    }
+    // TODO: new_from_source
+
+    fn load_online_achievements(agent: &Agent, text_map: Option<&TextMap>) -> Result<Vec<AchievementInfo>> {
+        let quests = Self::load_online_quests(agent)?;
+
+        let url = format!("{BASE_RESOURCE_URL}/ExcelOutput/AchievementData.json");
+        let json_object: serde_json::Value = Self::get(agent, &url)?;
+        let mut achievements = vec![];
+        for (_key, value) in Self::as_object(&json_object, &url)? {
+            let achievement_id = Self::id_field(value, "AchievementID", &url)?;
+
+            // achievements are completed as the quest of the same id, which has the reward
+            let quest = quests.get(&achievement_id).copied().unwrap_or_default();
+            if quest.jade.is_none() {
+                debug!(achievement_id, "no reward found for achievement");
+            }
+
+            achievements.push(AchievementInfo {
+                id: achievement_id,
+                jade: quest.jade.unwrap_or(0),
+                target: quest.target,
+                series: Self::optional_id_field(value, "SeriesID"),
+                name: text_map.and_then(|text_map| Self::lookup_text(text_map, &value["AchievementTitle"])),
+            });
+        }
+        Ok(achievements)
+    }
+    fn load_online_books(agent: &Agent, text_map: Option<&TextMap>) -> Result<Vec<BookInfo>> {
+        let worlds = Self::load_online_book_worlds(agent)?;
+
+        let url = format!("{BASE_RESOURCE_URL}/ExcelOutput/LocalbookConfig.json");
+        let json_object: serde_json::Value = Self::get(agent, &url)?;
+        let mut books = vec![];
+        for (_key, value) in Self::as_object(&json_object, &url)? {
+            let book_id = Self::id_field(value, "BookID", &url)?;
+            let series = Self::optional_id_field(value, "BookSeriesID");
+            books.push(BookInfo {
+                id: book_id,
+                series,
+                world: series.and_then(|series| worlds.get(&series).copied()),
+                name: text_map.and_then(|text_map| Self::lookup_text(text_map, &value["BookInsideName"])),
+            });
+        }
+        Ok(books)
+    }
+    /// Rewards and targets of the quests by quest id
+    fn load_online_quests(agent: &Agent) -> Result<HashMap<u32, QuestConfig>> {
+        let url = format!("{BASE_RESOURCE_URL}/ExcelOutput/RewardData.json");
+        let json_object: serde_json::Value = Self::get(agent, &url)?;
+        let mut jade_by_reward = HashMap::new();
+        for (_key, value) in Self::as_object(&json_object, &url)? {
+            let reward = Self::id_field(value, "RewardID", &url)?;
+            // stellar jade is called hcoin in the game data
+            if let Some(jade) = Self::optional_id_field(value, "Hcoin") {
+                jade_by_reward.insert(reward, jade);
+            }
+        }
+
+        let url = format!("{BASE_RESOURCE_URL}/ExcelOutput/FinishWay.json");
+        let json_object: serde_json::Value = Self::get(agent, &url)?;
+        let mut targets = HashMap::new();
+        for (_key, value) in Self::as_object(&json_object, &url)? {
+            let finish_way = Self::id_field(value, "ID", &url)?;
+            if let Some(target) = Self::optional_id_field(value, "Progress") {
+                targets.insert(finish_way, target);
+            }
+        }
+
+        let url = format!("{BASE_RESOURCE_URL}/ExcelOutput/QuestData.json");
+        let json_object: serde_json::Value = Self::get(agent, &url)?;
+        let mut quests = HashMap::new();
+        for (_key, value) in Self::as_object(&json_object, &url)? {
+            let quest = Self::id_field(value, "QuestID", &url)?;
+            let lookup = |field, values: &HashMap<u32, u32>| Self::optional_id_field(value, field).and_then(|id| values.get(&id).copied());
+            quests.insert(quest, QuestConfig {
+                jade: lookup("RewardID", &jade_by_reward),
+                target: lookup("FinishWayID", &targets),
+            });
+        }
+        Ok(quests)
+    }
+    /// Worlds of the book series by series id
+    fn load_online_book_worlds(agent: &Agent) -> Result<HashMap<u32, u32>> {
+        let url = format!("{BASE_RESOURCE_URL}/ExcelOutput/BookSeriesConfig.json");
+        let json_object: serde_json::Value = Self::get(agent, &url)?;
+        let mut worlds = HashMap::new();
+        for (_key, value) in Self::as_object(&json_object, &url)? {
+            let series = Self::id_field(value, "BookSeriesID", &url)?;
+            if let Some(world) = Self::optional_id_field(value, "BookSeriesWorld") {
+                worlds.insert(series, world);
+            }
+        }
+        Ok(worlds)
+    }
+    /// Names of the series in the excel `file` by id
+    fn load_online_series_names(agent: &Agent, text_map: &TextMap, file: &str, id_field: &str, name_field: &str) -> Result<HashMap<u32, String>> {
+        let url = format!("{BASE_RESOURCE_URL}/ExcelOutput/{file}.json");
+        let json_object: serde_json::Value = Self::get(agent, &url)?;
+        let mut names = HashMap::new();
+        for (_key, value) in Self::as_object(&json_object, &url)? {
+            let series = Self::id_field(value, id_field, &url)?;
+            if let Some(name) = Self::lookup_text(text_map, &value[name_field]) {
+                names.insert(series, name);
+            }
+        }
+        Ok(names)
+    }
+    fn load_online_text_map(agent: &Agent) -> Result<TextMap> {
+        Self::get(agent, &format!("{BASE_RESOURCE_URL}/TextMap/TextMapEN.json"))
+    }
+
+    fn as_object<'a>(json: &'a serde_json::Value, url: &str) -> Result<&'a serde_json::Map<String, serde_json::Value>> {
+        json.as_object().ok_or_else(|| ArchiverError::Resource {
+            url: url.to_string(),
+            reason: "expected an object".to_string(),
+        })
+    }
+
+    fn id_field(value: &serde_json::Value, field: &str, url: &str) -> Result<u32> {
+        value[field].as_u64()
+            .and_then(|id| u32::try_from(id).ok())
+            .ok_or_else(|| ArchiverError::Resource {
+                url: url.to_string(),
+                reason: format!("missing or invalid {field}"),
+            })
+    }
+
+    /// Like [`Database::id_field`], but for fields that are not needed to recognize items
+    fn optional_id_field(value: &serde_json::Value, field: &str) -> Option<u32> {
+        value[field].as_u64().and_then(|id| u32::try_from(id).ok())
+    }
+
+    /// Resolves a text reference like `{"Hash": -1234}` using the text map
+    fn lookup_text(text_map: &TextMap, text: &serde_json::Value) -> Option<String> {
+        let hash = match &text["Hash"] {
+            serde_json::Value::Number(hash) => hash.to_string(),
+            serde_json::Value::String(hash) => hash.clone(),
+            _ => return None,
+        };
+        text_map.get(&hash).cloned()
+    }
+
+    fn load_online_keys(agent: &Agent) -> Result<HashMap<u32, Vec<u8>>> {
+        let keys: HashMap<u32, String> = Self::get(agent, KEYS_URL)?;
+        let mut keys_bytes = HashMap::new();
+
+        for (k, v) in keys {
+            keys_bytes.insert(k, BASE64_STANDARD.decode(v)?);
+        }
+
+        Ok(keys_bytes)
+    }
+
+    /// Fetches only the latest decryption keys, without reloading the rest of the database.
+    ///
+    /// Returns `None` if the keys could not be loaded, logging why.
+    pub fn fetch_online_keys(timeouts: HttpTimeouts) -> Option<HashMap<u32, Vec<u8>>> {
+        match Self::load_online_keys(&timeouts.agent()) {
+            Ok(keys) => Some(keys),
+            Err(error) => {
+                warn!(%error, "could not fetch keys");
+                None
+            }
+        }
+    }
+
+    fn get<T: DeserializeOwned>(agent: &Agent, url: &str) -> Result<T> {
+        debug!(url, "requesting from resource");
+        let response = agent.get(url)
+            .call()
+            .map_err(|e| ArchiverError::Download {
+                url: url.to_string(),
+                source: Box::new(e),
+            })?;
+
+        response.into_json().map_err(|e| ArchiverError::Resource {
+            url: url.to_string(),
+            reason: e.to_string(),
+        })
+    }
+
+    /// Ids of all achievements known to the database
+    pub fn achievement_ids(&self) -> &[u32] {
+        &self.achievement_list
+    }
+
+    /// Ids of all readable books known to the database
+    pub fn book_ids(&self) -> &[u32] {
+        &self.book_list
+    }
+
+    /// Whether `id` is a known achievement id
+    pub fn has_achievement(&self, id: u32) -> bool {
+        self.achievements.contains_key(&id)
+    }
+
+    /// Information about the achievement `id`, if it is known
+    pub fn achievement(&self, id: u32) -> Option<&AchievementInfo> {
+        self.achievements.get(&id)
+    }
+
+    /// Amount of stellar jade rewarded for completing the achievement `id`,
+    /// or 0 if the achievement is unknown
+    pub fn achievement_jade(&self, id: u32) -> u32 {
+        self.achievements.get(&id).map_or(0, |a| a.jade)
+    }
+
+    /// Amount of stellar jade rewarded for completing every known achievement
+    pub fn total_achievement_jade(&self) -> u32 {
+        self.achievements.values().map(|a| a.jade).sum()
+    }
+
+    /// Range of the known achievement ids, `None` if there are none
+    pub fn achievement_id_range(&self) -> Option<RangeInclusive<u32>> {
+        id_range(&self.achievement_list)
+    }
+
+    /// Leading digits all known achievement ids share, which achievements added to the game later
+    /// usually share as well, even if their ids are higher than all known ones
+    pub fn achievement_id_prefix(&self) -> Option<IdPrefix> {
+        IdPrefix::of(&self.achievement_list)
+    }
+
+    /// Whether `id` is a known book id
+    pub fn has_book(&self, id: u32) -> bool {
+        self.books.contains_key(&id)
+    }
+
+    /// Information about the book `id`, if it is known
+    pub fn book(&self, id: u32) -> Option<&BookInfo> {
+        self.books.get(&id)
+    }
+
+    /// Name of the achievement series `id`, if names were loaded
+    pub fn achievement_series_name(&self, id: u32) -> Option<&str> {
+        self.achievement_series.get(&id).map(String::as_str)
+    }
+
+    /// Name of the book series `id`, if names were loaded
+    pub fn book_series_name(&self, id: u32) -> Option<&str> {
+        self.book_series.get(&id).map(String::as_str)
+    }
+
+    /// Range of the known book ids, `None` if there are none
+    pub fn book_id_range(&self) -> Option<RangeInclusive<u32>> {
+        id_range(&self.book_list)
+    }
+
+    /// Leading digits all known book ids share, see [`Database::achievement_id_prefix`]
+    pub fn book_id_prefix(&self) -> Option<IdPrefix> {
+        IdPrefix::of(&self.book_list)
+    }
+
+    /// Decryption keys, meant to be passed to [`GameSniffer::set_initial_keys`]
+    ///
+    /// [`GameSniffer::set_initial_keys`]: reliquary::network::GameSniffer::set_initial_keys
+    pub fn keys(&self) -> &HashMap<u32, Vec<u8>> {
+        &self.keys
+    }
+}
+
+fn id_range(ids: &[u32]) -> Option<RangeInclusive<u32>> {
+    Some(*ids.iter().min()?..=*ids.iter().max()?)
+}
+
+/// Leading digits shared by the ids of a category, followed by a fixed number of digits,
+/// e.g. `40` followed by 5 digits for achievement ids like 4010101 and 4090315
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IdPrefix {
+    prefix: u32,
+    /// Number of digits after the prefix
+    rest: u32,
+}
+
+impl IdPrefix {
+    /// Prefix of `ids`, `None` if there are none or they do not even share the first digit
+    fn of(ids: &[u32]) -> Option<IdPrefix> {
+        let (mut min, mut max) = (*ids.iter().min()?, *ids.iter().max()?);
+        // min and max sharing leading digits means all ids in between share them
+        let mut rest = 0;
+        while min != max {
+            min /= 10;
+            max /= 10;
+            rest += 1;
+        }
+        (min != 0).then_some(IdPrefix { prefix: min, rest })
+    }
+
+    /// Whether `id` starts with the prefix and has as many digits as the ids of the category
+    pub fn matches(&self, id: u32) -> bool {
+        10u32.checked_pow(self.rest).is_some_and(|divisor| id / divisor == self.prefix)
+    }
+}
@@ -0,0 +1,127 @@
+//! Errors of the archiver, with hints for users on how to resolve them.
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use thiserror::Error;
+
+pub type Result<T> = std::result::Result<T, ArchiverError>;
+
+#[derive(Debug, Error)]
+pub enum ArchiverError {
+    /// A resource of the database could not be downloaded
+    #[error("could not download {url}: {source}")]
+    Download {
+        url: String,
+        #[source]
+        source: Box<ureq::Error>,
+    },
+    /// A resource of the database was downloaded, but is not in the expected format
+    #[error("unexpected content of {url}: {reason}")]
+    Resource { url: String, reason: String },
+    #[error("could not load the database within {} seconds", .0.as_secs())]
+    DatabaseTimeout(Duration),
+    /// Reading or writing a file failed
+    #[error("could not {action} {}: {source}", path.display())]
+    File {
+        action: &'static str,
+        path: PathBuf,
+        #[source]
+        source: Box<ArchiverError>,
+    },
+    #[error("could not find interface {0}")]
+    UnknownInterface(String),
+    /// Capturing stopped without any packets of the game
+    #[error("no game traffic was captured")]
+    NoGameTraffic,
+    /// Packets of the game were captured, but none could be decrypted
+    #[error("the game traffic could not be decrypted")]
+    MissingKeys,
+    /// The login was captured, but none of its packets could be decrypted with the known keys
+    #[error("the login was captured, but could not be decrypted: the keys of this game version are missing")]
+    OutdatedKeys,
+    /// Capturing stopped before all data was collected, what was collected is exported anyway
+    #[error("capturing stopped before everything was collected, missing {}", .0.join(", "))]
+    Incomplete(Vec<&'static str>),
+    /// A feature that is not available on this platform or in this build
+    #[error("{0}")]
+    Unsupported(&'static str),
+    /// Data that is not what was expected, like a file of the wrong format
+    #[error("{0}")]
+    InvalidData(String),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    #[error(transparent)]
+    Proto(#[from] protobuf::Error),
+    #[error(transparent)]
+    Base64(#[from] base64::DecodeError),
+    #[error(transparent)]
+    Pcap(#[from] pcap::Error),
+    #[error(transparent)]
+    PcapFile(#[from] pcap_file::PcapError),
+    #[cfg(all(windows, feature = "windivert"))]
+    #[error(transparent)]
+    WinDivert(#[from] windivert::error::WinDivertError),
+}
+
+impl ArchiverError {
+    /// Exit code of the process when it stops because of this error, see the exit codes
+    /// section of the readme
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            ArchiverError::Incomplete(_) => 2,
+            ArchiverError::Download { .. } | ArchiverError::Resource { .. } | ArchiverError::DatabaseTimeout(_) => 3,
+            ArchiverError::UnknownInterface(_) | ArchiverError::Unsupported(_) | ArchiverError::Pcap(_) => 4,
+            #[cfg(all(windows, feature = "windivert"))]
+            ArchiverError::WinDivert(_) => 4,
+            ArchiverError::File { .. } => 5,
+            ArchiverError::NoGameTraffic => 6,
+            ArchiverError::MissingKeys | ArchiverError::OutdatedKeys => 7,
+            _ => 1,
+        }
+    }
+
+    /// What the user can do about the error, if there is anything
+    pub fn hint(&self) -> Option<&'static str> {
+        match self {
+            ArchiverError::Download { .. } | ArchiverError::DatabaseTimeout(_) => {
+                Some("check your internet connection and whether raw.githubusercontent.com is reachable")
+            }
+            ArchiverError::Resource { .. } => Some("the game data might have changed, please check for a newer version"),
+            ArchiverError::UnknownInterface(_) => Some("run the interfaces command to list available interfaces"),
+            ArchiverError::NoGameTraffic => {
+                Some("make sure the game is running and captured on the interface it uses, see the interfaces command")
+            }
+            ArchiverError::MissingKeys => Some(
+                "start capturing before logging in, otherwise the game data might not have the keys of this version yet",
+            ),
+            ArchiverError::OutdatedKeys => Some(
+                "the keys of a new game version are usually published a few days after the update, \
+                 check for a newer version with the self-update command or try again later",
+            ),
+            ArchiverError::Incomplete(_) => Some("go to the main menu and enter train hyperdrive while capturing"),
+            ArchiverError::Pcap(_) => Some("make sure npcap (windows) or libpcap is installed and that you have permission to capture"),
+            #[cfg(all(windows, feature = "windivert"))]
+            ArchiverError::WinDivert(_) => Some("windivert needs to be run as administrator"),
+            ArchiverError::File { source, .. } => source.hint(),
+            _ => None,
+        }
+    }
+}
+
+/// Adds the path of the file being read or written to errors
+pub trait PathContext<T> {
+    fn with_path(self, action: &'static str, path: &Path) -> Result<T>;
+}
+
+impl<T, E: Into<ArchiverError>> PathContext<T> for std::result::Result<T, E> {
+    fn with_path(self, action: &'static str, path: &Path) -> Result<T> {
+        self.map_err(|e| ArchiverError::File {
+            action,
+            path: path.to_path_buf(),
+            source: Box::new(e.into()),
+        })
+    }
+}
@@ -0,0 +1,144 @@
+//! Exporters selected at runtime, behind a box.
+use std::io::{self, Write};
+
+use reliquary::network::GameCommand;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::export::{Exporter, Progress};
+use crate::model::Account;
+
+/// Object safe version of [`Exporter`], with the export serialized to JSON.
+///
+/// Implemented for every exporter with a serializable export.
+pub trait AnyExporter: Send {
+    fn read_command(&mut self, command: &GameCommand);
+    fn read_commands(&mut self, commands: &[GameCommand]);
+    fn is_finished(&self) -> bool;
+    fn missing(&self) -> Vec<&'static str>;
+    fn progress(&self) -> Vec<Progress>;
+    fn is_settled(&self) -> bool;
+    fn parse_failures(&self) -> u64;
+    fn flush(&mut self, writer: &mut dyn Write) -> io::Result<()>;
+    fn restore(&mut self, account: &Account);
+    fn export_partial_json(&self) -> Value;
+    fn export_json(self: Box<Self>) -> Value;
+    fn take_switched_accounts_json(&mut self) -> Vec<Value>;
+}
+
+impl<E> AnyExporter for E
+where
+    E: Exporter + Send,
+    E::Export: Serialize,
+{
+    fn read_command(&mut self, command: &GameCommand) {
+        Exporter::read_command(self, command)
+    }
+
+    fn read_commands(&mut self, commands: &[GameCommand]) {
+        Exporter::read_commands(self, commands)
+    }
+
+    fn is_finished(&self) -> bool {
+        Exporter::is_finished(self)
+    }
+
+    fn missing(&self) -> Vec<&'static str> {
+        Exporter::missing(self)
+    }
+
+    fn progress(&self) -> Vec<Progress> {
+        Exporter::progress(self)
+    }
+
+    fn is_settled(&self) -> bool {
+        Exporter::is_settled(self)
+    }
+
+    fn parse_failures(&self) -> u64 {
+        Exporter::parse_failures(self)
+    }
+
+    fn flush(&mut self, writer: &mut dyn Write) -> io::Result<()> {
+        Exporter::flush(self, writer)
+    }
+
+    fn restore(&mut self, account: &Account) {
+        Exporter::restore(self, account)
+    }
+
+    fn export_partial_json(&self) -> Value {
+        to_json(&self.export_partial())
+    }
+
+    fn export_json(self: Box<Self>) -> Value {
+        to_json(&(*self).export())
+    }
+
+    fn take_switched_accounts_json(&mut self) -> Vec<Value> {
+        self.take_switched_accounts().iter().map(to_json).collect()
+    }
+}
+
+/// Exporter selected at runtime, e.g. from an [`ExporterRegistry`], usable wherever
+/// an [`Exporter`] is expected. The export is serialized to JSON
+///
+/// [`ExporterRegistry`]: crate::export::registry::ExporterRegistry
+pub struct BoxedExporter(pub Box<dyn AnyExporter>);
+
+impl Exporter for BoxedExporter {
+    type Export = Value;
+
+    fn read_command(&mut self, command: &GameCommand) {
+        self.0.read_command(command)
+    }
+
+    fn read_commands(&mut self, commands: &[GameCommand]) {
+        self.0.read_commands(commands)
+    }
+
+    fn is_finished(&self) -> bool {
+        self.0.is_finished()
+    }
+
+    fn missing(&self) -> Vec<&'static str> {
+        self.0.missing()
+    }
+
+    fn progress(&self) -> Vec<Progress> {
+        self.0.progress()
+    }
+
+    fn is_settled(&self) -> bool {
+        self.0.is_settled()
+    }
+
+    fn parse_failures(&self) -> u64 {
+        self.0.parse_failures()
+    }
+
+    fn flush(&mut self, writer: &mut dyn Write) -> io::Result<()> {
+        self.0.flush(writer)
+    }
+
+    fn restore(&mut self, account: &Account) {
+        self.0.restore(account)
+    }
+
+    fn export_partial(&self) -> Self::Export {
+        self.0.export_partial_json()
+    }
+
+    fn export(self) -> Self::Export {
+        self.0.export_json()
+    }
+
+    fn take_switched_accounts(&mut self) -> Vec<Self::Export> {
+        self.0.take_switched_accounts_json()
+    }
+}
+
+fn to_json(export: &impl Serialize) -> Value {
+    // exports are plain data, serializing them cannot fail
+    serde_json::to_value(export).unwrap()
+}
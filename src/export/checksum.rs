@@ -0,0 +1,47 @@
+//! SHA-256 checksums of exports, written next to them in the format of `sha256sum`,
+//! e.g. to `archive_output.json.sha256`, so archived exports can be checked for changes.
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+use tracing::debug;
+
+use crate::error::{PathContext, Result};
+
+/// Writes the checksum of a file to its [`path`]
+pub fn write(path: &Path) -> Result<()> {
+    let checksum_path = self::path(path);
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    std::fs::write(&checksum_path, format!("{}  {name}\n", checksum(path)?)).with_path("write", &checksum_path)
+}
+
+/// Checks the file against its checksum file written with [`write`], if there is one,
+/// returning the problem if they do not match
+pub fn verify(path: &Path) -> Result<Option<String>> {
+    let checksum_path = self::path(path);
+    if !checksum_path.exists() {
+        return Ok(None);
+    }
+
+    let content = std::fs::read_to_string(&checksum_path).with_path("read", &checksum_path)?;
+    let expected = content.split_whitespace().next().unwrap_or_default().to_lowercase();
+    let actual = checksum(path)?;
+    if actual == expected {
+        debug!(checksum = actual, "checksum matches");
+        Ok(None)
+    } else {
+        Ok(Some(format!("its checksum is {actual}, but {} lists {expected}", checksum_path.display())))
+    }
+}
+
+/// SHA-256 checksum of the content of a file, as lowercase hex
+pub fn checksum(path: &Path) -> Result<String> {
+    let content = std::fs::read(path).with_path("read", path)?;
+    Ok(format!("{:x}", Sha256::digest(content)))
+}
+
+/// Path of the checksum file of a file, e.g. `archive_output.json.sha256`
+pub fn path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".sha256");
+    path.with_file_name(name)
+}
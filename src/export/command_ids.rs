@@ -0,0 +1,77 @@
+//! Ids of the commands that are read, which can be overridden for beta clients
+//! whose command ids differ from the ones of the released game.
+//!
+//! Overrides are read from a JSON object mapping command names to ids, for example
+//! `{ "GetQuestDataScRsp": 1234, "GetBagScRsp": 5678 }`.
+//!
+//! Note that the sniffer itself still expects `PlayerGetTokenScRsp` to have the released id,
+//! as it needs that command to decrypt the following packets.
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+use reliquary::network::gen::command_id;
+use tracing::{info, warn};
+
+use crate::error::{PathContext, Result};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CommandIds {
+    pub player_get_token_sc_rsp: u16,
+    pub player_login_sc_rsp: u16,
+    pub player_login_finish_sc_rsp: u16,
+    pub get_bag_sc_rsp: u16,
+    pub get_quest_data_sc_rsp: u16,
+    pub player_sync_sc_notify: u16,
+    pub take_quest_reward_sc_rsp: u16,
+    pub get_player_detail_info_sc_rsp: u16,
+}
+
+impl Default for CommandIds {
+    /// Ids of the released game
+    fn default() -> Self {
+        CommandIds {
+            player_get_token_sc_rsp: command_id::PlayerGetTokenScRsp,
+            player_login_sc_rsp: command_id::PlayerLoginScRsp,
+            player_login_finish_sc_rsp: command_id::PlayerLoginFinishScRsp,
+            get_bag_sc_rsp: command_id::GetBagScRsp,
+            get_quest_data_sc_rsp: command_id::GetQuestDataScRsp,
+            player_sync_sc_notify: command_id::PlayerSyncScNotify,
+            take_quest_reward_sc_rsp: command_id::TakeQuestRewardScRsp,
+            get_player_detail_info_sc_rsp: command_id::GetPlayerDetailInfoScRsp,
+        }
+    }
+}
+
+impl CommandIds {
+    /// Ids of the released game with the overrides of a JSON file applied.
+    /// Commands that are not read are ignored with a warning
+    pub fn load_overrides(path: &Path) -> Result<CommandIds> {
+        let file = File::open(path).with_path("open", path)?;
+        let overrides: HashMap<String, u16> = serde_json::from_reader(BufReader::new(file)).with_path("read", path)?;
+
+        let mut ids = CommandIds::default();
+        for (name, id) in overrides {
+            let field = match name.as_str() {
+                "PlayerGetTokenScRsp" => &mut ids.player_get_token_sc_rsp,
+                "PlayerLoginScRsp" => &mut ids.player_login_sc_rsp,
+                "PlayerLoginFinishScRsp" => &mut ids.player_login_finish_sc_rsp,
+                "GetBagScRsp" => &mut ids.get_bag_sc_rsp,
+                "GetQuestDataScRsp" => &mut ids.get_quest_data_sc_rsp,
+                "PlayerSyncScNotify" => &mut ids.player_sync_sc_notify,
+                "TakeQuestRewardScRsp" => &mut ids.take_quest_reward_sc_rsp,
+                "GetPlayerDetailInfoScRsp" => &mut ids.get_player_detail_info_sc_rsp,
+                _ => {
+                    warn!("ignoring override of {name}, the command is not read");
+                    continue;
+                }
+            };
+
+            info!(old = *field, new = id, "overriding command id of {name}");
+            *field = id;
+        }
+
+        Ok(ids)
+    }
+}
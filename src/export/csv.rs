@@ -0,0 +1,63 @@
+//! Every collected item as a row of comma separated values, to be opened in a spreadsheet.
+use std::borrow::Cow;
+use std::fmt::Write;
+use std::sync::Arc;
+
+use crate::database::Database;
+use crate::export::format::{Format, ModelExporter};
+use crate::model::Account;
+
+/// Columns of the rows, books leave the columns of achievements empty
+const HEADER: &str = "uid,category,id,name,first_seen,jade,claimed";
+
+/// Format of a table with a row for every item, written as text instead of JSON
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CsvFormat;
+
+pub type CsvExporter = ModelExporter<CsvFormat>;
+
+impl CsvExporter {
+    pub fn new(database: impl Into<Arc<Database>>) -> CsvExporter {
+        ModelExporter::with_format(database, CsvFormat)
+    }
+}
+
+impl Format for CsvFormat {
+    type Export = String;
+
+    fn convert(&self, account: &Account, _database: &Database) -> Self::Export {
+        let uid = account.uid.map(|uid| uid.to_string()).unwrap_or_default();
+        let mut rows = format!("{HEADER}\n");
+
+        // writing to a string cannot fail
+        for achievement in account.achievements.values() {
+            let name = field(achievement.name.as_deref().unwrap_or_default());
+            writeln!(
+                rows,
+                "{uid},achievement,{},{name},{},{},{}",
+                achievement.id, achievement.first_seen, achievement.jade, achievement.reward_taken
+            )
+            .unwrap();
+        }
+        for book in account.books.values() {
+            let name = field(book.name.as_deref().unwrap_or_default());
+            writeln!(rows, "{uid},book,{},{name},{},,", book.id, book.first_seen).unwrap();
+        }
+
+        rows
+    }
+}
+
+/// Uid in the first row of items of an export, if it has any items and a uid
+pub fn uid(rows: &str) -> Option<u64> {
+    rows.lines().nth(1)?.split(',').next()?.parse().ok()
+}
+
+/// Quotes a field that contains a character with a meaning in CSV, like the commas of a name
+fn field(value: &str) -> Cow<'_, str> {
+    if value.contains([',', '"', '\n', '\r']) {
+        Cow::Owned(format!("\"{}\"", value.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(value)
+    }
+}
@@ -0,0 +1,93 @@
+//! Dumps of the decrypted commands of a capture, to attach to bug reports.
+//!
+//! Unlike a full capture, a dump contains nothing but the commands of the game,
+//! and the uid and key seed of the token command can be redacted.
+//! Dumps can be read back with [`load`] to feed the commands into an exporter again.
+//!
+//! A dump starts with [`MAGIC`], followed by one record per command:
+//! the command id as `u16` and the length of the protobuf data as `u32`, both little endian,
+//! followed by the protobuf data.
+use std::fs::File;
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::Path;
+
+use protobuf::Message;
+use reliquary::network::GameCommand;
+use reliquary::network::gen::command_id;
+use reliquary::network::gen::proto::PlayerGetTokenScRsp::PlayerGetTokenScRsp;
+
+use crate::error::{ArchiverError, PathContext, Result};
+use crate::export::fixture::game_command;
+
+/// Start of every dump, including the version of the format
+pub const MAGIC: &[u8; 8] = b"CADUMP1\n";
+
+/// Writer of commands to a dump file
+pub struct DumpWriter {
+    writer: BufWriter<File>,
+    redact: bool,
+}
+
+impl DumpWriter {
+    /// Creates a dump file, redacting the uid and key seed of the token command if `redact` is set
+    pub fn create(path: &Path, redact: bool) -> Result<DumpWriter> {
+        let mut writer = BufWriter::new(File::create(path).with_path("create", path)?);
+        writer.write_all(MAGIC).with_path("write", path)?;
+        Ok(DumpWriter { writer, redact })
+    }
+
+    pub fn write(&mut self, command: &GameCommand) -> Result<()> {
+        let redacted;
+        let proto_data = if self.redact && command.command_id == command_id::PlayerGetTokenScRsp {
+            let mut token = command.parse_proto::<PlayerGetTokenScRsp>()?;
+            token.uid = 0;
+            token.secret_key_seed = 0;
+            redacted = token.write_to_bytes()?;
+            &redacted
+        } else {
+            &command.proto_data
+        };
+
+        self.writer.write_all(&command.command_id.to_le_bytes())?;
+        self.writer.write_all(&(proto_data.len() as u32).to_le_bytes())?;
+        self.writer.write_all(proto_data)?;
+        // the capture might end with the process being killed
+        self.writer.flush()?;
+
+        Ok(())
+    }
+}
+
+/// Reads all commands of a dump file
+pub fn load(path: &Path) -> Result<Vec<GameCommand>> {
+    let file = File::open(path).with_path("open", path)?;
+    read(BufReader::new(file)).with_path("read", path)
+}
+
+fn read(mut reader: impl Read) -> Result<Vec<GameCommand>> {
+    let mut magic = [0; 8];
+    reader.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(ArchiverError::InvalidData("not a command dump".to_string()));
+    }
+
+    let mut commands = vec![];
+    loop {
+        let mut command_id = [0; 2];
+        match reader.read_exact(&mut command_id) {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e.into()),
+        }
+
+        let mut len = [0; 4];
+        reader.read_exact(&mut len)?;
+
+        let mut proto_data = vec![0; u32::from_le_bytes(len) as usize];
+        reader.read_exact(&mut proto_data)?;
+
+        commands.push(game_command(u16::from_le_bytes(command_id), proto_data));
+    }
+
+    Ok(commands)
+}
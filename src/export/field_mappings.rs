@@ -0,0 +1,194 @@
+//! Field numbers of the protos of other game versions, as patches often shift the obfuscated
+//! field numbers of the messages that are read, which breaks a category until the protos are
+//! updated.
+//!
+//! Mappings are read from a JSON array, tried in order for commands whose proto does not match
+//! the released game. Each maps the field numbers a game version sends to the numbers of the
+//! fields in the released protos, by message name, for example
+//! `[{ "version": "3.3", "messages": { "Quest": { "9": 1, "12": 2 } } }]`.
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+use protobuf::reflect::{MessageDescriptor, ReflectFieldRef, ReflectValueRef, RuntimeFieldType, RuntimeType};
+use protobuf::rt::WireType;
+use protobuf::{CodedInputStream, CodedOutputStream, MessageDyn, MessageFull};
+use serde::Deserialize;
+use tracing::info;
+
+use crate::error::{PathContext, Result};
+
+/// Field numbers of the messages of one game version
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct FieldMapping {
+    /// Game version the field numbers are of, only shown in the log
+    pub version: String,
+    /// Number of the field in the released protos by the number the version sends, by message name
+    pub messages: HashMap<String, HashMap<u32, u32>>,
+}
+
+impl FieldMapping {
+    /// Mappings of a JSON file, in the order they are tried
+    pub fn load(path: &Path) -> Result<Vec<FieldMapping>> {
+        let file = File::open(path).with_path("open", path)?;
+        let mappings: Vec<FieldMapping> = serde_json::from_reader(BufReader::new(file)).with_path("read", path)?;
+        for mapping in &mappings {
+            let messages: Vec<&str> = mapping.messages.keys().map(String::as_str).collect();
+            info!(version = mapping.version, "read field numbers of {}", messages.join(", "));
+        }
+        Ok(mappings)
+    }
+
+    /// Parses `data` as `P` with the field numbers of this version, if it fits: it parses,
+    /// and no field of it or of any message in it is unknown to the released protos
+    pub fn parse<P: MessageFull>(&self, data: &[u8]) -> Option<P> {
+        let data = self.remap(data, &P::descriptor()).ok()?;
+        let proto = P::parse_from_bytes(&data).ok()?;
+        (!has_unknown_fields(&proto)).then_some(proto)
+    }
+
+    /// Encodes `data`, a message of `descriptor`, again with the field numbers of the released protos
+    fn remap(&self, data: &[u8], descriptor: &MessageDescriptor) -> protobuf::Result<Vec<u8>> {
+        let numbers = self.messages.get(descriptor.name());
+        let mut remapped = Vec::with_capacity(data.len());
+        let mut output = CodedOutputStream::vec(&mut remapped);
+
+        let mut input = CodedInputStream::from_bytes(data);
+        while !input.eof()? {
+            let (number, wire_type) = input.read_tag_unpack()?;
+            let number = numbers.and_then(|numbers| numbers.get(&number)).copied().unwrap_or(number);
+            output.write_tag(number, wire_type)?;
+
+            // messages in the message can have shifted field numbers as well
+            match descriptor.field_by_number(number).and_then(|field| message_type(field.runtime_field_type())) {
+                Some(nested) if wire_type == WireType::LengthDelimited => {
+                    let bytes = input.read_bytes()?;
+                    output.write_bytes_no_tag(&self.remap(&bytes, &nested)?)?;
+                }
+                _ => {
+                    let start = input.pos() as usize;
+                    input.skip_field(wire_type)?;
+                    output.write_raw_bytes(&data[start..input.pos() as usize])?;
+                }
+            }
+        }
+
+        output.flush()?;
+        drop(output);
+        Ok(remapped)
+    }
+}
+
+/// Whether `message` or any message in it has fields the protos do not know, which the
+/// released game never sends, so their field numbers were shifted
+pub fn has_unknown_fields(message: &dyn MessageDyn) -> bool {
+    if message.special_fields_dyn().unknown_fields().iter().next().is_some() {
+        return true;
+    }
+
+    let is_unknown = |value: ReflectValueRef| match value {
+        ReflectValueRef::Message(nested) => has_unknown_fields(&*nested),
+        _ => false,
+    };
+    message.descriptor_dyn().fields().any(|field| match field.get_reflect(message) {
+        ReflectFieldRef::Optional(value) => value.value().is_some_and(is_unknown),
+        ReflectFieldRef::Repeated(values) => values.into_iter().any(is_unknown),
+        ReflectFieldRef::Map(_) => false,
+    })
+}
+
+fn message_type(field_type: RuntimeFieldType) -> Option<MessageDescriptor> {
+    match field_type {
+        RuntimeFieldType::Singular(RuntimeType::Message(descriptor))
+        | RuntimeFieldType::Repeated(RuntimeType::Message(descriptor)) => Some(descriptor),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use protobuf::Message;
+    use reliquary::network::gen::proto::GetBagScRsp::GetBagScRsp;
+    use reliquary::network::gen::proto::Material::Material;
+
+    use super::*;
+
+    /// Number a field of `Material` is sent with by the made up game version
+    const SHIFTED: u32 = 1000;
+
+    fn number<P: MessageFull>(field: &str) -> u32 {
+        P::descriptor().field_by_name(field).unwrap().number()
+    }
+
+    fn mapping(from: u32, to: u32) -> FieldMapping {
+        FieldMapping {
+            version: "test".to_string(),
+            messages: HashMap::from([("Material".to_string(), HashMap::from([(from, to)]))]),
+        }
+    }
+
+    /// A material with its tid sent as field `number`
+    fn material(number: u32, tid: u32) -> Vec<u8> {
+        let mut data = vec![];
+        let mut output = CodedOutputStream::vec(&mut data);
+        output.write_uint32(number, tid).unwrap();
+        output.flush().unwrap();
+        drop(output);
+        data
+    }
+
+    fn bag(material: &[u8]) -> Vec<u8> {
+        let mut data = vec![];
+        let mut output = CodedOutputStream::vec(&mut data);
+        output.write_bytes(number::<GetBagScRsp>("material_list"), material).unwrap();
+        output.flush().unwrap();
+        drop(output);
+        data
+    }
+
+    #[test]
+    fn reads_mappings_by_message_name() {
+        let json = r#"[{ "version": "3.3", "messages": { "Quest": { "9": 1, "12": 2 } } }]"#;
+        let mappings: Vec<FieldMapping> = serde_json::from_str(json).unwrap();
+
+        assert_eq!(mappings, [FieldMapping {
+            version: "3.3".to_string(),
+            messages: HashMap::from([("Quest".to_string(), HashMap::from([(9, 1), (12, 2)]))]),
+        }]);
+    }
+
+    #[test]
+    fn parses_shifted_fields() {
+        assert!(Material::descriptor().field_by_number(SHIFTED).is_none());
+        let data = material(SHIFTED, 140001);
+        assert!(has_unknown_fields(&Material::parse_from_bytes(&data).unwrap()));
+
+        let parsed: Material = mapping(SHIFTED, number::<Material>("tid")).parse(&data).unwrap();
+        assert_eq!(parsed.tid, 140001);
+        assert!(!has_unknown_fields(&parsed));
+    }
+
+    #[test]
+    fn parses_shifted_fields_of_nested_messages() {
+        let data = bag(&material(SHIFTED, 140001));
+        assert!(has_unknown_fields(&GetBagScRsp::parse_from_bytes(&data).unwrap()));
+
+        let parsed: GetBagScRsp = mapping(SHIFTED, number::<Material>("tid")).parse(&data).unwrap();
+        assert_eq!(parsed.material_list[0].tid, 140001);
+    }
+
+    #[test]
+    fn rejects_mappings_that_do_not_fit() {
+        let data = bag(&material(SHIFTED, 140001));
+        assert!(mapping(SHIFTED + 1, number::<Material>("tid")).parse::<GetBagScRsp>(&data).is_none());
+        assert!(mapping(SHIFTED, number::<Material>("tid")).parse::<GetBagScRsp>(&[0xff; 3]).is_none());
+    }
+
+    #[test]
+    fn leaves_released_fields_alone() {
+        let data = bag(&material(number::<Material>("tid"), 140001));
+        let parsed: GetBagScRsp = mapping(SHIFTED, number::<Material>("tid")).parse(&data).unwrap();
+        assert_eq!(parsed, GetBagScRsp::parse_from_bytes(&data).unwrap());
+    }
+}
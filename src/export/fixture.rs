@@ -0,0 +1,192 @@
+//! Hand-written commands to feed exporters with, to check how they behave without a capture.
+//!
+//! A fixture is a JSON list of commands, for example:
+//!
+//! ```json
+//! [
+//!     { "type": "token", "uid": 123456789 },
+//!     { "type": "quest_data", "quests": [{ "id": 4010101, "status": "finish" }] },
+//!     { "type": "bag", "materials": [140001] },
+//!     { "type": "sync", "quests": [{ "id": 4010102, "status": "finish" }], "materials": [] },
+//!     { "type": "reward_taken", "quests": [4010102] },
+//!     { "type": "player_detail", "uid": 123456789, "nickname": "Trailblazer" }
+//! ]
+//! ```
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+use protobuf::{EnumOrUnknown, Message};
+use reliquary::network::GameCommand;
+use reliquary::network::gen::command_id;
+use reliquary::network::gen::proto::GetBagScRsp::GetBagScRsp;
+use reliquary::network::gen::proto::GetPlayerDetailInfoScRsp::GetPlayerDetailInfoScRsp;
+use reliquary::network::gen::proto::GetQuestDataScRsp::GetQuestDataScRsp;
+use reliquary::network::gen::proto::Material::Material;
+use reliquary::network::gen::proto::PlayerBasicInfo::PlayerBasicInfo;
+use reliquary::network::gen::proto::PlayerDetailInfo::PlayerDetailInfo;
+use reliquary::network::gen::proto::PlayerGetTokenScRsp::PlayerGetTokenScRsp;
+use reliquary::network::gen::proto::PlayerRecordInfo::PlayerRecordInfo;
+use reliquary::network::gen::proto::PlayerSyncScNotify::PlayerSyncScNotify;
+use reliquary::network::gen::proto::Quest::Quest;
+use reliquary::network::gen::proto::QuestStatus::QuestStatus;
+use reliquary::network::gen::proto::TakeQuestRewardScRsp::TakeQuestRewardScRsp;
+use serde::Deserialize;
+
+use crate::error::{PathContext, Result};
+
+/// A command of a fixture, named after the response it stands in for
+#[derive(Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FixtureCommand {
+    /// `PlayerGetTokenScRsp`, carrying the uid
+    Token { uid: u32 },
+    /// `GetPlayerDetailInfoScRsp`, the profile of a player, with the number of completed
+    /// achievements it shows
+    PlayerDetail {
+        uid: u32,
+        nickname: String,
+        #[serde(default)]
+        achievement_count: u32,
+    },
+    /// `GetQuestDataScRsp`, carrying achievements among other quests
+    QuestData { quests: Vec<FixtureQuest> },
+    /// `GetBagScRsp`, with materials by id, which include books
+    Bag { materials: Vec<u32> },
+    /// `PlayerSyncScNotify`, with the quests and materials that changed during the session,
+    /// and the nickname if the basic info of the account changed
+    Sync {
+        #[serde(default)]
+        quests: Vec<FixtureQuest>,
+        #[serde(default)]
+        materials: Vec<u32>,
+        #[serde(default)]
+        nickname: Option<String>,
+    },
+    /// `TakeQuestRewardScRsp`, with the quests whose reward was claimed by id
+    RewardTaken { quests: Vec<u32> },
+}
+
+#[derive(Deserialize, Debug)]
+pub struct FixtureQuest {
+    pub id: u32,
+    pub status: FixtureQuestStatus,
+    /// Progress of quests in progress
+    #[serde(default)]
+    pub progress: u32,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum FixtureQuestStatus {
+    Doing,
+    Finish,
+    Close,
+}
+
+/// Reads a fixture and turns it into the commands the game would have sent
+pub fn load(path: &Path) -> Result<Vec<GameCommand>> {
+    let file = File::open(path).with_path("open", path)?;
+    let commands: Vec<FixtureCommand> = serde_json::from_reader(BufReader::new(file)).with_path("read", path)?;
+    commands.iter().map(FixtureCommand::to_game_command).collect()
+}
+
+impl FixtureCommand {
+    pub fn to_game_command(&self) -> Result<GameCommand> {
+        let (command_id, proto_data) = match self {
+            FixtureCommand::Token { uid } => {
+                let proto = PlayerGetTokenScRsp {
+                    uid: *uid,
+                    ..Default::default()
+                };
+                (command_id::PlayerGetTokenScRsp, proto.write_to_bytes()?)
+            }
+            FixtureCommand::PlayerDetail { uid, nickname, achievement_count } => {
+                let proto = GetPlayerDetailInfoScRsp {
+                    detail_info: Some(PlayerDetailInfo {
+                        uid: *uid,
+                        nickname: nickname.clone(),
+                        record_info: Some(PlayerRecordInfo {
+                            achievement_count: *achievement_count,
+                            ..Default::default()
+                        }).into(),
+                        ..Default::default()
+                    }).into(),
+                    ..Default::default()
+                };
+                (command_id::GetPlayerDetailInfoScRsp, proto.write_to_bytes()?)
+            }
+            FixtureCommand::QuestData { quests } => {
+                let proto = GetQuestDataScRsp {
+                    quest_list: quests.iter().map(FixtureQuest::to_proto).collect(),
+                    ..Default::default()
+                };
+                (command_id::GetQuestDataScRsp, proto.write_to_bytes()?)
+            }
+            FixtureCommand::Bag { materials } => {
+                let proto = GetBagScRsp {
+                    material_list: materials.iter().copied().map(material).collect(),
+                    ..Default::default()
+                };
+                (command_id::GetBagScRsp, proto.write_to_bytes()?)
+            }
+            FixtureCommand::Sync { quests, materials, nickname } => {
+                let basic_info = nickname.as_ref().map(|nickname| PlayerBasicInfo {
+                    nickname: nickname.clone(),
+                    ..Default::default()
+                });
+                let proto = PlayerSyncScNotify {
+                    quest_list: quests.iter().map(FixtureQuest::to_proto).collect(),
+                    material_list: materials.iter().copied().map(material).collect(),
+                    basic_info: basic_info.into(),
+                    ..Default::default()
+                };
+                (command_id::PlayerSyncScNotify, proto.write_to_bytes()?)
+            }
+            FixtureCommand::RewardTaken { quests } => {
+                let proto = TakeQuestRewardScRsp {
+                    succ_quest_id_list: quests.clone(),
+                    ..Default::default()
+                };
+                (command_id::TakeQuestRewardScRsp, proto.write_to_bytes()?)
+            }
+        };
+
+        Ok(game_command(command_id, proto_data))
+    }
+}
+
+fn material(tid: u32) -> Material {
+    Material {
+        tid,
+        num: 1,
+        ..Default::default()
+    }
+}
+
+/// Command as the sniffer would have parsed it from a packet
+pub(crate) fn game_command(command_id: u16, proto_data: Vec<u8>) -> GameCommand {
+    GameCommand {
+        command_id,
+        header_len: 0,
+        data_len: proto_data.len() as u32,
+        proto_data,
+    }
+}
+
+impl FixtureQuest {
+    fn to_proto(&self) -> Quest {
+        let status = match self.status {
+            FixtureQuestStatus::Doing => QuestStatus::QUEST_DOING,
+            FixtureQuestStatus::Finish => QuestStatus::QUEST_FINISH,
+            FixtureQuestStatus::Close => QuestStatus::QUEST_CLOSE,
+        };
+
+        Quest {
+            id: self.id,
+            status: EnumOrUnknown::new(status),
+            progress: self.progress,
+            ..Default::default()
+        }
+    }
+}
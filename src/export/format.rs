@@ -0,0 +1,256 @@
+//! Export formats as conversions of the collected [`Account`], sharing the parsing of commands.
+use std::io::{self, Write};
+use std::sync::Arc;
+
+use reliquary::network::GameCommand;
+use serde::Serialize;
+use serde_json::Value;
+use tracing::{info, instrument};
+
+use crate::database::Database;
+use crate::export::command_ids::CommandIds;
+use crate::export::field_mappings::FieldMapping;
+use crate::export::{ExportObserver, Exporter, Progress};
+use crate::filter::{self, ItemFilter};
+use crate::model::{Account, Collector, CompletedStatuses};
+use crate::validation::ValidationReport;
+
+/// Output format, converting the data of an account into what is written to the export file
+pub trait Format {
+    type Export;
+    /// Converts `account`, the database being the one the account was collected with
+    fn convert(&self, account: &Account, database: &Database) -> Self::Export;
+    /// Writes the items of `account` that were not written before to `writer`,
+    /// for formats that can be written incrementally. Does nothing unless overridden
+    fn flush(&mut self, _account: &Account, _database: &Database, _writer: &mut dyn Write) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Object safe version of [`Format`], with the export serialized to JSON.
+///
+/// Implemented for every format with a serializable export.
+pub trait AnyFormat: Send {
+    fn convert_json(&self, account: &Account, database: &Database) -> Value;
+    fn flush(&mut self, account: &Account, database: &Database, writer: &mut dyn Write) -> io::Result<()>;
+}
+
+impl<F> AnyFormat for F
+where
+    F: Format + Send,
+    F::Export: Serialize,
+{
+    fn convert_json(&self, account: &Account, database: &Database) -> Value {
+        // exports are plain data, serializing them cannot fail
+        serde_json::to_value(self.convert(account, database)).unwrap()
+    }
+
+    fn flush(&mut self, account: &Account, database: &Database, writer: &mut dyn Write) -> io::Result<()> {
+        Format::flush(self, account, database, writer)
+    }
+}
+
+/// Format selected at runtime, e.g. from an [`ExporterRegistry`]
+///
+/// [`ExporterRegistry`]: crate::export::registry::ExporterRegistry
+impl Format for Box<dyn AnyFormat> {
+    type Export = Value;
+
+    fn convert(&self, account: &Account, database: &Database) -> Self::Export {
+        (**self).convert_json(account, database)
+    }
+
+    fn flush(&mut self, account: &Account, database: &Database, writer: &mut dyn Write) -> io::Result<()> {
+        AnyFormat::flush(&mut **self, account, database, writer)
+    }
+}
+
+/// Several formats of the same account, so that exporting in several formats collects the
+/// account only once. The export contains the export of every format, in the order they were added
+#[derive(Default)]
+pub struct Formats(Vec<Box<dyn AnyFormat>>);
+
+impl Formats {
+    pub fn new(formats: Vec<Box<dyn AnyFormat>>) -> Formats {
+        Formats(formats)
+    }
+}
+
+impl Format for Formats {
+    type Export = Vec<Value>;
+
+    fn convert(&self, account: &Account, database: &Database) -> Self::Export {
+        self.0.iter().map(|format| (**format).convert_json(account, database)).collect()
+    }
+
+    fn flush(&mut self, account: &Account, database: &Database, writer: &mut dyn Write) -> io::Result<()> {
+        for format in &mut self.0 {
+            AnyFormat::flush(&mut **format, account, database, writer)?;
+        }
+        Ok(())
+    }
+}
+
+/// Exporter collecting an [`Account`] from the commands and exporting it in format `F`
+pub struct ModelExporter<F> {
+    collector: Collector,
+    format: F,
+    filters: Vec<ItemFilter>,
+}
+
+impl<F: Format> ModelExporter<F> {
+    pub fn with_format(database: impl Into<Arc<Database>>, format: F) -> ModelExporter<F> {
+        ModelExporter {
+            collector: Collector::new(database),
+            format,
+            filters: vec![],
+        }
+    }
+
+    /// Recognizes commands by these ids instead of the ids of the released game
+    pub fn set_command_ids(&mut self, command_ids: CommandIds) {
+        self.collector.set_command_ids(command_ids);
+    }
+
+    pub fn set_field_mappings(&mut self, field_mappings: Vec<FieldMapping>) {
+        self.collector.set_field_mappings(field_mappings);
+    }
+
+    /// Exports under `uid` whatever uid is detected, see [`Collector::set_uid_override`]
+    pub fn set_uid_override(&mut self, uid: u32) {
+        self.collector.set_uid_override(uid);
+    }
+
+    /// Counts achievements with these statuses as completed, instead of all completed ones
+    pub fn set_completed_statuses(&mut self, completed: CompletedStatuses) {
+        self.collector.set_completed_statuses(completed);
+    }
+
+    /// Only exports the items that pass all `filters`, while still collecting all of them
+    pub fn set_filters(&mut self, filters: Vec<ItemFilter>) {
+        self.filters = filters;
+    }
+
+    /// Notifies `observer` of every newly detected uid, achievement and book
+    pub fn add_observer(&mut self, observer: impl ExportObserver + 'static) {
+        self.collector.add_observer(observer);
+    }
+
+    /// Data of the account collected so far, before it is converted
+    pub fn account(&self) -> &Account {
+        self.collector.account()
+    }
+
+    fn convert(&self, account: &Account) -> F::Export {
+        let database = self.collector.database();
+        if self.filters.is_empty() {
+            self.format.convert(account, database)
+        } else {
+            self.format.convert(&filter::apply(&self.filters, account, database), database)
+        }
+    }
+}
+
+impl<F: Format> Exporter for ModelExporter<F> {
+    type Export = F::Export;
+
+    fn read_command(&mut self, command: &GameCommand) {
+        self.collector.read_command(command);
+    }
+
+    fn read_commands(&mut self, commands: &[GameCommand]) {
+        self.collector.read_commands(commands);
+    }
+
+    fn is_finished(&self) -> bool {
+        self.account().uid.is_some()
+            && self.collector.has_read_quests()
+            && self.collector.has_read_inventory()
+            && self.is_settled()
+    }
+
+    /// Whether the rest of a large inventory or quest data cannot follow in the next packets anymore
+    fn is_settled(&self) -> bool {
+        self.collector.is_inventory_complete() && self.collector.is_quest_data_complete()
+    }
+
+    fn missing(&self) -> Vec<&'static str> {
+        let account = self.account();
+        let mut missing = vec![];
+
+        if account.uid.is_none() {
+            missing.push("uid (no token packet seen)");
+        }
+
+        // items of a restored export or of a sync do not mean the whole category was read
+        if !self.collector.has_read_quests() {
+            missing.push("achievements (no quest data packet seen)");
+        }
+
+        if !self.collector.has_read_inventory() {
+            missing.push("books (no inventory packet seen)");
+        }
+
+        missing
+    }
+
+    fn progress(&self) -> Vec<Progress> {
+        let account = self.account();
+        vec![
+            Progress {
+                name: "uid",
+                collected: account.uid.is_some(),
+                count: None,
+            },
+            Progress {
+                name: "achievements",
+                collected: self.collector.has_read_quests(),
+                count: Some(account.achievements.len()),
+            },
+            Progress {
+                name: "books",
+                collected: self.collector.has_read_inventory(),
+                count: Some(account.books.len()),
+            },
+        ]
+    }
+
+    fn parse_failures(&self) -> u64 {
+        self.collector.parse_failures()
+    }
+
+    fn export_partial(&self) -> Self::Export {
+        self.convert(self.collector.account())
+    }
+
+    fn restore(&mut self, account: &Account) {
+        self.collector.restore(account.clone());
+    }
+
+    fn flush(&mut self, writer: &mut dyn Write) -> io::Result<()> {
+        let (account, database) = (self.collector.account(), self.collector.database());
+        if self.filters.is_empty() {
+            self.format.flush(account, database, writer)
+        } else {
+            self.format.flush(&filter::apply(&self.filters, account, database), database, writer)
+        }
+    }
+
+    #[instrument(skip_all)]
+    fn export(self) -> Self::Export {
+        let account = self.account();
+        info!(achievements = account.achievements.len(), books = account.books.len(), "exporting collected data");
+        ValidationReport::new(account, self.collector.database()).log();
+        self.export_partial()
+    }
+
+    fn take_switched_accounts(&mut self) -> Vec<Self::Export> {
+        let accounts = self.collector.take_switched_accounts();
+        accounts.iter()
+            .map(|account| {
+                ValidationReport::new(account, self.collector.database()).log();
+                self.convert(account)
+            })
+            .collect()
+    }
+}
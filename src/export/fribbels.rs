@@ -3,271 +3,353 @@
 //!
 //! [Fribbels HSR Optimizer]: https://github.com/fribbels/hsr-optimizer
 //! [kel-z's HSR-Scanner]: https://github.com/kel-z/HSR-Scanner
-use std::collections::HashMap;
-
-use base64::Engine;
-use base64::prelude::BASE64_STANDARD;
-use reliquary::network::GameCommand;
-use reliquary::network::gen::command_id;
-use reliquary::network::gen::proto::GetBagScRsp::GetBagScRsp;
-use reliquary::network::gen::proto::GetQuestDataScRsp::GetQuestDataScRsp;
-use reliquary::network::gen::proto::Material::Material;
-use reliquary::network::gen::proto::PlayerGetTokenScRsp::PlayerGetTokenScRsp;
-use reliquary::network::gen::proto::Quest::Quest;
-use reliquary::network::gen::proto::QuestStatus::QuestStatus::{QUEST_CLOSE, QUEST_FINISH};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use std::sync::Arc;
+
+use chrono::{Local, NaiveDate};
 use serde::{Deserialize, Serialize};
-use serde::de::DeserializeOwned;
-use tracing::{debug, info, instrument, trace, warn};
+use tracing::{debug, warn};
 
-use crate::export::Exporter;
+use crate::database::Database;
+use crate::error::{PathContext, Result};
+use crate::export::format::{Format, ModelExporter};
+use crate::model::{Account, Achievement, Book, UnknownIds};
 
-const BASE_RESOURCE_URL: &str = "https://raw.githubusercontent.com/Dimbreath/StarRailData/master";
+/// Version of the schema of the export, raised on changes that older readers cannot handle.
+///
+/// Version 4 added the jade, the dates items were first captured on, the unclaimed
+/// achievements and the ids missing from the game data, see [`Export::upgrade`]
+pub const VERSION: u32 = 4;
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Export {
-    pub source: &'static str,
-    pub build: &'static str,
+    pub source: String,
+    pub build: String,
     pub version: u32,
     pub metadata: Metadata,
-    achievements: Vec<u32>,
-    books: Vec<u32>,
+    achievements: BTreeSet<u32>,
+    /// Books in the inventory, read or not, as the captured packets do not tell which were read
+    books: BTreeSet<u32>,
+    /// Ids of completed achievements and obtained books missing from the game data, usually
+    /// items of a game version the game data was not updated for yet. Converting the export
+    /// once the game data knows them adds them to the items
+    #[serde(default, skip_serializing_if = "UnknownIds::is_empty")]
+    pub unknown_ids: UnknownIds,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-pub struct Metadata {
-    pub uid: Option<u32>,
-}
-
-pub struct OptimizerExporter {
-    database: Database,
-    uid: Option<u32>,
-    achievements: Vec<u32>,
-    books: Vec<u32>,
-}
-
-impl OptimizerExporter {
-    pub fn new(database: Database) -> OptimizerExporter {
-        OptimizerExporter {
-            database,
-            uid: None,
-            achievements: vec![],
-            books: vec![],
+impl Export {
+    /// Reads an export written before. Exports of older versions are read as well,
+    /// the metadata they lack is left empty
+    pub fn load(path: &Path) -> Result<Export> {
+        let file = File::open(path).with_path("open", path)?;
+        let mut export: Export = serde_json::from_reader(BufReader::new(file)).with_path("read", path)?;
+        // written by older versions that exported whatever uid the token packet had
+        if export.metadata.uid == Some(0) {
+            warn!("export has uid 0, reading it without a uid");
+            export.metadata.uid = None;
         }
+        if export.version > VERSION {
+            warn!(version = export.version, "export was written by a newer version, newer fields are ignored");
+        }
+        Ok(export)
     }
 
-    pub fn set_uid(&mut self, uid: u32) {
-        self.uid = Some(uid);
+    /// Brings an export of an older version up to [`VERSION`], one version at a time
+    pub fn upgrade(mut self, database: &Database) -> Export {
+        if self.version < 4 {
+            self.upgrade_to_v4(database);
+        }
+        self
     }
 
-    pub fn add_inventory(&mut self, bag: GetBagScRsp) {
-        let books: Vec<Book> = bag.material_list.iter()
-            .filter_map(|r| export_proto_book(&self.database, r))
+    /// Fills in the metadata version 3 lacks: its items are dated today, its achievements
+    /// count as claimed and ids missing from the game data are moved to the unknown ids
+    fn upgrade_to_v4(&mut self, database: &Database) {
+        let today = Local::now().date_naive();
+        let unknown_achievements: BTreeSet<u32> = self.achievements.iter()
+            .copied()
+            .filter(|&id| !database.has_achievement(id))
             .collect();
-
-        info!(num=books.len(), "found books");
-        let mut ids: Vec<u32> = books.iter().map(|book| book.id.clone()).collect();
-        self.books.append(&mut ids);
-    }
-
-    pub fn add_achievements(&mut self, quest: GetQuestDataScRsp ) {
-        let achievements: Vec<Achievement> = quest.quest_list.iter()
-            .filter_map(|r| export_proto_achievement(&self.database, r))
+        let unknown_books: BTreeSet<u32> = self.books.iter()
+            .copied()
+            .filter(|&id| !database.has_book(id))
             .collect();
+        self.achievements.retain(|id| !unknown_achievements.contains(id));
+        self.books.retain(|id| !unknown_books.contains(id));
+        self.unknown_ids.achievements.extend(unknown_achievements);
+        self.unknown_ids.books.extend(unknown_books);
 
-        info!(num=achievements.len(), "found achievements");
-        let mut ids: Vec<u32> = achievements.iter().map(|achievement| achievement.id.clone()).collect();
-        self.achievements.append(&mut ids);
+        for &id in &self.achievements {
+            self.metadata.first_seen.achievements.entry(id).or_insert(today);
+        }
+        for &id in &self.books {
+            self.metadata.first_seen.books.entry(id).or_insert(today);
+        }
+        self.metadata.unclaimed.clear();
+
+        let earned = self.achievements.iter().map(|&id| database.achievement_jade(id)).sum();
+        self.metadata.jade = Jade {
+            earned,
+            remaining: database.total_achievement_jade().saturating_sub(earned),
+        };
+        debug!(from = self.version, achievements = self.achievements.len(), books = self.books.len(), "upgraded export to version 4");
+        self.version = 4;
     }
-}
 
-impl Exporter for OptimizerExporter {
-    type Export = Export;
+    /// Ids listed more than once in the export at `path` by category, which [`Export::load`]
+    /// reads only once. Exports written by the archiver never contain any
+    pub fn duplicates(path: &Path) -> Result<BTreeMap<&'static str, BTreeSet<u32>>> {
+        #[derive(Deserialize)]
+        struct Items {
+            achievements: Vec<u32>,
+            books: Vec<u32>,
+        }
 
-    fn read_command(&mut self, command: GameCommand) {
-        match command.command_id {
-            command_id::PlayerGetTokenScRsp => {
-                debug!("detected uid");
-                let cmd = command.parse_proto::<PlayerGetTokenScRsp>();
-                match cmd {
-                    Ok(cmd) => {
-                        self.set_uid(cmd.uid)
-                    }
-                    Err(error) => {
-                        warn!(%error, "could not parse token command");
-                    }
-                }
-            }
-            command_id::GetBagScRsp => {
-                debug!("detected inventory packet");
-                let cmd = command.parse_proto::<GetBagScRsp>();
-                match cmd {
-                    Ok(cmd) => {
-                        self.add_inventory(cmd)
-                    }
-                    Err(error) => {
-                        warn!(%error, "could not parse inventory data command");
-                    }
-                }
-            }
-            command_id::GetQuestDataScRsp => {
-                debug!("detected quest packet");
-                let cmd = command.parse_proto::<GetQuestDataScRsp>();
-                match cmd {
-                    Ok(cmd) => {
-                        self.add_achievements(cmd)
-                    }
-                    Err(error) => {
-                        warn!(%error, "could not parse quest data command");
-                    }
-                }
-            }
-            _ => {
-                trace!(command_id=command.command_id, tag=command.get_command_name(), "ignored");
+        let file = File::open(path).with_path("open", path)?;
+        let items: Items = serde_json::from_reader(BufReader::new(file)).with_path("read", path)?;
+
+        let mut duplicates = BTreeMap::new();
+        for (category, ids) in [("achievements", items.achievements), ("books", items.books)] {
+            let mut seen = BTreeSet::new();
+            let repeated: BTreeSet<u32> = ids.into_iter().filter(|&id| !seen.insert(id)).collect();
+            if !repeated.is_empty() {
+                duplicates.insert(category, repeated);
             }
         }
+        Ok(duplicates)
     }
 
-    fn is_finished(&self) -> bool {
-        self.uid.is_some()
-            && !self.achievements.is_empty()
-            && !self.books.is_empty()
+    /// Ids of the completed achievements
+    pub fn achievements(&self) -> &BTreeSet<u32> {
+        &self.achievements
     }
 
-    #[instrument(skip_all)]
-    fn export(self) -> Self::Export {
-        info!("exporting collected data");
+    /// Ids of the obtained books
+    pub fn books(&self) -> &BTreeSet<u32> {
+        &self.books
+    }
 
-        if self.uid.is_none() {
-            warn!("uid was not recorded");
+    /// Items that were added and removed in `newer`, compared to this export
+    pub fn diff(&self, newer: &Export) -> ExportDiff {
+        ExportDiff {
+            achievements: ItemsDiff::new(&self.achievements, &newer.achievements),
+            books: ItemsDiff::new(&self.books, &newer.books),
         }
+    }
 
-        if self.achievements.is_empty() {
-            warn!("relics were not recorded");
-        }
+    /// Account with the items of the export, to convert it into another format or version.
+    ///
+    /// Items of older exports that lack the date they were first captured on are dated today,
+    /// achievements not listed as unclaimed count as claimed. Unknown ids the database knows
+    /// by now are added to the items
+    pub fn to_account(&self, database: &Database) -> Account {
+        let today = Local::now().date_naive();
+        let first_seen = |dates: &BTreeMap<u32, NaiveDate>, id| dates.get(&id).copied().unwrap_or(today);
+        let (known_achievements, unknown_achievements): (BTreeSet<u32>, BTreeSet<u32>) = self.unknown_ids.achievements.iter()
+            .copied()
+            .partition(|&id| database.has_achievement(id));
+        let (known_books, unknown_books): (BTreeSet<u32>, BTreeSet<u32>) = self.unknown_ids.books.iter()
+            .copied()
+            .partition(|&id| database.has_book(id));
+
+        let achievements = self.achievements.union(&known_achievements)
+            .map(|&id| (id, Achievement {
+                id,
+                name: database.achievement(id).and_then(|a| a.name.clone()),
+                jade: database.achievement_jade(id),
+                reward_taken: !self.metadata.unclaimed.contains(&id),
+                first_seen: first_seen(&self.metadata.first_seen.achievements, id),
+            }))
+            .collect();
+        let books = self.books.union(&known_books)
+            .map(|&id| (id, Book {
+                id,
+                name: database.book(id).and_then(|b| b.name.clone()),
+                first_seen: first_seen(&self.metadata.first_seen.books, id),
+            }))
+            .collect();
 
-        if self.books.is_empty() {
-            warn!("relics were not recorded");
+        Account {
+            uid: self.metadata.uid,
+            achievements,
+            books,
+            unknown: UnknownIds {
+                achievements: unknown_achievements,
+                books: unknown_books,
+            },
+            reported_achievements: None,
         }
+    }
 
-        Export {
-            source: "completionist_archiver",
-            build: env!("CARGO_PKG_VERSION"),
-            version: 3,
-            metadata: Metadata {
-                uid: self.uid,
-            },
-            achievements: self.achievements,
-            books: self.books,
+    /// Adds the items of an earlier export of the same account that were not captured this time,
+    /// e.g. because the inventory was not opened, keeping the earliest date each item was seen.
+    ///
+    /// Achievements only found in `previous` keep whether their reward was claimed. The jade is
+    /// counted again from the merged achievements with `database`, like a new export would be.
+    /// Returns `false` without merging if `previous` is an export of a different account
+    pub fn merge(&mut self, previous: &Export, database: &Database) -> bool {
+        if !self.is_same_account(previous) {
+            return false;
         }
+        self.metadata.uid = self.metadata.uid.or(previous.metadata.uid);
+
+        let unclaimed: Vec<u32> = previous.metadata.unclaimed.iter()
+            .filter(|id| !self.achievements.contains(id))
+            .copied()
+            .collect();
+        self.metadata.unclaimed.extend(unclaimed);
+
+        let new_achievements = merge_items(&mut self.achievements, &previous.achievements);
+        let new_books = merge_items(&mut self.books, &previous.books);
+        merge_items(&mut self.unknown_ids.achievements, &previous.unknown_ids.achievements);
+        merge_items(&mut self.unknown_ids.books, &previous.unknown_ids.books);
+        debug!(achievements = new_achievements, books = new_books, "kept items only found in previous export");
+
+        merge_first_seen(&mut self.metadata.first_seen.achievements, &previous.metadata.first_seen.achievements);
+        merge_first_seen(&mut self.metadata.first_seen.books, &previous.metadata.first_seen.books);
+
+        // unclaimed ids of achievements that are not in either export count for nothing
+        let achievements = &self.achievements;
+        self.metadata.unclaimed.retain(|id| achievements.contains(id));
+        let earned = self.to_account(database).earned_jade();
+        self.metadata.jade = Jade {
+            earned,
+            remaining: database.total_achievement_jade().saturating_sub(earned),
+        };
+
+        true
+    }
+
+    /// Whether both exports are of the same account, exports without uid matching any account
+    pub fn is_same_account(&self, other: &Export) -> bool {
+        self.metadata.uid.is_none() || other.metadata.uid.is_none() || self.metadata.uid == other.metadata.uid
     }
 }
 
-pub struct Database {
-    achievement_list: Vec<u32>,
-    book_list: Vec<u32>,
-    // text_map: TextMap,
-    keys: HashMap<u32, Vec<u8>>,
+/// Difference between the items of two exports, see [`Export::diff`]
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct ExportDiff {
+    pub achievements: ItemsDiff,
+    pub books: ItemsDiff,
 }
 
-impl Database {
-    #[instrument(name = "config_map")]
-    pub fn new_from_online() -> Self {
-        info!("initializing database from online sources, this might take a while...");
-        Database {
-            achievement_list: Self::load_online_achievement_list(),
-            book_list: Self::load_online_book_list(),
-            // text_map: Self::load_online_text_map(),
-            keys: Self::load_online_keys(),
-        }
+impl ExportDiff {
+    pub fn is_empty(&self) -> bool {
+        self.achievements.is_empty() && self.books.is_empty()
     }
-    // TODO: new_from_source
-
-    fn load_online_achievement_list() -> Vec<u32> {
-        let json_object = Self::get_json(format!("{BASE_RESOURCE_URL}/ExcelOutput/AchievementData.json"));
-        let mut achievement_list = vec![];
-        for (_key, value) in json_object.as_object().unwrap() {
-            let achievement_id: u32 = value["AchievementID"].as_u64().unwrap() as u32;
-            achievement_list.push(achievement_id)
-        }
-        achievement_list
-    }
-    fn load_online_book_list() -> Vec<u32> {
-        let json_object = Self::get_json(format!("{BASE_RESOURCE_URL}/ExcelOutput/LocalbookConfig.json"));
-        let mut book_list = vec![];
-        for (_key, value) in json_object.as_object().unwrap() {
-            let book_id: u32 = value["BookID"].as_u64().unwrap() as u32;
-            book_list.push(book_id)
-        }
-        book_list
+
+    /// Jade rewarded by the achievements that were added
+    pub fn earned_jade(&self, database: &Database) -> u32 {
+        self.achievements.added.iter().map(|&id| database.achievement_jade(id)).sum()
     }
-    /*fn load_online_text_map() -> TextMap {
-        Self::get(format!("{BASE_RESOURCE_URL}/TextMap/TextMapEN.json"))
-    }*/
+}
 
-    fn load_online_keys() -> HashMap<u32, Vec<u8>> {
-        let keys: HashMap<u32, String> = Self::get("https://raw.githubusercontent.com/tamilpp25/Iridium-SR/main/data/Keys.json".to_string());
-        let mut keys_bytes = HashMap::new();
+/// Ids of a category that were added and removed
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct ItemsDiff {
+    pub added: BTreeSet<u32>,
+    pub removed: BTreeSet<u32>,
+}
 
-        for (k, v) in keys {
-            keys_bytes.insert(k, BASE64_STANDARD.decode(v).unwrap());
+impl ItemsDiff {
+    fn new(old: &BTreeSet<u32>, new: &BTreeSet<u32>) -> ItemsDiff {
+        ItemsDiff {
+            added: new.difference(old).copied().collect(),
+            removed: old.difference(new).copied().collect(),
         }
-
-        keys_bytes
     }
 
-    fn get<T: DeserializeOwned>(url: String) -> T {
-        debug!(url, "requesting from resource");
-        ureq::get(&url)
-            .call()
-            .unwrap()
-            .into_json()
-            .unwrap()
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
     }
+}
 
-    fn get_json(url: String) -> serde_json::Value {
-        debug!(url, "requesting from resource");
-        ureq::get(&url)
-            .call()
-            .unwrap()
-            .into_json()
-            .unwrap()
-    }
+/// Adds the items of `previous` missing from `items`, returning how many were added
+fn merge_items(items: &mut BTreeSet<u32>, previous: &BTreeSet<u32>) -> usize {
+    let len = items.len();
+    items.extend(previous);
+    items.len() - len
+}
 
-    pub fn keys(&self) -> &HashMap<u32, Vec<u8>> {
-        &self.keys
+fn merge_first_seen(first_seen: &mut BTreeMap<u32, NaiveDate>, previous: &BTreeMap<u32, NaiveDate>) {
+    for (&id, &date) in previous {
+        first_seen.entry(id)
+            .and_modify(|first| *first = (*first).min(date))
+            .or_insert(date);
     }
 }
 
-#[tracing::instrument(name = "achievement", skip_all, fields(id = proto.id))]
-fn export_proto_achievement(db: &Database, proto: &Quest) -> Option<Achievement> {
-    if (proto.status.unwrap() == QUEST_CLOSE || proto.status.unwrap() == QUEST_FINISH) && db.achievement_list.contains(&proto.id) {
-        Some(Achievement {
-            id: proto.id,
-        })
-    }
-    else {
-        None
-    }
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Metadata {
+    pub uid: Option<u32>,
+    #[serde(default)]
+    pub jade: Jade,
+    /// Dates the items were first captured on
+    #[serde(default)]
+    pub first_seen: FirstSeen,
+    /// Completed achievements whose reward has not been claimed yet
+    #[serde(default)]
+    pub unclaimed: BTreeSet<u32>,
 }
 
-#[tracing::instrument(name = "book", skip_all, fields(id = proto.tid))]
-fn export_proto_book(db: &Database, proto: &Material) -> Option<Book> {
-    if db.book_list.contains(&proto.tid) {
-        Some(Book {
-            id: proto.tid,
-        })
-    }
-    else {
-        None
-    }
+/// Dates items were first captured on, by id
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct FirstSeen {
+    pub achievements: BTreeMap<u32, NaiveDate>,
+    pub books: BTreeMap<u32, NaiveDate>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-pub struct Achievement {
-    pub id: u32,
+/// Stellar jade rewarded by achievements
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct Jade {
+    /// Jade rewarded by the completed achievements
+    pub earned: u32,
+    /// Jade that can still be earned by completing the remaining achievements
+    pub remaining: u32,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-pub struct Book {
-    pub id: u32,
-}
\ No newline at end of file
+/// Format of the Fribbels HSR Optimizer
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OptimizerFormat;
+
+pub type OptimizerExporter = ModelExporter<OptimizerFormat>;
+
+impl OptimizerExporter {
+    pub fn new(database: impl Into<Arc<Database>>) -> OptimizerExporter {
+        ModelExporter::with_format(database, OptimizerFormat)
+    }
+}
+
+impl Format for OptimizerFormat {
+    type Export = Export;
+
+    fn convert(&self, account: &Account, database: &Database) -> Self::Export {
+        let earned = account.earned_jade();
+        let remaining = database.total_achievement_jade().saturating_sub(earned);
+        debug!(earned, remaining, "stellar jade from achievements");
+
+        Export {
+            source: "completionist_archiver".to_string(),
+            build: env!("CARGO_PKG_VERSION").to_string(),
+            version: VERSION,
+            metadata: Metadata {
+                uid: account.uid,
+                jade: Jade {
+                    earned,
+                    remaining,
+                },
+                first_seen: FirstSeen {
+                    achievements: account.achievements.values().map(|a| (a.id, a.first_seen)).collect(),
+                    books: account.books.values().map(|b| (b.id, b.first_seen)).collect(),
+                },
+                unclaimed: account.unclaimed().map(|a| a.id).collect(),
+            },
+            achievements: account.achievements.keys().copied().collect(),
+            books: account.books.keys().copied().collect(),
+            unknown_ids: account.unknown.clone(),
+        }
+    }
+}
@@ -0,0 +1,74 @@
+//! Merging fribbels exports of the same account, e.g. captured on different devices,
+//! into one with every item and the earliest date it was captured on.
+use std::path::PathBuf;
+
+use serde::Serialize;
+use tracing::info;
+
+use crate::database::Database;
+use crate::error::{ArchiverError, Result};
+use crate::export::fribbels::Export;
+
+/// Exports that are of another account than the others, so nothing was merged
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct AccountConflict {
+    /// Uid of the account the exports were merged into
+    pub uid: u32,
+    /// Indices of the exports of other accounts
+    pub exports: Vec<usize>,
+}
+
+/// Merges exports, oldest first, into the newest one, which keeps its metadata.
+///
+/// The exports are merged into the account of the newest export with a uid, so that every
+/// conflicting export is reported. Nothing is merged if any export is of another account.
+/// The jade of the merged export is counted with `database`, see [`Export::merge`].
+///
+/// # Panics
+///
+/// If there are no exports
+pub fn merge(mut exports: Vec<Export>, database: &Database) -> std::result::Result<Export, AccountConflict> {
+    if let Some(account) = exports.iter().rev().find(|export| export.metadata.uid.is_some()) {
+        let conflicts: Vec<usize> = exports.iter()
+            .enumerate()
+            .filter(|(_, export)| !export.is_same_account(account))
+            .map(|(index, _)| index)
+            .collect();
+        if !conflicts.is_empty() {
+            return Err(AccountConflict {
+                uid: account.metadata.uid.unwrap_or_default(),
+                exports: conflicts,
+            });
+        }
+    }
+
+    let mut merged = exports.pop().expect("there are exports to merge");
+    for export in exports.iter().rev() {
+        merged.merge(export, database);
+    }
+    Ok(merged)
+}
+
+/// Merges the exports at `paths` like [`merge`], failing with the paths of the exports of other accounts
+pub fn merge_files(paths: &[PathBuf], database: &Database) -> Result<Export> {
+    let exports = paths.iter().map(|path| Export::load(path)).collect::<Result<Vec<_>>>()?;
+    let uids: Vec<u32> = exports.iter().map(|export| export.metadata.uid.unwrap_or_default()).collect();
+
+    let merged = merge(exports, database).map_err(|conflict| {
+        let conflicts: Vec<String> = conflict.exports.iter()
+            .map(|&index| format!("{} (uid {})", paths[index].display(), uids[index]))
+            .collect();
+        ArchiverError::InvalidData(format!(
+            "can only merge exports of the same account, but these are not of uid {}: {}",
+            conflict.uid,
+            conflicts.join(", ")
+        ))
+    })?;
+    info!(
+        achievements = merged.achievements().len(),
+        books = merged.books().len(),
+        "merged {} exports",
+        paths.len()
+    );
+    Ok(merged)
+}
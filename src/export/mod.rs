@@ -1,10 +1,257 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::io::{self, Write};
+use std::sync::mpsc;
+use std::time::Duration;
+
 use reliquary::network::GameCommand;
+use serde::Serialize;
+
+use crate::model::Account;
 
+pub mod boxed;
+pub mod checksum;
+pub mod command_ids;
+pub mod csv;
+pub mod dump;
+pub mod field_mappings;
+pub mod fixture;
+pub mod format;
 pub mod fribbels;
+pub mod merge;
+pub mod ndjson;
+pub mod output;
+pub mod registry;
+pub mod router;
+pub mod stats;
+pub mod verify;
 
+/// Collects data from the commands of a capture into an export.
+///
+/// The export can be taken at any time with [`Exporter::export_partial`], so the capture loop
+/// can write snapshots while capturing (watch mode, the HTTP API) and when it is cut short
+/// by a timeout or ctrl-c, and [`Exporter::export`] once capturing stopped.
 pub trait Exporter {
     type Export;
-    fn read_command(&mut self, command: GameCommand);
+    fn read_command(&mut self, command: &GameCommand);
+    /// Reads a batch of commands in order, exporters may parse them in parallel
+    fn read_commands(&mut self, commands: &[GameCommand]) {
+        for command in commands {
+            self.read_command(command);
+        }
+    }
+    /// Whether all data was collected and capturing can stop
     fn is_finished(&self) -> bool;
-    fn export(self) -> Self::Export;
-}
\ No newline at end of file
+    /// Descriptions of the data that has not been collected yet, empty once finished
+    fn missing(&self) -> Vec<&'static str>;
+    /// Progress of collecting each category of data
+    fn progress(&self) -> Vec<Progress>;
+    /// Whether the data read last is complete, as large categories arrive in several packets
+    /// right after each other. Complete unless overridden
+    fn is_settled(&self) -> bool {
+        true
+    }
+    /// Number of commands that could not be parsed completely, usually because the game changed them
+    fn parse_failures(&self) -> u64 {
+        0
+    }
+    /// Export of the data collected so far, while more commands can still be read afterwards
+    fn export_partial(&self) -> Self::Export;
+    /// Replaces the data collected so far with `account`, e.g. read from an earlier export,
+    /// to export it in another format. Does nothing unless overridden
+    fn restore(&mut self, _account: &Account) {}
+    /// Writes what was collected since the last flush to `writer`, for formats that can be
+    /// written incrementally instead of all at once. Does nothing unless overridden
+    fn flush(&mut self, _writer: &mut dyn Write) -> io::Result<()> {
+        Ok(())
+    }
+    /// Final export once capturing stopped, the same as the partial export unless overridden
+    fn export(self) -> Self::Export
+    where
+        Self: Sized,
+    {
+        self.export_partial()
+    }
+    /// Exports of accounts that were switched away from while capturing, oldest first.
+    /// Exporters that separate accounts start collecting from scratch on a login with
+    /// a different account, so the data of several accounts is never merged
+    fn take_switched_accounts(&mut self) -> Vec<Self::Export> {
+        vec![]
+    }
+}
+
+/// Progress of collecting one category of data, displayed like `achievements ✓ 1203`
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Progress {
+    pub name: &'static str,
+    /// Whether the data of this category was collected
+    pub collected: bool,
+    /// Number of items collected so far, if the category consists of items
+    pub count: Option<usize>,
+}
+
+impl Display for Progress {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.name, if self.collected { "✓" } else { "✗" })?;
+        if let Some(count) = self.count {
+            write!(f, " {count}")?;
+        }
+        Ok(())
+    }
+}
+
+/// When a capture has collected enough data to stop, judged by the [`Progress`] of an exporter.
+///
+/// The default criteria leave it up to [`Exporter::is_finished`].
+#[derive(Debug, Clone, Default)]
+pub struct FinishCriteria {
+    /// Names of the categories that have to be collected, all categories if `None`
+    pub required: Option<Vec<String>>,
+    /// Minimum number of items per category name, 0 for categories not listed
+    pub min_counts: HashMap<String, usize>,
+    /// How long after the last time the progress changed to stop, once anything was collected,
+    /// even if the other criteria are not met. Data sent after logging in is waited for as well
+    pub quiet_period: Option<Duration>,
+}
+
+impl FinishCriteria {
+    /// Whether the criteria are met, `since_change` being the time since the progress last changed
+    pub fn is_met<E: Exporter>(&self, exporter: &E, since_change: Duration) -> bool {
+        let progress = exporter.progress();
+        if self.quiet_period.is_some_and(|quiet_period| since_change >= quiet_period)
+            && progress.iter().any(|progress| progress.collected)
+        {
+            return true;
+        }
+
+        if self.required.is_none() && self.min_counts.is_empty() {
+            return exporter.is_finished();
+        }
+
+        // the rest of a category may still be on its way, whatever the criteria
+        exporter.is_settled()
+            && progress.iter()
+                .filter(|progress| self.is_required(progress.name))
+                .all(|progress| {
+                    let min_count = self.min_counts.get(progress.name).copied().unwrap_or(0);
+                    progress.collected && progress.count.unwrap_or(min_count) >= min_count
+                })
+    }
+
+    fn is_required(&self, name: &str) -> bool {
+        match &self.required {
+            Some(required) => required.iter().any(|r| r == name),
+            None => true,
+        }
+    }
+}
+
+/// Data detected by an exporter while capturing, sent as soon as it is read
+#[derive(Serialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event {
+    Uid { uid: u32 },
+    Achievement { id: u32, name: Option<String> },
+    Book { id: u32, name: Option<String> },
+}
+
+/// Subscriber to the data an exporter detects while capturing, notified as soon as it is read.
+///
+/// All methods do nothing by default, so observers only implement what they are interested in.
+pub trait ExportObserver: Send {
+    fn on_uid(&mut self, _uid: u32) {}
+    /// Called with the name of the achievement, if names were loaded into the database
+    fn on_achievement(&mut self, _id: u32, _name: Option<&str>) {}
+    /// Called with the name of the book, if names were loaded into the database
+    fn on_book(&mut self, _id: u32, _name: Option<&str>) {}
+}
+
+/// Sends every detected item as an [`Event`], e.g. to the websocket server.
+/// The receiving end going away does not stop the capture
+impl ExportObserver for mpsc::Sender<Event> {
+    fn on_uid(&mut self, uid: u32) {
+        let _ = self.send(Event::Uid { uid });
+    }
+
+    fn on_achievement(&mut self, id: u32, name: Option<&str>) {
+        let _ = self.send(Event::Achievement {
+            id,
+            name: name.map(str::to_string),
+        });
+    }
+
+    fn on_book(&mut self, id: u32, name: Option<&str>) {
+        let _ = self.send(Event::Book {
+            id,
+            name: name.map(str::to_string),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Exporter that collected achievements, the rest of which may still be on its way
+    struct Collected {
+        settled: bool,
+    }
+
+    impl Exporter for Collected {
+        type Export = ();
+
+        fn read_command(&mut self, _command: &GameCommand) {}
+
+        fn is_finished(&self) -> bool {
+            false
+        }
+
+        fn missing(&self) -> Vec<&'static str> {
+            vec!["books"]
+        }
+
+        fn progress(&self) -> Vec<Progress> {
+            vec![
+                Progress {
+                    name: "achievements",
+                    collected: true,
+                    count: Some(100),
+                },
+                Progress {
+                    name: "books",
+                    collected: false,
+                    count: Some(0),
+                },
+            ]
+        }
+
+        fn is_settled(&self) -> bool {
+            self.settled
+        }
+
+        fn export_partial(&self) -> Self::Export {}
+    }
+
+    #[test]
+    fn waits_for_required_categories_to_settle() {
+        let criteria = FinishCriteria {
+            required: Some(vec!["achievements".to_string()]),
+            min_counts: HashMap::from([("achievements".to_string(), 100)]),
+            ..Default::default()
+        };
+
+        assert!(!criteria.is_met(&Collected { settled: false }, Duration::ZERO));
+        assert!(criteria.is_met(&Collected { settled: true }, Duration::ZERO));
+    }
+
+    #[test]
+    fn stops_after_quiet_period_without_everything() {
+        let criteria = FinishCriteria {
+            quiet_period: Some(Duration::from_secs(60)),
+            ..Default::default()
+        };
+
+        assert!(!criteria.is_met(&Collected { settled: true }, Duration::from_secs(59)));
+        assert!(criteria.is_met(&Collected { settled: true }, Duration::from_secs(60)));
+    }
+}
@@ -0,0 +1,83 @@
+//! Every collected item as an [`Event`], which can be streamed as newline delimited JSON
+//! while capturing, one event per line.
+use std::collections::BTreeSet;
+use std::io::{self, Write};
+use std::sync::Arc;
+
+use crate::database::Database;
+use crate::export::format::{Format, ModelExporter};
+use crate::export::Event;
+use crate::model::Account;
+
+/// Format of the events of the websocket, written as one JSON object per line when streamed
+#[derive(Debug, Default)]
+pub struct NdjsonFormat {
+    /// Account of the items that were flushed
+    uid: Option<u32>,
+    achievements: BTreeSet<u32>,
+    books: BTreeSet<u32>,
+}
+
+pub type NdjsonExporter = ModelExporter<NdjsonFormat>;
+
+impl NdjsonExporter {
+    pub fn new(database: impl Into<Arc<Database>>) -> NdjsonExporter {
+        ModelExporter::with_format(database, NdjsonFormat::default())
+    }
+}
+
+impl Format for NdjsonFormat {
+    type Export = Vec<Event>;
+
+    fn convert(&self, account: &Account, _database: &Database) -> Self::Export {
+        let uid = account.uid.map(|uid| Event::Uid { uid });
+        let achievements = account.achievements.values().map(|achievement| Event::Achievement {
+            id: achievement.id,
+            name: achievement.name.clone(),
+        });
+        let books = account.books.values().map(|book| Event::Book {
+            id: book.id,
+            name: book.name.clone(),
+        });
+
+        uid.into_iter().chain(achievements).chain(books).collect()
+    }
+
+    fn flush(&mut self, account: &Account, _database: &Database, writer: &mut dyn Write) -> io::Result<()> {
+        if account.uid != self.uid {
+            // items of an account switched to are written again, even if the previous account had them
+            if self.uid.is_some() {
+                self.achievements.clear();
+                self.books.clear();
+            }
+            if let Some(uid) = account.uid {
+                write_event(writer, &Event::Uid { uid })?;
+            }
+            self.uid = account.uid;
+        }
+
+        for achievement in account.achievements.values() {
+            if self.achievements.insert(achievement.id) {
+                write_event(writer, &Event::Achievement {
+                    id: achievement.id,
+                    name: achievement.name.clone(),
+                })?;
+            }
+        }
+        for book in account.books.values() {
+            if self.books.insert(book.id) {
+                write_event(writer, &Event::Book {
+                    id: book.id,
+                    name: book.name.clone(),
+                })?;
+            }
+        }
+
+        writer.flush()
+    }
+}
+
+fn write_event(writer: &mut dyn Write, event: &Event) -> io::Result<()> {
+    serde_json::to_writer(&mut *writer, event)?;
+    writer.write_all(b"\n")
+}
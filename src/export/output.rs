@@ -0,0 +1,293 @@
+//! Writing exports to paths rendered from templates like `exports/{uid}_{date}_{format}.json`,
+//! one file per account and format, and what to do about files that exist already.
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::{debug, error, info, warn};
+
+use crate::database::Database;
+use crate::error::{PathContext, Result};
+use crate::export::checksum;
+use crate::export::csv;
+use crate::export::fribbels::Export;
+
+/// What to do if an output file exists already
+#[derive(clap::ValueEnum, Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OnConflict {
+    /// Ask whether to overwrite, back up or merge
+    Prompt,
+    /// Fail without writing anything
+    Refuse,
+    /// Rename the existing file after the time it was written
+    Backup,
+    /// Merge the existing fribbels export into the new one, other formats are backed up
+    Merge,
+    /// Replace the existing file
+    Overwrite,
+}
+
+/// Where and how exports are written
+#[derive(Debug, Clone)]
+pub struct Outputs {
+    /// Path of the exports, with the placeholders of [`render`] and [`formatted`].
+    /// `-` prints the exports to stdout
+    pub output: PathBuf,
+    /// Paths of the exports of single formats instead of `output`, by format
+    pub out: Vec<(String, PathBuf)>,
+    /// Formats of the exports, in the order the exports of several formats list them
+    pub formats: Vec<String>,
+    pub on_conflict: OnConflict,
+    /// Asks what to do about an existing file with [`OnConflict::Prompt`].
+    /// Without a way to ask, existing files are backed up
+    pub prompt: Option<fn(&Path) -> OnConflict>,
+    /// Only log where the exports would be written
+    pub dry_run: bool,
+    /// Write the checksum of every export next to it, see [`checksum`]
+    pub checksum: bool,
+}
+
+impl Outputs {
+    /// Writes the exports of all accounts, returning the paths of the written files.
+    ///
+    /// The fribbels export of the account of `previous` is merged into it, see [`Export::merge`].
+    /// Exports of several accounts are written to a file for every account, unless the path has
+    /// a `{uid}` placeholder already, and exports of several formats to a file for every format
+    pub fn write_all(&self, database: &Database, mut exports: Vec<Value>, previous: Option<&Export>) -> Result<Vec<PathBuf>> {
+        if let Some(previous) = previous {
+            for export in &mut exports {
+                self.merge_previous(database, export, previous);
+            }
+        }
+
+        let mut written = vec![];
+
+        for export in &exports {
+            if is_stdout(&self.output) {
+                // the exports of several formats are printed together
+                write_export(&self.output, export)?;
+                continue;
+            }
+
+            let uid = export_uid(export);
+            let account_output = |template: &Path| {
+                let output = render(template, uid);
+                if exports.len() > 1 && !template.to_string_lossy().contains("{uid}") {
+                    // multiple accounts logged in, write every account to its own file
+                    suffixed(&output, &uid.map_or("unknown".to_string(), |uid| uid.to_string()))
+                } else {
+                    output
+                }
+            };
+            let output = account_output(&self.output);
+            let format_output = |format: &str| match self.out.iter().find(|(out_format, _)| out_format == format) {
+                Some((_, path)) => account_output(path),
+                None if self.formats.len() == 1 => formatted(&output, format).unwrap_or_else(|| output.clone()),
+                None => formatted(&output, format).unwrap_or_else(|| suffixed(&output, format)),
+            };
+
+            if self.formats.len() == 1 {
+                let path = format_output(&self.formats[0]);
+                self.write(database, &path, export, &self.formats[0], "output")?;
+                written.push(path);
+                continue;
+            }
+
+            // the export of several formats contains the export of every format in order
+            let formats = export.as_array().into_iter().flatten();
+            for (format, export) in self.formats.iter().zip(formats) {
+                let path = format_output(format);
+                self.write(database, &path, export, format, &format!("{format} output"))?;
+                written.push(path);
+            }
+        }
+
+        Ok(written)
+    }
+
+    /// Path the export of a single account in `format` is written to
+    pub fn path(&self, format: &str, uid: Option<u64>) -> PathBuf {
+        let output = match self.out.iter().find(|(out_format, _)| out_format == format) {
+            Some((_, path)) => render(path, uid),
+            None => render(&self.output, uid),
+        };
+        formatted(&output, format).unwrap_or(output)
+    }
+
+    /// Writes an export of `format` described by `what`, resolving a conflict with an existing
+    /// file first, or only logs where it would be written when this is a dry run.
+    /// Existing fribbels exports are merged against `database`
+    pub fn write(&self, database: &Database, path: &Path, export: &Value, format: &str, what: &str) -> Result<()> {
+        if self.dry_run {
+            let bytes = serde_json::to_vec_pretty(export)?.len();
+            info!(bytes, "dry run, would write {what} to {}", path.display());
+            return Ok(());
+        }
+
+        let mut export = export.clone();
+        self.resolve_conflict(database, path, format, &mut export)?;
+        write_export(path, &export)?;
+        info!("wrote {what} to {}", path.display());
+        if self.checksum {
+            checksum::write(path)?;
+        }
+        Ok(())
+    }
+
+    /// Makes room for an export of `format` to be written to `path` if there is a file already
+    fn resolve_conflict(&self, database: &Database, path: &Path, format: &str, export: &mut Value) -> Result<()> {
+        if !path.exists() {
+            return Ok(());
+        }
+
+        let mut on_conflict = self.on_conflict;
+        if on_conflict == OnConflict::Prompt {
+            on_conflict = self.prompt.map_or(OnConflict::Backup, |prompt| prompt(path));
+        }
+        if on_conflict == OnConflict::Merge && !merge_existing(database, path, format, export)? {
+            on_conflict = OnConflict::Backup;
+        }
+
+        match on_conflict {
+            OnConflict::Refuse => Err(std::io::Error::from(std::io::ErrorKind::AlreadyExists)).with_path("write", path),
+            OnConflict::Backup => {
+                // named after when the existing file was written
+                let modified = std::fs::metadata(path).and_then(|metadata| metadata.modified()).map(chrono::DateTime::<Local>::from);
+                let backup = suffixed(path, &modified.unwrap_or_else(|_| Local::now()).format("%Y-%m-%d_%H-%M-%S").to_string());
+                std::fs::rename(path, &backup).with_path("back up", path)?;
+                info!("backed up {} to {}", path.display(), backup.display());
+                Ok(())
+            }
+            OnConflict::Overwrite | OnConflict::Merge | OnConflict::Prompt => Ok(()),
+        }
+    }
+
+    /// Merges the fribbels export of an account into `previous`, if it is an export of the same account
+    fn merge_previous(&self, database: &Database, export: &mut Value, previous: &Export) {
+        let Some(index) = self.formats.iter().position(|format| format == "fribbels") else {
+            return;
+        };
+        let export = if self.formats.len() == 1 {
+            Some(export)
+        } else {
+            export.as_array_mut().and_then(|exports| exports.get_mut(index))
+        };
+        let Some(export) = export else {
+            return;
+        };
+
+        let mut merged: Export = match serde_json::from_value(export.clone()) {
+            Ok(merged) => merged,
+            Err(e) => {
+                error!(%e, "could not read export to merge");
+                return;
+            }
+        };
+
+        if merged.merge(previous, database) {
+            info!(uid = merged.metadata.uid, "merged with previous export");
+            *export = serde_json::to_value(merged).unwrap();
+        } else {
+            debug!(uid = merged.metadata.uid, "not merged, previous export is of a different account");
+        }
+    }
+}
+
+/// Merges the existing fribbels export at `path` into `export`, returning `false` if it cannot
+/// be merged, as it is of another format or account
+fn merge_existing(database: &Database, path: &Path, format: &str, export: &mut Value) -> Result<bool> {
+    if format != "fribbels" {
+        debug!("only fribbels exports can be merged, backing up {} instead", path.display());
+        return Ok(false);
+    }
+
+    let existing = Export::load(path)?;
+    let mut merged: Export = serde_json::from_value(export.clone())?;
+    if !merged.merge(&existing, database) {
+        warn!("{} is an export of another account, backing it up instead", path.display());
+        return Ok(false);
+    }
+
+    info!("merged with existing export {}", path.display());
+    *export = serde_json::to_value(merged)?;
+    Ok(true)
+}
+
+/// Uid of the account of an export, looked up in the metadata of any of its formats
+pub fn export_uid(export: &Value) -> Option<u64> {
+    match export {
+        Value::Array(exports) => exports.iter().find_map(export_uid),
+        Value::String(rows) => csv::uid(rows),
+        // the fribbels export, or the uid event of the ndjson export
+        _ => export.pointer("/metadata/uid").or_else(|| export.pointer("/uid")).and_then(Value::as_u64),
+    }
+}
+
+/// Output path with the placeholders `{uid}`, `{date}` and `{time}` replaced,
+/// e.g. `exports/{uid}_{date}.json`. `{format}` is replaced by [`formatted`]
+pub fn render(output: &Path, uid: Option<u64>) -> PathBuf {
+    let now = Local::now();
+    let rendered = output.to_string_lossy()
+        .replace("{uid}", &uid.map_or("unknown".to_string(), |uid| uid.to_string()))
+        .replace("{date}", &now.format("%Y-%m-%d").to_string())
+        .replace("{time}", &now.format("%H-%M-%S").to_string());
+    PathBuf::from(rendered)
+}
+
+/// Output path with the `{format}` placeholder replaced, if it has one
+pub fn formatted(output: &Path, format: &str) -> Option<PathBuf> {
+    let output = output.to_string_lossy();
+    output.contains("{format}").then(|| PathBuf::from(output.replace("{format}", format)))
+}
+
+/// Output path of one of multiple files, e.g. `archive_output_123456789.json`
+pub fn suffixed(output: &Path, suffix: &str) -> PathBuf {
+    let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = output.extension().unwrap_or_default().to_string_lossy();
+    output.with_file_name(format!("{stem}_{suffix}.{extension}"))
+}
+
+/// Whether the export is printed to stdout instead of written to a file
+pub fn is_stdout(output: &Path) -> bool {
+    output == Path::new("-")
+}
+
+/// Writes an export to `path`, or to stdout if it is `-`. Exports of text formats like csv
+/// are written as they are, all others as pretty JSON
+pub fn write_export(path: &Path, export: &Value) -> Result<()> {
+    let Value::String(text) = export else {
+        return write_json(path, export);
+    };
+    if is_stdout(path) {
+        return Ok(std::io::stdout().lock().write_all(text.as_bytes())?);
+    }
+
+    create_parent_dirs(path)?;
+    std::fs::write(path, text).with_path("write", path)
+}
+
+/// Writes anything serializable as pretty JSON to `path`, or to stdout if it is `-`
+pub fn write_json(path: &Path, value: &impl Serialize) -> Result<()> {
+    if is_stdout(path) {
+        let mut stdout = std::io::stdout().lock();
+        serde_json::to_writer_pretty(&mut stdout, value)?;
+        return Ok(writeln!(stdout)?);
+    }
+
+    create_parent_dirs(path)?;
+    let file = File::create(path).with_path("create", path)?;
+    serde_json::to_writer_pretty(&file, value).with_path("write", path)
+}
+
+/// Creates the directories an output is written to, as templated outputs can be sorted
+/// into directories that do not exist yet
+pub fn create_parent_dirs(path: &Path) -> Result<()> {
+    match path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        Some(parent) => std::fs::create_dir_all(parent).with_path("create", parent),
+        None => Ok(()),
+    }
+}
@@ -0,0 +1,169 @@
+//! Exporters selectable by name at runtime, like the `--format` option of the CLI.
+//!
+//! The registry comes with the exporters of this crate, and code using the library
+//! can register its own exporters next to them.
+//!
+//! Exporters registered as a format of the account model, see [`ExporterRegistry::register_format`],
+//! share the collected account when several are created at once, so every command is only
+//! parsed once whatever the number of formats. Other exporters can only be created on their own.
+use std::collections::BTreeMap;
+use std::sync::{mpsc, Arc};
+
+use crate::database::Database;
+use crate::export::command_ids::CommandIds;
+use crate::export::boxed::AnyExporter;
+use crate::export::csv::CsvFormat;
+use crate::export::field_mappings::FieldMapping;
+use crate::export::format::{AnyFormat, Format, Formats, ModelExporter};
+use crate::export::fribbels::OptimizerFormat;
+use crate::export::ndjson::NdjsonFormat;
+use crate::export::Event;
+use crate::filter::ItemFilter;
+use crate::model::CompletedStatuses;
+
+/// Everything an exporter can be created from
+#[derive(Clone)]
+pub struct ExporterContext {
+    /// Game data shared by every exporter created from the context
+    pub database: Arc<Database>,
+    pub command_ids: CommandIds,
+    /// Field numbers of other game versions to try for commands that do not match their proto
+    pub field_mappings: Vec<FieldMapping>,
+    /// Statuses of quests counted as completed achievements
+    pub completed: CompletedStatuses,
+    /// Filters the exported items have to pass
+    pub filters: Vec<ItemFilter>,
+    /// Channel to send detected items to, if anyone is listening
+    pub events: Option<mpsc::Sender<Event>>,
+    /// Uid to export under instead of the detected one
+    pub uid: Option<u32>,
+}
+
+impl ExporterContext {
+    pub fn new(database: impl Into<Arc<Database>>) -> ExporterContext {
+        ExporterContext {
+            database: database.into(),
+            command_ids: CommandIds::default(),
+            field_mappings: vec![],
+            completed: CompletedStatuses::default(),
+            filters: vec![],
+            events: None,
+            uid: None,
+        }
+    }
+}
+
+/// Creates a new exporter, called once for every capture (or game client)
+pub type ExporterFactory = Box<dyn Fn(&ExporterContext) -> Box<dyn AnyExporter> + Send + Sync>;
+
+/// Creates a new format of the account model, see [`ExporterRegistry::register_format`]
+pub type FormatFactory = Arc<dyn Fn() -> Box<dyn AnyFormat> + Send + Sync>;
+
+struct Entry {
+    description: &'static str,
+    factory: ExporterFactory,
+    /// Format the exporter converts the account model to, if it is one
+    format: Option<FormatFactory>,
+}
+
+/// Exporters by name
+#[derive(Default)]
+pub struct ExporterRegistry {
+    exporters: BTreeMap<String, Entry>,
+}
+
+impl ExporterRegistry {
+    /// Registry without any exporters
+    pub fn new() -> ExporterRegistry {
+        ExporterRegistry::default()
+    }
+
+    /// Registry with the exporters of this crate
+    pub fn with_builtin() -> ExporterRegistry {
+        let mut registry = ExporterRegistry::new();
+
+        registry.register_format("csv", "Comma separated values, a row for every item", || Box::new(CsvFormat));
+        registry.register_format("fribbels", "Fribbels HSR Optimizer", || Box::new(OptimizerFormat));
+        registry.register_format("ndjson", "Newline delimited JSON, streamed with --stream", || {
+            Box::new(NdjsonFormat::default())
+        });
+
+        registry
+    }
+
+    /// Registers an exporter under `name`, replacing the exporter registered under it before
+    pub fn register<F>(&mut self, name: &str, description: &'static str, factory: F)
+    where
+        F: Fn(&ExporterContext) -> Box<dyn AnyExporter> + Send + Sync + 'static,
+    {
+        self.exporters.insert(name.to_string(), Entry {
+            description,
+            factory: Box::new(factory),
+            format: None,
+        });
+    }
+
+    /// Registers a format of the account model under `name`, exported by a [`ModelExporter`].
+    /// Replaces the exporter registered under it before
+    pub fn register_format<F>(&mut self, name: &str, description: &'static str, format: F)
+    where
+        F: Fn() -> Box<dyn AnyFormat> + Send + Sync + 'static,
+    {
+        let format: FormatFactory = Arc::new(format);
+        let create = Arc::clone(&format);
+        self.exporters.insert(name.to_string(), Entry {
+            description,
+            factory: Box::new(move |context| Box::new(model_exporter(create(), context))),
+            format: Some(format),
+        });
+    }
+
+    /// Names of all registered exporters, in alphabetical order
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.exporters.keys().map(String::as_str)
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.exporters.contains_key(name)
+    }
+
+    /// Human readable name of the format of an exporter
+    pub fn description(&self, name: &str) -> Option<&'static str> {
+        self.exporters.get(name).map(|entry| entry.description)
+    }
+
+    /// Creates the exporter registered under `name`, `None` if there is none
+    pub fn create(&self, name: &str, context: &ExporterContext) -> Option<Box<dyn AnyExporter>> {
+        self.exporters.get(name).map(|entry| (entry.factory)(context))
+    }
+
+    /// Creates the exporters registered under `names`, if there is more than one combined into
+    /// a single [`ModelExporter`] of their [`Formats`]. `None` if any of them is not registered,
+    /// or if there is more than one and any of them is not a format
+    pub fn create_all(&self, names: &[String], context: &ExporterContext) -> Option<Box<dyn AnyExporter>> {
+        if let [name] = names {
+            return self.create(name, context);
+        }
+
+        let formats = names.iter()
+            .map(|name| self.exporters.get(name)?.format.as_ref().map(|format| format()))
+            .collect::<Option<Vec<_>>>()?;
+        Some(Box::new(model_exporter(Formats::new(formats), context)))
+    }
+}
+
+/// Exporter of the account model in `format`, with the options of the context
+fn model_exporter<F: Format>(format: F, context: &ExporterContext) -> ModelExporter<F> {
+    let mut exporter = ModelExporter::with_format(Arc::clone(&context.database), format);
+    exporter.set_command_ids(context.command_ids);
+    exporter.set_field_mappings(context.field_mappings.clone());
+    exporter.set_completed_statuses(context.completed);
+    exporter.set_filters(context.filters.clone());
+    if let Some(uid) = context.uid {
+        exporter.set_uid_override(uid);
+    }
+    if let Some(events) = &context.events {
+        exporter.add_observer(events.clone());
+    }
+    exporter
+}
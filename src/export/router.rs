@@ -0,0 +1,303 @@
+//! Dispatch of commands to handlers registered by command id, so handlers of new commands can be
+//! added without touching the others, and shared by exporters.
+//!
+//! Commands that cannot be parsed, usually because a game patch changed a field of their proto,
+//! are parsed again field by field, so the fields that did not change are still read. Commands
+//! whose field numbers were shifted are parsed with the field numbers of other game versions
+//! first, see [`FieldMapping`].
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use protobuf::{CodedInputStream, MessageFull};
+use reliquary::network::GameCommand;
+use tracing::{debug, trace, warn};
+
+use crate::export::field_mappings::{has_unknown_fields, FieldMapping};
+
+type Parser<T> = Box<dyn Fn(&GameCommand, &[FieldMapping]) -> protobuf::Result<Parsed<T>> + Send + Sync>;
+
+/// Handlers of commands by command id, each parsing the command into its proto first.
+///
+/// Parsing only needs a shared reference to the router, so commands can be parsed on several
+/// threads at once with [`CommandRouter::parse`] and passed to their handlers in order afterwards
+pub struct CommandRouter<T> {
+    parsers: HashMap<u16, Parser<T>>,
+    mappings: Vec<FieldMapping>,
+}
+
+/// What happened to a dispatched command
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dispatch {
+    Handled,
+    /// Some fields of the command could not be parsed, the handler got the others
+    Salvaged,
+    /// The command could not be parsed into the proto of its handler
+    ParseFailed,
+    /// There is no handler for the command
+    Ignored,
+}
+
+/// A parsed command waiting to be passed to its handler
+pub struct Parsed<T> {
+    dispatch: Dispatch,
+    handle: Option<Box<dyn FnOnce(&mut T) + Send>>,
+}
+
+impl<T> Parsed<T> {
+    fn unhandled(dispatch: Dispatch) -> Parsed<T> {
+        Parsed {
+            dispatch,
+            handle: None,
+        }
+    }
+
+    /// Passes the command to its handler, if it could be parsed
+    pub fn apply(self, target: &mut T) -> Dispatch {
+        if let Some(handle) = self.handle {
+            handle(target);
+        }
+        self.dispatch
+    }
+}
+
+impl<T> CommandRouter<T> {
+    pub fn new() -> CommandRouter<T> {
+        CommandRouter {
+            parsers: HashMap::new(),
+            mappings: vec![],
+        }
+    }
+
+    /// Tries the field numbers of `mappings` in order for commands that do not match their proto
+    pub fn with_field_mappings(mut self, mappings: Vec<FieldMapping>) -> CommandRouter<T> {
+        self.mappings = mappings;
+        self
+    }
+
+    /// Passes commands with `command_id`, parsed as `P`, to `handler`.
+    /// Replaces the handler registered for `command_id` before
+    pub fn route<P, F>(mut self, command_id: u16, handler: F) -> CommandRouter<T>
+    where
+        P: MessageFull,
+        F: Fn(&mut T, P) + Send + Sync + 'static,
+    {
+        let handler = Arc::new(handler);
+        self.parsers.insert(command_id, Box::new(move |command, mappings| {
+            let parsed = match command.parse_proto::<P>() {
+                Ok(proto) if mappings.is_empty() || !has_unknown_fields(&proto) => Ok(proto),
+                parsed => match parse_mapped::<P>(&command.proto_data, mappings) {
+                    Some(proto) => Ok(proto),
+                    None => parsed,
+                },
+            };
+            let (proto, dispatch) = match parsed {
+                Ok(proto) => (proto, Dispatch::Handled),
+                Err(error) => {
+                    let Some((proto, skipped)) = parse_partial::<P>(&command.proto_data) else {
+                        return Err(error);
+                    };
+                    warn!(
+                        %error,
+                        command_id = command.command_id,
+                        "could not parse {} command completely, skipped {}",
+                        command.get_command_name(),
+                        skipped.join(", ")
+                    );
+                    (proto, Dispatch::Salvaged)
+                }
+            };
+
+            let handler = Arc::clone(&handler);
+            Ok(Parsed {
+                dispatch,
+                handle: Some(Box::new(move |target: &mut T| handler(target, proto))),
+            })
+        }));
+        self
+    }
+
+    /// Parses `command` into the proto of its handler, logging commands that could not be parsed
+    pub fn parse(&self, command: &GameCommand) -> Parsed<T> {
+        let Some(parser) = self.parsers.get(&command.command_id) else {
+            trace!(command_id=command.command_id, tag=command.get_command_name(), "ignored");
+            return Parsed::unhandled(Dispatch::Ignored);
+        };
+
+        parser(command, &self.mappings).unwrap_or_else(|error| {
+            warn!(%error, command_id = command.command_id, "could not parse {} command", command.get_command_name());
+            Parsed::unhandled(Dispatch::ParseFailed)
+        })
+    }
+
+    /// Parses `command` and passes it to its handler right away
+    pub fn dispatch(&self, target: &mut T, command: &GameCommand) -> Dispatch {
+        self.parse(command).apply(target)
+    }
+}
+
+/// Parses `data` as `P` with the first of `mappings` that fits it, if any
+fn parse_mapped<P: MessageFull>(data: &[u8], mappings: &[FieldMapping]) -> Option<P> {
+    mappings.iter().find_map(|mapping| {
+        let proto = mapping.parse::<P>(data)?;
+        debug!(version = mapping.version, "parsed {} with the field numbers of another version", P::descriptor().name());
+        Some(proto)
+    })
+}
+
+/// Parses `data` as `P` one field at a time, skipping the fields that cannot be parsed.
+/// Every entry of a repeated field is a field of its own, so only the broken entries are lost.
+///
+/// Returns the message with the names of the skipped fields, or `None` if no field could be parsed
+fn parse_partial<P: MessageFull>(data: &[u8]) -> Option<(P, Vec<String>)> {
+    let mut message = P::new();
+    let mut skipped: Vec<String> = vec![];
+    let mut parsed_any = false;
+
+    let mut input = CodedInputStream::from_bytes(data);
+    loop {
+        let (number, field) = match next_field(&mut input, data) {
+            Ok(Some(field)) => field,
+            Ok(None) => break,
+            Err(_) => {
+                // the length of the field is unknown, so nothing after it can be read
+                skipped.push("the rest of the command".to_string());
+                break;
+            }
+        };
+
+        if message.merge_from_bytes(field).is_ok() {
+            parsed_any = true;
+            continue;
+        }
+
+        let name = P::descriptor()
+            .field_by_number(number)
+            .map_or_else(|| format!("field {number}"), |field| field.name().to_string());
+        if !skipped.contains(&name) {
+            skipped.push(name);
+        }
+    }
+
+    parsed_any.then_some((message, skipped))
+}
+
+/// Number and encoded bytes, including the tag, of the next field of `input` reading `data`
+fn next_field<'d>(input: &mut CodedInputStream, data: &'d [u8]) -> protobuf::Result<Option<(u32, &'d [u8])>> {
+    if input.eof()? {
+        return Ok(None);
+    }
+
+    let start = input.pos() as usize;
+    let (number, wire_type) = input.read_tag_unpack()?;
+    input.skip_field(wire_type)?;
+    Ok(Some((number, &data[start..input.pos() as usize])))
+}
+
+impl<T> Default for CommandRouter<T> {
+    fn default() -> Self {
+        CommandRouter::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use protobuf::{CodedOutputStream, Message};
+    use reliquary::network::gen::proto::GetBagScRsp::GetBagScRsp;
+    use reliquary::network::gen::proto::Material::Material;
+
+    use super::*;
+    use crate::export::fixture::game_command;
+
+    const BAG: u16 = 1;
+
+    fn material(tid: u32) -> Vec<u8> {
+        Material {
+            tid,
+            ..Default::default()
+        }.write_to_bytes().unwrap()
+    }
+
+    /// Encoded `material_list` entries of a bag with these bytes
+    fn bag(materials: &[Vec<u8>]) -> Vec<u8> {
+        let number = GetBagScRsp::descriptor().field_by_name("material_list").unwrap().number();
+        let mut data = vec![];
+        let mut output = CodedOutputStream::vec(&mut data);
+        for material in materials {
+            output.write_bytes(number, material).unwrap();
+        }
+        output.flush().unwrap();
+        drop(output);
+        data
+    }
+
+    fn tids(bag: &GetBagScRsp) -> Vec<u32> {
+        bag.material_list.iter().map(|material| material.tid).collect()
+    }
+
+    fn router() -> CommandRouter<Vec<u32>> {
+        CommandRouter::new().route(BAG, |collected: &mut Vec<u32>, bag: GetBagScRsp| collected.extend(tids(&bag)))
+    }
+
+    #[test]
+    fn reads_fields_one_at_a_time() {
+        let data = bag(&[material(140001), material(140002)]);
+        let mut input = CodedInputStream::from_bytes(&data);
+
+        let (number, first) = next_field(&mut input, &data).unwrap().unwrap();
+        assert_eq!(Some(number), GetBagScRsp::descriptor().field_by_name("material_list").map(|field| field.number()));
+        let (_, second) = next_field(&mut input, &data).unwrap().unwrap();
+        assert_eq!([first, second].concat(), data);
+        assert_eq!(next_field(&mut input, &data).unwrap(), None);
+    }
+
+    #[test]
+    fn fails_reading_truncated_fields() {
+        let data = bag(&[material(140001)]);
+        let truncated = &data[..data.len() - 1];
+        assert!(next_field(&mut CodedInputStream::from_bytes(truncated), truncated).is_err());
+
+        // a tag that never ends
+        let data = [0xff; 3];
+        assert!(next_field(&mut CodedInputStream::from_bytes(&data), &data).is_err());
+    }
+
+    #[test]
+    fn skips_broken_entries() {
+        // the second entry starts with a tag that never ends
+        let data = bag(&[material(140001), vec![0xff], material(140002)]);
+        assert!(GetBagScRsp::parse_from_bytes(&data).is_err());
+
+        let (parsed, skipped) = parse_partial::<GetBagScRsp>(&data).unwrap();
+        assert_eq!(tids(&parsed), [140001, 140002]);
+        assert_eq!(skipped, ["material_list"]);
+    }
+
+    #[test]
+    fn keeps_fields_before_truncation() {
+        let data = bag(&[material(140001), material(140002)]);
+        let (parsed, skipped) = parse_partial::<GetBagScRsp>(&data[..data.len() - 2]).unwrap();
+        assert_eq!(tids(&parsed), [140001]);
+        assert_eq!(skipped, ["the rest of the command"]);
+    }
+
+    #[test]
+    fn gives_up_without_any_field() {
+        assert!(parse_partial::<GetBagScRsp>(&[0xff; 3]).is_none());
+        assert!(parse_partial::<GetBagScRsp>(&bag(&[vec![0xff]])).is_none());
+    }
+
+    #[test]
+    fn dispatches_by_command_id() {
+        let router = router();
+        let mut collected = vec![];
+
+        let complete = bag(&[material(140001)]);
+        assert_eq!(router.dispatch(&mut collected, &game_command(BAG, complete.clone())), Dispatch::Handled);
+        let broken = bag(&[vec![0xff], material(140002)]);
+        assert_eq!(router.dispatch(&mut collected, &game_command(BAG, broken)), Dispatch::Salvaged);
+        assert_eq!(router.dispatch(&mut collected, &game_command(BAG, vec![0xff; 3])), Dispatch::ParseFailed);
+        assert_eq!(router.dispatch(&mut collected, &game_command(BAG + 1, complete)), Dispatch::Ignored);
+
+        assert_eq!(collected, [140001, 140002]);
+    }
+}
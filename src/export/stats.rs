@@ -0,0 +1,98 @@
+//! How much of the game a fribbels export completed, overall and by series.
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::Serialize;
+
+use crate::database::Database;
+use crate::export::fribbels::Export;
+
+/// Completion of an export, counting only the items the database knows
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct ExportStats {
+    pub uid: Option<u32>,
+    pub achievements: Found,
+    pub books: Found,
+    /// Jade rewarded by the completed achievements
+    pub earned_jade: u32,
+    /// Jade rewarded by all achievements
+    pub total_jade: u32,
+    /// Jade of the completed achievements whose reward has not been claimed yet
+    pub unclaimed_jade: u32,
+    /// Number of completed achievements whose reward has not been claimed yet
+    pub unclaimed: usize,
+    /// Completion by series, items without a series last
+    pub achievement_series: Vec<SeriesFound>,
+    pub book_series: Vec<SeriesFound>,
+}
+
+/// Number of found items out of the known ones
+#[derive(Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Found {
+    pub found: usize,
+    pub known: usize,
+}
+
+/// Completion of a series of items
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct SeriesFound {
+    /// Id of the series, `None` for the items without one
+    pub series: Option<u32>,
+    /// Name of the series, if the game data has one
+    pub name: Option<String>,
+    pub found: Found,
+}
+
+impl ExportStats {
+    pub fn new(export: &Export, database: &Database) -> ExportStats {
+        let achievements: BTreeSet<u32> = export.achievements().iter().copied().filter(|&id| database.has_achievement(id)).collect();
+        let books: BTreeSet<u32> = export.books().iter().copied().filter(|&id| database.has_book(id)).collect();
+
+        let achievement_series = series_found(database.achievement_ids(), &achievements, |id| database.achievement(id).and_then(|a| a.series))
+            .into_iter()
+            .map(|(series, found)| SeriesFound {
+                series,
+                name: series.and_then(|series| database.achievement_series_name(series)).map(str::to_string),
+                found,
+            })
+            .collect();
+        let book_series = series_found(database.book_ids(), &books, |id| database.book(id).and_then(|b| b.series))
+            .into_iter()
+            .map(|(series, found)| SeriesFound {
+                series,
+                name: series.and_then(|series| database.book_series_name(series)).map(str::to_string),
+                found,
+            })
+            .collect();
+
+        ExportStats {
+            uid: export.metadata.uid,
+            achievements: Found {
+                found: achievements.len(),
+                known: database.achievement_ids().len(),
+            },
+            books: Found {
+                found: books.len(),
+                known: database.book_ids().len(),
+            },
+            earned_jade: achievements.iter().map(|&id| database.achievement_jade(id)).sum(),
+            total_jade: database.total_achievement_jade(),
+            unclaimed_jade: export.metadata.unclaimed.iter().map(|&id| database.achievement_jade(id)).sum(),
+            unclaimed: export.metadata.unclaimed.len(),
+            achievement_series,
+            book_series,
+        }
+    }
+}
+
+/// Found items out of the known ones by series, items without a series last
+fn series_found(ids: &[u32], found: &BTreeSet<u32>, series: impl Fn(u32) -> Option<u32>) -> Vec<(Option<u32>, Found)> {
+    let mut counts: BTreeMap<Option<u32>, Found> = BTreeMap::new();
+    for &id in ids {
+        let count = counts.entry(series(id)).or_default();
+        count.found += usize::from(found.contains(&id));
+        count.known += 1;
+    }
+
+    let (unknown, known): (Vec<_>, Vec<_>) = counts.into_iter().partition(|(series, _)| series.is_none());
+    known.into_iter().chain(unknown).collect()
+}
@@ -0,0 +1,64 @@
+//! Checks of fribbels exports written before against the database, to notice exports that have
+//! to be converted or captured again.
+use std::path::Path;
+
+use tracing::info;
+
+use crate::database::Database;
+use crate::error::{ArchiverError, Result};
+use crate::export::checksum;
+use crate::export::fribbels::{self, Export};
+use crate::model;
+use crate::validation::ValidationReport;
+
+/// Checks the export at `path` against the database, failing with everything that is out of date:
+/// an older version, items listed twice or unknown to the database, a checksum that does not match,
+/// a missing uid or jade that does not match the achievements
+pub fn verify(database: &Database, path: &Path) -> Result<()> {
+    let export = Export::load(path)?;
+    let problems = problems(database, &export, path)?;
+
+    if problems.is_empty() {
+        info!("{} is up to date", path.display());
+        Ok(())
+    } else {
+        Err(ArchiverError::InvalidData(format!("{} is out of date: {}", path.display(), problems.join(", "))))
+    }
+}
+
+/// Everything out of date in `export`, read from `path`
+fn problems(database: &Database, export: &Export, path: &Path) -> Result<Vec<String>> {
+    let mut account = export.to_account(database);
+    account.unknown.achievements.extend(export.achievements().iter().copied().filter(|&id| !database.has_achievement(id)));
+    account.unknown.books.extend(export.books().iter().copied().filter(|&id| !database.has_book(id)));
+
+    let report = ValidationReport::new(&account, database);
+    report.log();
+
+    let mut problems = vec![];
+    if export.version < fribbels::VERSION {
+        problems.push(format!("it is of version {}, upgrade it with the convert command", export.version));
+    }
+    if export.version > fribbels::VERSION {
+        problems.push(format!("it is of version {}, which is newer than this archiver knows", export.version));
+    }
+    for (category, ids) in Export::duplicates(path)? {
+        let ids: Vec<String> = ids.iter().map(u32::to_string).collect();
+        problems.push(format!("it lists the {category} {} more than once", ids.join(", ")));
+    }
+    if let Some(problem) = checksum::verify(path)? {
+        problems.push(problem);
+    }
+    match export.metadata.uid {
+        None => problems.push("it has no uid".to_string()),
+        Some(uid) if !model::is_plausible_uid(uid) => problems.push(format!("its uid {uid} is not the uid of an account")),
+        Some(_) => {}
+    }
+    if report.is_database_outdated() {
+        problems.push("it contains items the game data does not know".to_string());
+    }
+    if export.metadata.jade.earned != account.earned_jade() {
+        problems.push(format!("its earned jade is {}, but the achievements reward {}", export.metadata.jade.earned, account.earned_jade()));
+    }
+    Ok(problems)
+}
@@ -0,0 +1,219 @@
+//! Filters narrowing down the exported items, e.g. to generate a checklist of a single series.
+//!
+//! A filter is written as `<category>.<field>=<values>`, the values separated by commas,
+//! for example `achievements.series=7` or `books.world=4,5`. Items are exported if they match
+//! every filter of their category.
+use std::collections::BTreeSet;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use crate::database::Database;
+use crate::model::Account;
+
+/// Category of items a filter applies to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterCategory {
+    Achievements,
+    Books,
+}
+
+/// Property of an item a filter compares
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterField {
+    Id,
+    /// Series of achievements and books
+    Series,
+    /// World the series of a book is found in
+    World,
+    /// Stellar jade rewarded by an achievement
+    Jade,
+}
+
+/// Filter keeping the items of a category whose field has one of the values
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ItemFilter {
+    pub category: FilterCategory,
+    pub field: FilterField,
+    pub values: BTreeSet<u32>,
+}
+
+impl ItemFilter {
+    /// Whether the achievement `id` passes the filter, always the case for filters of other categories
+    pub fn keeps_achievement(&self, database: &Database, id: u32) -> bool {
+        if self.category != FilterCategory::Achievements {
+            return true;
+        }
+
+        let info = database.achievement(id);
+        let value = match self.field {
+            FilterField::Id => Some(id),
+            FilterField::Series => info.and_then(|info| info.series),
+            FilterField::Jade => info.map(|info| info.jade),
+            FilterField::World => None,
+        };
+        value.is_some_and(|value| self.values.contains(&value))
+    }
+
+    /// Whether the book `id` passes the filter, always the case for filters of other categories
+    pub fn keeps_book(&self, database: &Database, id: u32) -> bool {
+        if self.category != FilterCategory::Books {
+            return true;
+        }
+
+        let info = database.book(id);
+        let value = match self.field {
+            FilterField::Id => Some(id),
+            FilterField::Series => info.and_then(|info| info.series),
+            FilterField::World => info.and_then(|info| info.world),
+            FilterField::Jade => None,
+        };
+        value.is_some_and(|value| self.values.contains(&value))
+    }
+}
+
+/// Copy of `account` with only the items that pass all `filters`
+pub fn apply(filters: &[ItemFilter], account: &Account, database: &Database) -> Account {
+    let mut account = account.clone();
+    account.achievements.retain(|&id, _| filters.iter().all(|filter| filter.keeps_achievement(database, id)));
+    account.books.retain(|&id, _| filters.iter().all(|filter| filter.keeps_book(database, id)));
+    account
+}
+
+impl FromStr for ItemFilter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (target, values) = s.split_once('=')
+            .ok_or_else(|| format!("expected <category>.<field>=<values>, got {s}"))?;
+        let (category, field) = target.trim().split_once('.')
+            .ok_or_else(|| format!("expected <category>.<field>, got {target}"))?;
+
+        let category = match category {
+            "achievements" => FilterCategory::Achievements,
+            "books" => FilterCategory::Books,
+            _ => return Err(format!("unknown category {category}, expected achievements or books")),
+        };
+        let field = match (category, field) {
+            (_, "id") => FilterField::Id,
+            (_, "series") => FilterField::Series,
+            (FilterCategory::Books, "world") => FilterField::World,
+            (FilterCategory::Achievements, "jade") => FilterField::Jade,
+            (FilterCategory::Achievements, _) => return Err(format!("unknown field {field}, expected id, series or jade")),
+            (FilterCategory::Books, _) => return Err(format!("unknown field {field}, expected id, series or world")),
+        };
+        let values = values.split(',')
+            .map(|value| value.trim().parse::<u32>().map_err(|e| format!("invalid value {value}: {e}")))
+            .collect::<Result<BTreeSet<u32>, String>>()?;
+
+        Ok(ItemFilter {
+            category,
+            field,
+            values,
+        })
+    }
+}
+
+impl Display for ItemFilter {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let category = match self.category {
+            FilterCategory::Achievements => "achievements",
+            FilterCategory::Books => "books",
+        };
+        let field = match self.field {
+            FilterField::Id => "id",
+            FilterField::Series => "series",
+            FilterField::World => "world",
+            FilterField::Jade => "jade",
+        };
+        let values: Vec<String> = self.values.iter().map(u32::to_string).collect();
+        write!(f, "{category}.{field}={}", values.join(","))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::database::{AchievementInfo, BookInfo};
+
+    fn filter(s: &str) -> ItemFilter {
+        s.parse().unwrap()
+    }
+
+    fn database() -> Database {
+        Database::new(
+            vec![AchievementInfo {
+                id: 4010101,
+                jade: 5,
+                target: None,
+                series: Some(1),
+                name: None,
+            }],
+            vec![BookInfo {
+                id: 140001,
+                series: Some(7),
+                world: Some(4),
+                name: None,
+            }],
+            HashMap::new(),
+        )
+    }
+
+    #[test]
+    fn parses_filters() {
+        assert_eq!(filter("achievements.series=7"), ItemFilter {
+            category: FilterCategory::Achievements,
+            field: FilterField::Series,
+            values: BTreeSet::from([7]),
+        });
+        assert_eq!(filter(" books.world = 5, 4,5"), ItemFilter {
+            category: FilterCategory::Books,
+            field: FilterField::World,
+            values: BTreeSet::from([4, 5]),
+        });
+        assert_eq!(filter("achievements.jade=5").field, FilterField::Jade);
+        assert_eq!(filter("books.id=140001").field, FilterField::Id);
+    }
+
+    #[test]
+    fn rejects_malformed_filters() {
+        for s in [
+            "",
+            "achievements.series",
+            "achievements=7",
+            "quests.id=1",
+            "achievements.world=4",
+            "books.jade=5",
+            "books.series=",
+            "books.series=7,",
+            "books.series=seven",
+            "books.series=-1",
+        ] {
+            assert!(s.parse::<ItemFilter>().is_err(), "{s}");
+        }
+    }
+
+    #[test]
+    fn displays_as_parsed() {
+        for s in ["achievements.jade=5,10", "books.world=4"] {
+            assert_eq!(filter(s).to_string(), s);
+        }
+    }
+
+    #[test]
+    fn keeps_items_of_matching_fields() {
+        let database = database();
+
+        assert!(filter("achievements.series=1,2").keeps_achievement(&database, 4010101));
+        assert!(!filter("achievements.jade=10").keeps_achievement(&database, 4010101));
+        assert!(filter("books.world=4").keeps_book(&database, 140001));
+        assert!(!filter("books.series=1").keeps_book(&database, 140001));
+        // items missing from the database only match on their id
+        assert!(filter("achievements.id=4010102").keeps_achievement(&database, 4010102));
+        assert!(!filter("achievements.series=1").keeps_achievement(&database, 4010102));
+        // filters of the other category keep everything
+        assert!(filter("books.world=5").keeps_achievement(&database, 4010101));
+        assert!(filter("achievements.jade=10").keeps_book(&database, 140001));
+    }
+}
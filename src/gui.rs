@@ -0,0 +1,219 @@
+//! Graphical frontend for users that do not want to use a terminal.
+//!
+//! The window loads the database on startup, captures live packets with the options given
+//! on the command line, shows what was collected so far, and writes the export on request.
+use std::path::PathBuf;
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use eframe::egui;
+use tracing::{error, info};
+
+use completionist_archiver::database::Database;
+use completionist_archiver::export::boxed::BoxedExporter;
+use completionist_archiver::export::output;
+use completionist_archiver::export::registry::{ExporterContext, ExporterRegistry};
+use completionist_archiver::server::SharedCaptureState;
+use completionist_archiver::session::SessionOptions;
+
+use crate::source::live_capture;
+use crate::{report_error, Args, CAPTURING, STOP_REQUESTED};
+
+const TITLE: &str = "completionist archiver";
+/// How often the window is redrawn to show new progress
+const REPAINT_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Opens the window, returning once it is closed
+pub fn run(args: Args) {
+    let app = App::new(args);
+
+    if let Err(e) = eframe::run_native(TITLE, eframe::NativeOptions::default(), Box::new(|_cc| Box::new(app))) {
+        error!(%e, "could not open window");
+    }
+}
+
+enum DatabaseStatus {
+    Loading,
+    Loaded(Arc<Database>),
+    Failed,
+}
+
+struct App {
+    args: Arc<Args>,
+    database: Arc<Mutex<DatabaseStatus>>,
+    state: SharedCaptureState,
+    capture: Option<JoinHandle<()>>,
+    registry: ExporterRegistry,
+    /// Name of the format to capture for the next capture
+    format: String,
+    output: String,
+    message: Option<String>,
+}
+
+impl App {
+    fn new(args: Args) -> App {
+        let database = Arc::new(Mutex::new(DatabaseStatus::Loading));
+
+        let options = args.database_options();
+        let deadline = Duration::from_secs(args.database_timeout);
+        let status = database.clone();
+        std::thread::spawn(move || {
+            *status.lock().unwrap() = match Database::new_from_online_with_deadline(&options, deadline) {
+                Ok(database) => DatabaseStatus::Loaded(Arc::new(database)),
+                Err(e) => {
+                    report_error(&e);
+                    DatabaseStatus::Failed
+                }
+            };
+        });
+
+        let format = args.output().format.first().cloned().unwrap_or_else(|| "fribbels".to_string());
+
+        App {
+            output: args.output().output.display().to_string(),
+            args: Arc::new(args),
+            database,
+            state: SharedCaptureState::default(),
+            capture: None,
+            format,
+            registry: ExporterRegistry::with_builtin(),
+            message: None,
+        }
+    }
+
+    fn is_capturing(&self) -> bool {
+        self.capture.as_ref().is_some_and(|capture| !capture.is_finished())
+    }
+
+    fn start_capture(&mut self) {
+        let status = self.database.lock().unwrap();
+        let DatabaseStatus::Loaded(database) = &*status else {
+            return;
+        };
+
+        let keys = database.keys().clone();
+        let Some(exporter) = self.registry.create(&self.format, &ExporterContext::new(Arc::clone(database))) else {
+            return;
+        };
+        let exporter = BoxedExporter(exporter);
+        drop(status);
+
+        let args = self.args.clone();
+
+        // start over with a fresh state, the previous capture is discarded
+        self.state = SharedCaptureState::default();
+        let options = SessionOptions {
+            finish: args.finish_criteria(),
+            shared_state: Some(self.state.clone()),
+            stop: Some(&STOP_REQUESTED),
+            ..Default::default()
+        };
+
+        STOP_REQUESTED.store(false, Ordering::Relaxed);
+        CAPTURING.store(true, Ordering::Relaxed);
+        self.capture = Some(std::thread::spawn(move || {
+            // the export is written from the shared state when requested
+            if let Err(e) = live_capture(&args, exporter, keys, options) {
+                report_error(&e);
+            }
+            CAPTURING.store(false, Ordering::Relaxed);
+        }));
+        self.message = None;
+    }
+
+    fn export(&mut self) {
+        let Some(export) = self.state.lock().unwrap().export.clone() else {
+            self.message = Some("nothing captured yet".to_string());
+            return;
+        };
+
+        let path = PathBuf::from(&self.output);
+        if let Err(e) = output::write_json(&path, &export) {
+            error!(%e, "could not write export");
+            self.message = Some(e.to_string());
+            return;
+        }
+
+        info!("wrote output to {}", path.display());
+        self.message = Some(format!("wrote export to {}", path.display()));
+    }
+}
+
+impl eframe::App for App {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.heading(TITLE);
+
+            let database_loaded = match &*self.database.lock().unwrap() {
+                DatabaseStatus::Loading => {
+                    ui.label("database: loading...");
+                    false
+                }
+                DatabaseStatus::Loaded(_) => {
+                    ui.label("database: loaded");
+                    true
+                }
+                DatabaseStatus::Failed => {
+                    ui.label("database: could not be loaded, see the log for details");
+                    false
+                }
+            };
+
+            ui.separator();
+
+            let capturing = self.is_capturing();
+            ui.add_enabled_ui(!capturing, |ui| {
+                egui::ComboBox::from_label("format")
+                    .selected_text(self.registry.description(&self.format).unwrap_or(self.format.as_str()))
+                    .show_ui(ui, |ui| {
+                        for name in self.registry.names() {
+                            let description = self.registry.description(name).unwrap_or(name);
+                            ui.selectable_value(&mut self.format, name.to_string(), description);
+                        }
+                    });
+            });
+            ui.horizontal(|ui| {
+                if ui.add_enabled(database_loaded && !capturing, egui::Button::new("Start capture")).clicked() {
+                    self.start_capture();
+                }
+                if ui.add_enabled(capturing, egui::Button::new("Stop capture")).clicked() {
+                    STOP_REQUESTED.store(true, Ordering::Relaxed);
+                }
+            });
+
+            {
+                let state = self.state.lock().unwrap();
+                if capturing {
+                    ui.label(format!(
+                        "capturing, {:02}:{:02} elapsed. go to the main menu and enter train hyperdrive",
+                        state.elapsed / 60,
+                        state.elapsed % 60
+                    ));
+                } else if state.finished {
+                    ui.label("capture finished");
+                }
+                for progress in &state.progress {
+                    ui.label(progress.to_string());
+                }
+            }
+
+            ui.separator();
+
+            ui.horizontal(|ui| {
+                ui.label("output");
+                ui.text_edit_singleline(&mut self.output);
+            });
+            if ui.button("Export").clicked() {
+                self.export();
+            }
+
+            if let Some(message) = &self.message {
+                ui.label(message);
+            }
+        });
+
+        ctx.request_repaint_after(REPAINT_INTERVAL);
+    }
+}
@@ -0,0 +1,57 @@
+//! Prompts telling users what to do in the game while capturing, as new users do not know which
+//! screens of the game send the data that is still missing. Each step is checked off once its
+//! data was collected.
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+use completionist_archiver::server::SharedCaptureState;
+
+use crate::i18n::t;
+use crate::CAPTURING;
+
+/// How often the progress is checked
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// What to do in the game to collect a category, `None` for categories without a step
+fn step(category: &str) -> Option<String> {
+    match category {
+        "uid" => Some(t!("guide-login")),
+        "achievements" => Some(t!("guide-achievements")),
+        "books" => Some(t!("guide-books")),
+        _ => None,
+    }
+}
+
+/// Prints the steps of the categories that are still missing on a thread of its own while
+/// capturing, following the progress published to `state`
+pub fn spawn(state: SharedCaptureState) {
+    std::thread::spawn(move || {
+        let mut pending: Vec<&'static str> = vec![];
+
+        loop {
+            std::thread::sleep(POLL_INTERVAL);
+
+            let state = state.lock().unwrap();
+            if state.finished || !CAPTURING.load(Ordering::Relaxed) {
+                break;
+            }
+
+            let missing: Vec<&'static str> = state.progress.iter()
+                .filter(|progress| !progress.collected && step(progress.name).is_some())
+                .map(|progress| progress.name)
+                .collect();
+
+            for step in pending.iter().filter(|name| !missing.contains(name)).filter_map(|name| step(name)) {
+                println!("  ✓ {step}");
+            }
+            // shown again if a category has to be collected again, e.g. after switching accounts
+            if missing.iter().any(|name| !pending.contains(name)) {
+                println!("{}", t!("guide-intro"));
+                for (number, step) in missing.iter().filter_map(|name| step(name)).enumerate() {
+                    println!("  {}. {step}", number + 1);
+                }
+            }
+            pending = missing;
+        }
+    });
+}
@@ -0,0 +1,144 @@
+//! Translations of the messages shown to users in the terminal, written in [Fluent] in
+//! `locales/<language>/archiver.ftl`. Messages missing from a translation are shown in English.
+//!
+//! Numbers, percentages and dates shown in the terminal are formatted as usual in the language.
+//! The log is not translated, so that logs attached to bug reports stay readable, and
+//! machine outputs like the log, the exports and the reports always use ISO 8601 dates.
+//!
+//! [Fluent]: https://projectfluent.org
+use std::sync::OnceLock;
+
+use chrono::{DateTime, Local};
+use fluent_bundle::concurrent::FluentBundle;
+use fluent_bundle::{FluentArgs, FluentResource};
+use tracing::{debug, warn};
+use unic_langid::LanguageIdentifier;
+
+/// Languages with a translation, English first as it is the fallback
+const LANGUAGES: [(&str, &str); 3] = [
+    ("en", include_str!("../locales/en/archiver.ftl")),
+    ("zh-CN", include_str!("../locales/zh-CN/archiver.ftl")),
+    ("ja", include_str!("../locales/ja/archiver.ftl")),
+];
+
+static TRANSLATIONS: OnceLock<Translations> = OnceLock::new();
+
+struct Translations {
+    bundle: FluentBundle<FluentResource>,
+    fallback: FluentBundle<FluentResource>,
+}
+
+/// Formats a message of the current language, with arguments given as `name = value`
+macro_rules! t {
+    ($id:literal) => {
+        $crate::i18n::message($id, None)
+    };
+    ($id:literal, $($name:ident = $value:expr),+ $(,)?) => {{
+        let mut args = fluent_bundle::FluentArgs::new();
+        $(args.set(stringify!($name), $value);)+
+        $crate::i18n::message($id, Some(&args))
+    }};
+}
+pub(crate) use t;
+
+/// Chooses the language given with --lang, or the language of the system otherwise.
+/// Languages without a translation fall back to English
+pub fn init(lang: Option<&str>) {
+    let requested = lang.map(str::to_string).or_else(sys_locale::get_locale).unwrap_or_default();
+    let language = choose_language(&requested);
+    debug!(requested, language, "chose language");
+
+    let _ = TRANSLATIONS.set(Translations {
+        bundle: bundle(language),
+        fallback: bundle(LANGUAGES[0].0),
+    });
+}
+
+/// Languages with a translation
+fn languages() -> impl Iterator<Item = &'static str> {
+    LANGUAGES.iter().map(|(language, _)| *language)
+}
+
+/// Message `id` of the current language, or the id itself if there is no such message
+pub fn message(id: &str, args: Option<&FluentArgs>) -> String {
+    let translations = TRANSLATIONS.get_or_init(|| Translations {
+        bundle: bundle(LANGUAGES[0].0),
+        fallback: bundle(LANGUAGES[0].0),
+    });
+
+    for bundle in [&translations.bundle, &translations.fallback] {
+        let Some(pattern) = bundle.get_message(id).and_then(|message| message.value()) else {
+            continue;
+        };
+        let mut errors = vec![];
+        let message = bundle.format_pattern(pattern, args, &mut errors);
+        if !errors.is_empty() {
+            warn!(id, ?errors, "could not format message");
+        }
+        return message.into_owned();
+    }
+
+    warn!(id, "missing message");
+    id.to_string()
+}
+
+/// Formats a number with the digit grouping of the current language, e.g. `12,345`
+pub fn number(number: impl Into<u64>) -> String {
+    let digits = number.into().to_string();
+    let separator = message("number-group-separator", None);
+
+    let mut grouped = String::with_capacity(digits.len() + digits.len() / 3 * separator.len());
+    for (index, digit) in digits.chars().enumerate() {
+        if index > 0 && (digits.len() - index) % 3 == 0 {
+            grouped.push_str(&separator);
+        }
+        grouped.push(digit);
+    }
+    grouped
+}
+
+/// Formats the share of `part` in `whole` as a whole percentage, 100% if `whole` is 0
+pub fn percent(part: usize, whole: usize) -> String {
+    let percent = if whole == 0 { 100 } else { part * 100 / whole };
+    message("percent", Some(&FluentArgs::from_iter([("value", percent.to_string())])))
+}
+
+/// Formats a local date and time as usual in the current language
+pub fn date_time(date_time: &DateTime<Local>) -> String {
+    date_time.format(&message("date-time-format", None)).to_string()
+}
+
+/// Language with a translation for a locale like `zh-Hans-CN`, `ja_JP.UTF-8` or `de`
+fn choose_language(requested: &str) -> &'static str {
+    let requested = requested.split('.').next().unwrap_or_default().replace('_', "-");
+    let Ok(requested) = requested.parse::<LanguageIdentifier>() else {
+        return LANGUAGES[0].0;
+    };
+
+    languages()
+        .find(|language| {
+            let language: LanguageIdentifier = language.parse().unwrap();
+            // chinese is translated to simplified chinese only
+            language.language == requested.language && (requested.language != "zh" || is_simplified_chinese(&requested))
+        })
+        .unwrap_or(LANGUAGES[0].0)
+}
+
+fn is_simplified_chinese(language: &LanguageIdentifier) -> bool {
+    match (language.script.as_ref().map(|script| script.as_str()), language.region.as_ref().map(|region| region.as_str())) {
+        (Some(script), _) => script == "Hans",
+        (None, Some(region)) => !matches!(region, "TW" | "HK" | "MO"),
+        (None, None) => true,
+    }
+}
+
+fn bundle(language: &str) -> FluentBundle<FluentResource> {
+    let (_, source) = LANGUAGES.iter().find(|(name, _)| *name == language).expect("language has a translation");
+    let resource = FluentResource::try_new(source.to_string()).expect("translations are valid fluent");
+
+    let mut bundle = FluentBundle::new_concurrent(vec![language.parse().unwrap()]);
+    // the isolation marks around arguments are shown as garbage by most terminals
+    bundle.set_use_isolating(false);
+    bundle.add_resource(resource).expect("translations have no duplicate messages");
+    bundle
+}
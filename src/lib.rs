@@ -1 +1,19 @@
+//! Library behind the completionist archiver.
+//!
+//! [`database::Database`] loads the game data and decryption keys needed to make sense of
+//! captured packets. Decrypted commands are collected into the [`model`] of an account,
+//! which the [`export`] module turns into export files after [`validation`] against the database.
+//! A [`session::Session`] puts both together, feeding packets captured live or read from
+//! capture files using the [`capture`] module into an [`export::Exporter`].
+//! The progress of a running capture can be shared using the [`server`] module.
+//! Everything that can fail returns an [`error::ArchiverError`].
+pub mod capture;
+pub mod database;
+pub mod error;
 pub mod export;
+pub mod filter;
+pub mod logging;
+pub mod model;
+pub mod server;
+pub mod session;
+pub mod validation;
@@ -0,0 +1,114 @@
+//! Logging helpers for frontends using [`tracing_subscriber`].
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+
+use tracing::callsite::Identifier;
+use tracing::field::{Field, Visit};
+use tracing::{warn, Event, Level, Subscriber};
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::Layer;
+
+/// How often the same warning is logged before further repetitions are suppressed
+const REPEATED_WARNING_LIMIT: usize = 3;
+
+/// Layer collapsing repeated warnings, e.g. a parsing failure for every packet of a session
+/// with outdated keys.
+///
+/// Warnings are the same if they are logged at the same place with the same message and
+/// command id. Each is logged a few times, further repetitions are only counted and
+/// summed up by [`WarningThrottle::report`].
+#[derive(Clone, Default)]
+pub struct WarningThrottle {
+    warnings: Arc<Mutex<HashMap<WarningKey, Repetitions>>>,
+    /// How often every message was warned about since the start, kept across reports
+    totals: Arc<Mutex<BTreeMap<String, usize>>>,
+}
+
+#[derive(PartialEq, Eq, Hash)]
+struct WarningKey {
+    callsite: Identifier,
+    message: String,
+    command_id: Option<u64>,
+}
+
+#[derive(Default)]
+struct Repetitions {
+    logged: usize,
+    suppressed: usize,
+}
+
+impl WarningThrottle {
+    pub fn new() -> WarningThrottle {
+        WarningThrottle::default()
+    }
+
+    /// Logs how often every warning was suppressed since the last report, and starts over
+    pub fn report(&self) {
+        // taken out before logging, as logging goes through this layer again
+        let warnings = std::mem::take(&mut *self.warnings.lock().unwrap());
+
+        for (key, repetitions) in warnings {
+            if repetitions.suppressed > 0 {
+                warn!(
+                    command_id = key.command_id,
+                    "suppressed {} more repetitions of: {}",
+                    repetitions.suppressed,
+                    key.message
+                );
+            }
+        }
+    }
+
+    /// Every message warned about since the start with how often it was, logged or suppressed
+    pub fn totals(&self) -> Vec<(String, usize)> {
+        self.totals.lock().unwrap().iter().map(|(message, &count)| (message.clone(), count)).collect()
+    }
+}
+
+impl<S: Subscriber> Layer<S> for WarningThrottle {
+    fn event_enabled(&self, event: &Event<'_>, _ctx: Context<'_, S>) -> bool {
+        if *event.metadata().level() != Level::WARN {
+            return true;
+        }
+
+        let mut fields = WarningFields::default();
+        event.record(&mut fields);
+        *self.totals.lock().unwrap().entry(fields.message.clone()).or_default() += 1;
+        let key = WarningKey {
+            callsite: event.metadata().callsite(),
+            message: fields.message,
+            command_id: fields.command_id,
+        };
+
+        let mut warnings = self.warnings.lock().unwrap();
+        let repetitions = warnings.entry(key).or_default();
+        if repetitions.logged < REPEATED_WARNING_LIMIT {
+            repetitions.logged += 1;
+            true
+        } else {
+            repetitions.suppressed += 1;
+            false
+        }
+    }
+}
+
+#[derive(Default)]
+struct WarningFields {
+    message: String,
+    command_id: Option<u64>,
+}
+
+impl Visit for WarningFields {
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        if field.name() == "command_id" {
+            self.command_id = Some(value);
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        if field.name() == "message" {
+            self.message = format!("{value:?}");
+        }
+    }
+}
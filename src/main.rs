@@ -1,76 +1,900 @@
 use std::fs::File;
-use std::path::PathBuf;
-use std::sync::{mpsc, Mutex};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Mutex, OnceLock};
 use std::time::Duration;
 
-use clap::Parser;
-use pcap::{ConnectionStatus, Device, Error};
-use reliquary::network::{ConnectionPacket, GamePacket, GameSniffer};
-use reliquary::network::gen::command_id::{PlayerLoginFinishScRsp, PlayerLoginScRsp};
-use tracing::{debug, error, info, instrument, trace, warn};
+use chrono::{Local, NaiveTime};
+use clap::error::ErrorKind;
+use clap::parser::ValueSource;
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, error, info, warn};
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
 use tracing_subscriber::{EnvFilter, Layer, prelude::*, Registry};
 
-use completionist_archiver::export::Exporter;
-use completionist_archiver::export::fribbels::{Database, OptimizerExporter};
+use completionist_archiver::database::{Database, DatabaseOptions, HttpTimeouts};
+use completionist_archiver::error::{ArchiverError, PathContext};
+use completionist_archiver::export::FinishCriteria;
+use completionist_archiver::export::output::{self, OnConflict, Outputs};
+use completionist_archiver::export::registry::ExporterRegistry;
+use completionist_archiver::export::verify;
+use completionist_archiver::filter::ItemFilter;
+use completionist_archiver::logging::WarningThrottle;
+use completionist_archiver::model::{self, CompletedStatuses};
+
+use crate::commands::{can_prompt, capture_and_export, prompt_conflict, reveal};
+use crate::i18n::t;
+
+mod commands;
+mod config;
+#[cfg(feature = "gui")]
+mod gui;
+mod guide;
+mod i18n;
+#[cfg(feature = "notify")]
+mod notify;
+mod print;
+mod setup;
+mod source;
+mod stall;
+mod summary;
+#[cfg(all(windows, feature = "tray"))]
+mod tray;
+#[cfg(feature = "tui")]
+mod tui;
+mod update;
+
+/// Seconds after the last new data to stop unattended captures, which nobody is watching
+const UNATTENDED_QUIET_PERIOD: u64 = 120;
+/// Set while packets are being captured
+static CAPTURING: AtomicBool = AtomicBool::new(false);
+/// Set once the user asked to stop capturing early, either with ctrl-c or by pressing enter
+static STOP_REQUESTED: AtomicBool = AtomicBool::new(false);
+/// Lines entered while not capturing, see [`install_stop_handlers`]
+static INPUT: OnceLock<Mutex<mpsc::Receiver<String>>> = OnceLock::new();
+/// Collapses repeated warnings, reported once a capture ends
+static WARNINGS: OnceLock<WarningThrottle> = OnceLock::new();
+/// End of the log shown by the terminal dashboard, instead of logging to the terminal
+#[cfg(feature = "tui")]
+static LOG_TAIL: OnceLock<tui::LogTail> = OnceLock::new();
+
+#[derive(Parser, Debug, Clone)]
+struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// Options of the capture run without a command, which the commands without them fall back on
+    #[command(flatten)]
+    capture: CaptureArgs,
+    /// Path to a .toml file with defaults for the options, instead of archiver.toml
+    /// in the config directory of the platform
+    #[arg(long, value_name = "PATH", global = true, env = "ARCHIVER_CONFIG")]
+    config: Option<PathBuf>,
+    /// Profile of the config file to apply on top of its options, e.g. `alt` for the options
+    /// in its [profile.alt] table
+    #[arg(long, global = true, env = "ARCHIVER_PROFILE")]
+    profile: Option<String>,
+    /// How verbose the output should be, can be set up to 3 times. Has no effect if RUST_LOG is set
+    #[arg(short, long, global = true, action = clap::ArgAction::Count)]
+    verbose: u8,
+    /// Only log errors, to stderr, and print the paths of the written exports to stdout, one per line,
+    /// for scripts. Does not wait for enter before closing
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+    /// Language of the messages shown in the terminal and the format of their numbers and dates,
+    /// e.g. zh-CN or ja. Uses the language of the system if not set. The log is always in English
+    #[arg(long, visible_alias = "locale", global = true, env = "ARCHIVER_LANG")]
+    lang: Option<String>,
+    /// Path to output log to, always as JSON lines
+    #[arg(short, long, alias = "log-file", global = true, env = "ARCHIVER_LOG_PATH")]
+    log_path: Option<PathBuf>,
+    /// Format of the log written to the terminal, json writes one JSON object per line
+    /// for services like systemd or the task scheduler to parse
+    #[arg(long, value_enum, default_value_t = LogFormat::Text, global = true, env = "ARCHIVER_LOG_FORMAT")]
+    log_format: LogFormat,
+    /// How long to wait in seconds for a connection to a resource server
+    #[arg(long, default_value_t = 10, global = true, env = "ARCHIVER_CONNECT_TIMEOUT")]
+    connect_timeout: u64,
+    /// How long to wait in seconds for data from a resource server once connected
+    #[arg(long, default_value_t = 30, global = true, env = "ARCHIVER_READ_TIMEOUT")]
+    read_timeout: u64,
+    /// How long to wait in seconds for the database to finish loading before giving up
+    #[arg(long, default_value_t = 180, global = true, env = "ARCHIVER_DATABASE_TIMEOUT")]
+    database_timeout: u64,
+    /// Do not check on startup whether there is a newer version of the archiver
+    #[arg(long, global = true)]
+    no_update_check: bool,
+}
 
-const PACKET_FILTER: &str = "udp portrange 23301-23302";
+/// Options of a capture, live or of a recording
+#[derive(clap::Args, Debug, Clone)]
+struct CaptureArgs {
+    #[command(flatten)]
+    output: OutputArgs,
+    #[command(flatten)]
+    export: ExportArgs,
+    #[command(flatten)]
+    session: SessionArgs,
+    #[command(flatten)]
+    live: LiveArgs,
+}
 
-#[derive(Parser, Debug)]
-struct Args {
-    #[arg(default_value = "archive_output.json")]
-    /// Path to output .json file to
+/// Where and in which formats the exports are written
+#[derive(clap::Args, Debug, Clone)]
+struct OutputArgs {
+    #[arg(default_value = "archive_output.json", env = "ARCHIVER_OUTPUT")]
+    /// Path to output .json file to. Can contain the placeholders {uid}, {date}, {time} and {format},
+    /// e.g. "exports/{uid}_{date}_{format}.json". Use - to print the export to stdout
     output: PathBuf,
-    /// Read packets from .pcap file instead of capturing live packets
+    /// Format to export in, can be given multiple times or comma separated to export in several
+    /// formats at once, each written to OUTPUT with the format appended to the name.
+    /// In watch mode the exports of all formats are written to OUTPUT together. The csv format is
+    /// written as a table with a row for every item instead of JSON
+    #[arg(long, value_delimiter = ',', default_value = "fribbels", value_parser = parse_format, env = "ARCHIVER_FORMAT")]
+    format: Vec<String>,
+    /// Path to write the export of one format to instead of OUTPUT, e.g. `fribbels=archive.json`,
+    /// can be given multiple times. Can contain the same placeholders as OUTPUT. Not used when OUTPUT
+    /// is - or in watch mode
+    #[arg(long, value_name = "FORMAT=PATH", value_parser = parse_format_output)]
+    out: Vec<(String, PathBuf)>,
+    #[command(flatten)]
+    write: WriteArgs,
+}
+
+/// How the exports are written
+#[derive(clap::Args, Debug, Clone)]
+struct WriteArgs {
+    /// Capture and parse as usual, but only show what would be exported instead of writing
+    /// the output. Together with an OUTPUT of - the export is printed
+    #[arg(long)]
+    dry_run: bool,
+    /// What to do if an output file exists already. Without a terminal to prompt in,
+    /// the existing file is backed up. Watch mode always overwrites its output
+    #[arg(long, value_enum, default_value_t = OnConflict::Prompt, env = "ARCHIVER_ON_CONFLICT")]
+    on_conflict: OnConflict,
+    /// Write the SHA-256 checksum of every export next to it, e.g. to archive_output.json.sha256,
+    /// which the verify command checks the export against
+    #[arg(long)]
+    checksum: bool,
+}
+
+/// What is read from the commands of the game and exported
+#[derive(clap::Args, Debug, Clone)]
+struct ExportArgs {
+    /// Uid to export under, whatever uid is detected. For captures that missed the login,
+    /// where the uid cannot be detected
+    #[arg(long, env = "ARCHIVER_UID", value_parser = parse_uid)]
+    uid: Option<u32>,
+    /// Path to an earlier export to merge the new export into. Items that were not captured this
+    /// time are kept, and the date every item was first captured on is preserved.
+    /// Only applies to the fribbels format
+    #[arg(long, value_name = "PATH")]
+    merge: Option<PathBuf>,
+    /// Statuses of achievements that count as completed, can be given multiple times or comma separated.
+    /// E.g. `claimed` leaves out achievements whose reward was not claimed yet
+    #[arg(long, value_enum, value_delimiter = ',', default_values_t = [Completed::Unclaimed, Completed::Claimed])]
+    completed: Vec<Completed>,
+    /// Only export the items matching a filter like `achievements.series=7` or `books.world=4,5`,
+    /// can be given multiple times. Achievements can be filtered by id, series and jade,
+    /// books by id, series and world
+    #[arg(long, value_name = "FILTER")]
+    only: Vec<ItemFilter>,
+    /// Path to a .json file overriding the ids of the commands that are read, for beta clients,
+    /// e.g. {"GetQuestDataScRsp": 1234}
+    #[arg(long, value_name = "PATH", env = "ARCHIVER_COMMAND_IDS")]
+    command_ids: Option<PathBuf>,
+    /// Path to a .json file with the field numbers of other game versions, tried for commands
+    /// whose fields were shifted by a patch, e.g. [{"version": "3.3", "messages": {"Quest": {"9": 1}}}]
+    #[arg(long, value_name = "PATH", env = "ARCHIVER_FIELD_MAPPINGS")]
+    field_mappings: Option<PathBuf>,
+    /// Path to write a report of the capture session to as .json, with why capturing stopped,
+    /// the progress of every category, parse failures and the statistics of --stats
+    #[arg(long, value_name = "PATH", conflicts_with = "dry_run")]
+    report: Option<PathBuf>,
+    /// Path to stream the collected items to while capturing, as newline delimited JSON events
+    /// written as soon as items are detected. Requires the ndjson format, e.g. `--format fribbels,ndjson`
+    #[arg(long, value_name = "PATH", conflicts_with = "dry_run")]
+    stream: Option<PathBuf>,
+}
+
+/// When a capture stops, and what is published and recorded while capturing
+#[derive(clap::Args, Debug, Clone)]
+struct SessionArgs {
+    /// Category that has to be collected before capturing stops, can be given multiple times.
+    /// All categories (uid, achievements, books) are required if not set
+    #[arg(long, value_name = "CATEGORY")]
+    require: Vec<String>,
+    /// Minimum number of items of a category before capturing stops, e.g. achievements=100,
+    /// can be given multiple times
+    #[arg(long, value_name = "CATEGORY=COUNT", value_parser = parse_min_count)]
+    min_count: Vec<(String, usize)>,
+    /// Stop capturing once nothing new was collected for this many seconds, even if not everything
+    /// was collected yet. Data sent after logging in is waited for as well. Defaults to 120 with
+    /// --unattended and --schedule, and is not used in watch mode
+    #[arg(long, value_name = "SECONDS", alias = "idle-finish", env = "ARCHIVER_QUIET_PERIOD")]
+    quiet_period: Option<u64>,
+    /// Capture multiple game clients running at the same time, each client being exported
+    /// to its own file named after the uid. Progress is only logged
+    #[arg(long, conflicts_with_all = ["uid", "stats", "report", "debug_dump", "stream"])]
+    multi_instance: bool,
+    /// Address to serve a websocket on, broadcasting every detected item as a JSON event
+    /// while capturing, e.g. 127.0.0.1:9001
+    #[arg(long, env = "ARCHIVER_WEBSOCKET")]
+    websocket: Option<SocketAddr>,
+    /// Address to serve an HTTP API on while running, with `GET /status` returning the progress
+    /// of the capture and `GET /export` the data collected so far, e.g. 127.0.0.1:9002.
+    /// `GET /metrics` serves statistics of the capture for Prometheus
+    #[arg(long, env = "ARCHIVER_HTTP")]
+    http: Option<SocketAddr>,
+    /// Path to write statistics of the capture session to as .json, useful when reporting
+    /// captures that did not collect anything
+    #[arg(long, conflicts_with = "dry_run")]
+    stats: Option<PathBuf>,
+    /// Path to write the summary shown at the end of the capture to, with the warnings of the run
+    /// and the game data it was checked against, as .json or as text otherwise, e.g. `{uid}_run-summary.txt`.
+    /// Can contain the same placeholders as OUTPUT
+    #[arg(long, value_name = "PATH", conflicts_with = "dry_run")]
+    run_summary: Option<PathBuf>,
+    /// Path to write the decrypted commands of the capture to, to attach to bug reports.
+    /// Contains your uid unless --redact is set
+    #[arg(long, value_name = "PATH", conflicts_with = "dry_run")]
+    debug_dump: Option<PathBuf>,
+    /// Remove the uid and key seed from the debug dump
+    #[arg(long, requires = "debug_dump")]
+    redact: bool,
+    /// Play a sound once the export is written, and when nothing new was collected for a minute
+    /// while capturing, e.g. because the inventory has to be opened in the game
+    #[arg(long)]
+    sound: bool,
+    /// Show the written export in the file manager once capturing finished
+    #[arg(long)]
+    open: bool,
+    /// Show a desktop notification with how much was collected once the export is written,
+    /// and when nothing new was collected for a minute while capturing
+    #[cfg(feature = "notify")]
+    #[arg(long, conflicts_with = "multi_instance")]
+    notify: bool,
+}
+
+/// Where live packets are captured, for how long, and how the capture is shown
+#[derive(clap::Args, Debug, Clone)]
+struct LiveArgs {
+    /// Read packets from .pcap or .pcapng file instead of capturing live packets
     #[arg(long)]
     pcap: Option<PathBuf>,
-    /// How long to wait in seconds until timeout is triggered for live captures
-    #[arg(long, default_value_t = 120)]
+    /// Network interface to capture on, can be given multiple times.
+    /// Captures on all connected interfaces if not set, see the `interfaces` command for a list
+    #[arg(long, env = "ARCHIVER_INTERFACE")]
+    interface: Vec<String>,
+    /// Library to capture live packets with. WinDivert does not need Npcap to be installed,
+    /// but has to be run as administrator and always captures on all interfaces
+    #[arg(long, value_enum, default_value_t = Backend::Pcap)]
+    backend: Backend,
+    /// Read live packets in pcap or pcapng format from a TCP connection to this address
+    /// instead of capturing on this machine, e.g. from a router running
+    /// `tcpdump -i any -U -w - udp | nc -l -p 9000`
+    #[arg(long, value_name = "HOST:PORT", conflicts_with = "pcap")]
+    remote: Option<String>,
+    /// How long to capture in seconds before giving up and writing what was collected so far,
+    /// for live captures. Watch mode has no timeout
+    #[arg(long, default_value_t = 300, env = "ARCHIVER_TIMEOUT")]
     timeout: u64,
-    /// How verbose the output should be, can be set up to 3 times. Has no effect if RUST_LOG is set
-    #[arg(short, long, action = clap::ArgAction::Count)]
-    verbose: u8,
-    /// Path to output log to
-    #[arg(short, long)]
-    log_path: Option<PathBuf>,
+    /// Wait for the game to start before starting the timeout, for live captures
+    #[arg(long)]
+    wait: bool,
+    /// Keep capturing when the game is closed, instead of stopping and exporting right away
+    #[arg(long)]
+    keep_after_exit: bool,
+    /// Wait for the game, capture once and exit without waiting for enter, writing the output
+    /// to a file named after the current date. Suited to be started by a task scheduler or cron
+    #[arg(long, conflicts_with = "watch")]
+    unattended: bool,
+    /// Capture unattended every day at the given local time, e.g. "daily 20:00"
+    #[arg(long, value_parser = parse_schedule, conflicts_with = "watch")]
+    schedule: Option<NaiveTime>,
+    /// Keep capturing across game sessions without a timeout, rewriting the output file
+    /// whenever new data is collected, until stopped with enter or ctrl-c
+    #[arg(long, conflicts_with_all = ["pcap", "quiet_period", "dry_run", "multi_instance"])]
+    watch: bool,
+    /// Minimum time in minutes between rewrites of the output file in watch mode
+    #[arg(long, default_value_t = 0)]
+    watch_interval: u64,
+    /// Do not print what to do in the game to collect the data that is still missing while capturing
+    #[arg(long)]
+    no_guide: bool,
+    /// Capture in the background with a tray icon showing the progress instead of a console window,
+    /// it is recommended to also set a log path
+    #[cfg(all(windows, feature = "tray"))]
+    #[arg(long)]
+    tray: bool,
+    /// Open a window to capture and export from instead of capturing right away
+    #[cfg(feature = "gui")]
+    #[arg(long)]
+    gui: bool,
+    /// Show a dashboard in the terminal with the progress, the items detected last and the end
+    /// of the log, instead of the log output
+    #[cfg(feature = "tui")]
+    #[arg(long, conflicts_with_all = ["websocket", "multi_instance", "quiet"])]
+    tui: bool,
 }
 
-fn main() {
-    color_eyre::install().unwrap();
-    let args = Args::parse();
+#[derive(Subcommand, Debug, Clone)]
+enum ConfigCommand {
+    /// Check that the interfaces and files of the options exist, and print the options the config
+    /// file, the environment variables and the command line amount to, as a config file
+    Check,
+}
 
-    tracing_init(&args);
+#[derive(Subcommand, Debug, Clone)]
+enum Command {
+    /// List the network interfaces that can be captured on
+    Interfaces,
+    /// Capture packets live, same as running without a command
+    Capture {
+        /// Only record the game traffic to a .pcapng file instead of exporting it.
+        /// The recording can be exported later with the replay command
+        #[arg(long)]
+        raw: Option<PathBuf>,
+        #[command(flatten)]
+        capture: CaptureArgs,
+    },
+    /// Export a recording made with `capture --raw` (or any .pcap/.pcapng file) to OUTPUT
+    #[command(alias = "process")]
+    Replay {
+        /// Path to the recording
+        file: PathBuf,
+        /// Replay the recording at the pace it was recorded, sped up by this factor,
+        /// instead of as fast as possible
+        #[arg(long)]
+        speed: Option<f64>,
+        #[command(flatten)]
+        output: OutputArgs,
+        #[command(flatten)]
+        export: ExportArgs,
+        #[command(flatten)]
+        session: SessionArgs,
+    },
+    /// Export hand-written commands from a JSON fixture, or the commands of a dump made with
+    /// `--debug-dump`, to OUTPUT, to check the exporter without a capture
+    Inject {
+        /// Path to the fixture (.json) or dump (.bin)
+        fixture: PathBuf,
+        #[command(flatten)]
+        output: OutputArgs,
+        #[command(flatten)]
+        export: ExportArgs,
+    },
+    /// Convert a fribbels export written by any earlier version to the latest version of
+    /// the formats given with --format, writing it to OUTPUT without capturing again
+    Convert {
+        /// Path to the earlier export
+        export: PathBuf,
+        #[command(flatten)]
+        output: OutputArgs,
+        /// Only convert the items matching a filter, like the --only of a capture
+        #[arg(long, value_name = "FILTER")]
+        only: Vec<ItemFilter>,
+    },
+    /// Merge fribbels exports of the same account into OUTPUT, e.g. captured on different devices,
+    /// keeping every item and the earliest date it was captured on. The metadata of the last export
+    /// is kept, with the jade counted again from the merged achievements. Exports of other accounts
+    /// are listed without merging anything
+    Merge {
+        /// Paths to the exports, oldest first
+        #[arg(required = true, num_args = 2..)]
+        exports: Vec<PathBuf>,
+        /// Path to write the merged export to. Can contain the same placeholders as the OUTPUT of a capture
+        #[arg(short, long, default_value = "archive_output.json", env = "ARCHIVER_OUTPUT")]
+        output: PathBuf,
+        #[command(flatten)]
+        write: WriteArgs,
+    },
+    /// List the items that were added and removed between two fribbels exports, with their names
+    /// and the jade of the achievements
+    Diff {
+        /// Path to the older export
+        old: PathBuf,
+        /// Path to the newer export
+        new: PathBuf,
+        /// Print the list as Markdown, e.g. to post the progress
+        #[arg(long)]
+        markdown: bool,
+    },
+    /// Load the game data and show what it contains, to check that it is up to date
+    Db,
+    /// Ask for the interfaces to capture on, the output folder and the formats, and save them
+    /// to the config file. Also done on the first launch without any options
+    Setup,
+    /// Work with the config file
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+    /// Check a fribbels export against the game data, exiting with an error if it is out of date,
+    /// contains items the game data does not know or items twice, or does not match its checksum
+    Verify {
+        /// Path to the export
+        export: PathBuf,
+    },
+    /// Show how much of the game a fribbels export completed, by series, and the rewards that
+    /// have not been claimed yet
+    Stats {
+        /// Path to the export
+        export: PathBuf,
+    },
+    /// Print the completions of the options and commands for a shell, e.g.
+    /// `completionist-archiver completions bash > /etc/bash_completion.d/completionist-archiver`
+    Completions {
+        shell: Shell,
+    },
+    /// Replace this binary with the latest release from GitHub, after verifying its checksum
+    SelfUpdate {
+        /// Only check whether there is a newer release
+        #[arg(long)]
+        check: bool,
+    },
+}
 
-    debug!(?args);
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum Backend {
+    /// libpcap, or Npcap on Windows
+    Pcap,
+    /// WinDivert, only available on Windows when built with the windivert feature
+    #[value(name = "windivert")]
+    WinDivert,
+}
 
-    let database = Database::new_from_online();
-    let sniffer = GameSniffer::new().set_initial_keys(database.keys().clone());
-    let exporter = OptimizerExporter::new(database);
+#[derive(ValueEnum, Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum LogFormat {
+    /// Human readable lines
+    Text,
+    /// One JSON object per line
+    Json,
+}
 
-    let export = match args.pcap {
-        Some(_) => file_capture(&args, exporter, sniffer),
-        None => live_capture(&args, exporter, sniffer),
-    };
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum Completed {
+    /// Achievements whose progress is full, but that the game did not mark as completed yet
+    InProgress,
+    /// Completed achievements whose reward has not been claimed yet
+    Unclaimed,
+    /// Completed achievements whose reward was claimed
+    Claimed,
+}
+
+impl Args {
+    /// Options of the capture command, or of running without a command
+    fn capture(&self) -> &CaptureArgs {
+        match &self.command {
+            Some(Command::Capture { capture, .. }) => capture,
+            _ => &self.capture,
+        }
+    }
+
+    fn capture_mut(&mut self) -> &mut CaptureArgs {
+        match &mut self.command {
+            Some(Command::Capture { capture, .. }) => capture,
+            _ => &mut self.capture,
+        }
+    }
+
+    fn output(&self) -> &OutputArgs {
+        match &self.command {
+            Some(Command::Replay { output, .. } | Command::Inject { output, .. } | Command::Convert { output, .. }) => output,
+            _ => &self.capture().output,
+        }
+    }
+
+    fn export(&self) -> &ExportArgs {
+        match &self.command {
+            Some(Command::Replay { export, .. } | Command::Inject { export, .. }) => export,
+            _ => &self.capture().export,
+        }
+    }
 
-    if let Some(export) = export {
-        let file = File::create(&args.output).unwrap();
-        serde_json::to_writer_pretty(&file, &export).unwrap();
-        info!("wrote output to {}", &args.output.display());
+    fn session(&self) -> &SessionArgs {
+        match &self.command {
+            Some(Command::Replay { session, .. }) => session,
+            _ => &self.capture().session,
+        }
+    }
+
+    fn live(&self) -> &LiveArgs {
+        &self.capture().live
+    }
+
+    /// Options of the command that the config file can set, those of running without a command
+    /// for the options the command does not take
+    fn groups_mut(&mut self) -> (&mut OutputArgs, &mut ExportArgs, &mut SessionArgs, &mut LiveArgs) {
+        let default = &mut self.capture;
+        match &mut self.command {
+            Some(Command::Capture { capture, .. }) => (&mut capture.output, &mut capture.export, &mut capture.session, &mut capture.live),
+            Some(Command::Replay { output, export, session, .. }) => (output, export, session, &mut default.live),
+            Some(Command::Inject { output, export, .. }) => (output, export, &mut default.session, &mut default.live),
+            Some(Command::Convert { output, .. }) => (output, &mut default.export, &mut default.session, &mut default.live),
+            _ => (&mut default.output, &mut default.export, &mut default.session, &mut default.live),
+        }
+    }
+
+    fn http_timeouts(&self) -> HttpTimeouts {
+        HttpTimeouts {
+            connect: Duration::from_secs(self.connect_timeout),
+            read: Duration::from_secs(self.read_timeout),
+        }
+    }
+
+    fn finish_criteria(&self) -> FinishCriteria {
+        let (session, live) = (self.session(), self.live());
+        FinishCriteria {
+            required: (!session.require.is_empty()).then(|| session.require.clone()),
+            min_counts: session.min_count.iter().cloned().collect(),
+            quiet_period: session.quiet_period
+                .or((live.unattended || live.schedule.is_some()).then_some(UNATTENDED_QUIET_PERIOD))
+                .map(Duration::from_secs),
+        }
+    }
+
+    fn completed_statuses(&self) -> CompletedStatuses {
+        let completed = &self.export().completed;
+        CompletedStatuses {
+            in_progress: completed.contains(&Completed::InProgress),
+            unclaimed: completed.contains(&Completed::Unclaimed),
+            claimed: completed.contains(&Completed::Claimed),
+        }
+    }
+
+    fn database_options(&self) -> DatabaseOptions {
+        DatabaseOptions {
+            timeouts: self.http_timeouts(),
+            // names are only shown in events, statistics and diffs
+            names: self.session().websocket.is_some()
+                || self.shows_dashboard()
+                || matches!(self.command, Some(Command::Stats { .. } | Command::Diff { .. })),
+        }
+    }
+
+    /// Where and how the exports are written
+    fn outputs(&self) -> Outputs {
+        let prompt = can_prompt(self).then_some(prompt_conflict as fn(&Path) -> OnConflict);
+        if let Some(Command::Merge { output, write, .. }) = &self.command {
+            return Outputs {
+                output: output.clone(),
+                out: vec![],
+                formats: vec!["fribbels".to_string()],
+                on_conflict: write.on_conflict,
+                prompt,
+                dry_run: write.dry_run,
+                checksum: write.checksum,
+            };
+        }
+
+        let output = self.output();
+        Outputs {
+            output: output.output.clone(),
+            out: output.out.clone(),
+            formats: output.format.clone(),
+            // watch mode rewrites its own output
+            on_conflict: if self.live().watch { OnConflict::Overwrite } else { output.write.on_conflict },
+            prompt,
+            dry_run: output.write.dry_run,
+            checksum: output.write.checksum,
+        }
+    }
+
+    /// Whether desktop notifications are shown
+    fn notifies(&self) -> bool {
+        #[cfg(feature = "notify")]
+        return self.session().notify;
+        #[cfg(not(feature = "notify"))]
+        false
+    }
+
+    /// Whether the steps to take in the game are printed while capturing live, which is only
+    /// done in a terminal that is not used for anything else
+    fn guides(&self) -> bool {
+        #[cfg(all(windows, feature = "tray"))]
+        if self.live().tray {
+            return false;
+        }
+        !self.live().no_guide
+            && !self.quiet
+            && !self.session().multi_instance
+            && !self.shows_dashboard()
+            && !output::is_stdout(&self.output().output)
+    }
+
+    /// Whether the terminal dashboard is shown instead of the log output
+    fn shows_dashboard(&self) -> bool {
+        #[cfg(feature = "tui")]
+        return self.live().tui;
+        #[cfg(not(feature = "tui"))]
+        false
+    }
+}
+
+fn parse_schedule(s: &str) -> Result<NaiveTime, String> {
+    let time = s.strip_prefix("daily").unwrap_or(s).trim();
+    NaiveTime::parse_from_str(time, "%H:%M").map_err(|e| format!("expected \"daily HH:MM\": {e}"))
+}
+
+fn parse_format(s: &str) -> Result<String, String> {
+    let registry = ExporterRegistry::with_builtin();
+    if registry.contains(s) {
+        Ok(s.to_string())
     } else {
-        warn!("skipped writing output");
+        let names: Vec<&str> = registry.names().collect();
+        Err(format!("expected one of {}", names.join(", ")))
+    }
+}
+
+fn parse_format_output(s: &str) -> Result<(String, PathBuf), String> {
+    let (format, path) = s.split_once('=').ok_or("expected FORMAT=PATH")?;
+    Ok((parse_format(format)?, PathBuf::from(path)))
+}
+
+fn parse_uid(s: &str) -> Result<u32, String> {
+    let uid = s.parse().map_err(|e| format!("invalid uid: {e}"))?;
+    if !model::is_plausible_uid(uid) {
+        return Err(format!("{uid} is not the uid of an account, which has 9 or 10 digits"));
+    }
+    Ok(uid)
+}
+
+fn parse_min_count(s: &str) -> Result<(String, usize), String> {
+    let (category, count) = s.split_once('=').ok_or("expected CATEGORY=COUNT")?;
+    let count = count.parse().map_err(|e| format!("invalid count: {e}"))?;
+    Ok((category.to_string(), count))
+}
+
+/// Fails if options of the capture are given before a command, which only takes its own options
+fn reject_capture_options(matches: &ArgMatches) -> Result<(), clap::Error> {
+    let Some((name, _)) = matches.subcommand() else {
+        return Ok(());
+    };
+
+    let capture = <CaptureArgs as clap::Args>::augment_args(clap::Command::new("capture"));
+    let given = capture.get_arguments().find(|arg| matches.value_source(arg.get_id().as_str()) == Some(ValueSource::CommandLine));
+    match given {
+        Some(arg) => {
+            let option = arg.get_long().map_or_else(|| arg.get_id().as_str().to_uppercase(), |long| format!("--{long}"));
+            Err(Args::command().error(
+                ErrorKind::ArgumentConflict,
+                format!("{option} is an option of the capture, give it after the {name} command if it takes it"),
+            ))
+        }
+        None => Ok(()),
     }
+}
 
-    if let Some(log_path) = args.log_path {
+fn main() -> ExitCode {
+    color_eyre::install().unwrap();
+    let matches = Args::command().get_matches();
+    let mut args = Args::from_arg_matches(&matches)
+        .and_then(|args| reject_capture_options(&matches).map(|()| args))
+        .unwrap_or_else(|e| e.exit());
+    if let Some(Command::Completions { shell }) = args.command {
+        print_completions(shell);
+        return ExitCode::SUCCESS;
+    }
+    i18n::init(args.lang.as_deref());
+    let setup = matches!(args.command, Some(Command::Setup));
+    let config = if setup || setup::is_first_run() { setup::run() } else { Ok(()) };
+    // the config can set the log path, so it is read before logging is set up
+    let config = config.and_then(|()| config::apply(&mut args, &matches));
+
+    let logging = tracing_init(&args);
+    if let Err(e) = config.and(logging) {
+        return exit_code(Err(e));
+    }
+    // the formats can be set in the config
+    let output = args.output();
+    if let Some((format, _)) = output.out.iter().find(|(format, _)| !output.format.contains(format)) {
+        return exit_code(Err(ArchiverError::InvalidData(format!(
+            "an output is given for the {format} format, which is not exported. add it to --format"
+        ))));
+    }
+    if setup {
+        return ExitCode::SUCCESS;
+    }
+
+    debug!(?args);
+
+    if !args.no_update_check && !matches!(args.command, Some(Command::SelfUpdate { .. })) {
+        update::spawn_check(args.http_timeouts());
+    }
+
+    if let Some(Command::Interfaces) = args.command {
+        return exit_code(source::list_interfaces());
+    }
+
+    #[cfg(feature = "gui")]
+    if args.live().gui {
+        gui::run(args);
+        return ExitCode::SUCCESS;
+    }
+
+    #[cfg(all(windows, feature = "tray"))]
+    if args.live().tray {
+        return exit_code(tray::run(&args));
+    }
+
+    #[cfg(feature = "tui")]
+    if args.live().tui {
+        return exit_code(tui::run(&args, LOG_TAIL.get().cloned().unwrap_or_default()));
+    }
+
+    install_stop_handlers();
+
+    let result = if let Some(time) = args.live().schedule {
+        run_scheduled(&args, time)
+    } else if args.live().unattended {
+        run_unattended(&args)
+    } else {
+        run(&args)
+    };
+
+    if let Some(log_path) = &args.log_path {
         info!("wrote logs to {}", log_path.display());
     }
 
-    info!("press enter to close");
-    std::io::stdin().read_line(&mut String::new()).unwrap();
+    if !args.live().unattended && !args.quiet {
+        info!("{}", t!("close"));
+        let _ = read_line();
+    }
+
+    exit_code(result)
+}
+
+/// Reports the error the archiver stopped with, if any, and turns it into the exit code
+fn exit_code(result: Result<(), ArchiverError>) -> ExitCode {
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            report_error(&e);
+            ExitCode::from(e.exit_code())
+        }
+    }
+}
+
+fn report_error(error: &ArchiverError) {
+    error!("{error}");
+    if let Some(hint) = error.hint() {
+        warn!("{hint}");
+    }
+}
+
+fn run(args: &Args) -> Result<(), ArchiverError> {
+    // recording raw packets does not need the database
+    match &args.command {
+        Some(Command::Capture { raw: Some(path), .. }) => return source::raw_capture(args, path),
+        Some(Command::SelfUpdate { check }) => return update::run(args.http_timeouts(), *check),
+        Some(Command::Config { command: ConfigCommand::Check }) => return config::check(args),
+        _ => {}
+    }
+
+    let database = Database::new_from_online_with_deadline(&args.database_options(), Duration::from_secs(args.database_timeout))?;
+    match &args.command {
+        Some(Command::Inject { fixture, .. }) => commands::inject(args, database, fixture),
+        Some(Command::Convert { export, only, .. }) => commands::convert(args, database, export, only),
+        Some(Command::Merge { exports, .. }) => commands::merge(args, &database, exports),
+        Some(Command::Verify { export }) => verify::verify(&database, export),
+        Some(Command::Stats { export }) => print::stats(&database, export),
+        Some(Command::Diff { old, new, markdown }) => print::diff(&database, old, new, *markdown),
+        Some(Command::Db) => {
+            print::database(&database);
+            Ok(())
+        }
+        _ => {
+            let captured = capture_and_export(args, database, None, None)?;
+            // stdout is kept for the export or its paths
+            if !args.quiet && !output::is_stdout(&args.output().output) {
+                captured.summary.print();
+            }
+            if args.session().open {
+                reveal(&captured.written);
+            }
+            captured.problem.map_or(Ok(()), Err)
+        }
+    }
+}
+
+/// Waits for the game and captures once, writing the output to a file named after the current date
+fn run_unattended(args: &Args) -> Result<(), ArchiverError> {
+    let mut args = args.clone();
+    let capture = args.capture_mut();
+    capture.live.wait = true;
+    let output = &mut capture.output.output;
+    if output.to_string_lossy().contains("{date}") || output::is_stdout(output) {
+        return run(&args);
+    }
+
+    let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = output.extension().unwrap_or_default().to_string_lossy();
+    let date = Local::now().format("%Y-%m-%d");
+    *output = output.with_file_name(format!("{stem}_{date}.{extension}"));
+
+    run(&args)
+}
+
+/// Captures unattended every day at `time`, until the process is stopped.
+/// A failed capture is reported and retried the next day
+fn run_scheduled(args: &Args, time: NaiveTime) -> ! {
+    loop {
+        let now = Local::now().naive_local();
+        let mut next = now.date().and_time(time);
+        if next <= now {
+            next += chrono::Duration::days(1);
+        }
+
+        info!("next capture at {next}");
+        std::thread::sleep((next - now).to_std().unwrap_or_default());
+
+        STOP_REQUESTED.store(false, Ordering::Relaxed);
+        if let Err(e) = run_unattended(args) {
+            report_error(&e);
+        }
+    }
+}
+
+/// Lets the user stop capturing early with ctrl-c or by pressing enter.
+///
+/// Stdin is read on a background thread for the whole run, lines entered outside of
+/// capturing are sent to [`INPUT`].
+fn install_stop_handlers() {
+    ctrlc::set_handler(|| {
+        if !CAPTURING.load(Ordering::Relaxed) || STOP_REQUESTED.swap(true, Ordering::Relaxed) {
+            std::process::exit(130);
+        }
+        warn!("stopping capture, press ctrl-c again to exit immediately");
+    })
+    .expect("unable to set up ctrl-c handler");
+
+    let (tx, rx) = mpsc::channel();
+
+    std::thread::spawn(move || {
+        let mut line = String::new();
+        while std::io::stdin().read_line(&mut line).is_ok_and(|read| read > 0) {
+            if CAPTURING.load(Ordering::Relaxed) {
+                info!("stopping capture");
+                STOP_REQUESTED.store(true, Ordering::Relaxed);
+            } else if tx.send(line.trim().to_string()).is_err() {
+                break;
+            }
+            line.clear();
+        }
+    });
+
+    let _ = INPUT.set(Mutex::new(rx));
+}
+
+/// Reads a line from stdin, from the thread of [`install_stop_handlers`] if it is running
+fn read_line() -> Option<String> {
+    if let Some(input) = INPUT.get() {
+        return input.lock().unwrap().recv().ok();
+    }
+
+    let mut line = String::new();
+    std::io::stdin().read_line(&mut line).ok().filter(|&read| read > 0)?;
+    Some(line.trim().to_string())
 }
 
-fn tracing_init(args: &Args) {
+fn print_completions(shell: Shell) {
+    let mut command = Args::command();
+    let name = command.get_name().to_string();
+    clap_complete::generate(shell, &mut command, name, &mut std::io::stdout());
+}
+
+/// Sets up logging. A log file that cannot be created is returned as error once logging
+/// to the console works, so it can be reported
+fn tracing_init(args: &Args) -> Result<(), ArchiverError> {
     let env_filter = EnvFilter::builder()
         .with_default_directive(
             match args.verbose {
+                _ if args.quiet => "error",
                 0 => "completionist_archiver=info",
                 1 => "info",
                 2 => "debug",
@@ -81,219 +905,98 @@ fn tracing_init(args: &Args) {
         )
         .from_env_lossy();
 
-    let stdout_log = tracing_subscriber::fmt::layer()
-        .with_ansi(false)
-        .with_filter(env_filter);
+    // stdout is kept for the paths of the exports, or the export itself
+    let writer = if args.quiet || output::is_stdout(&args.output().output) {
+        BoxMakeWriter::new(std::io::stderr)
+    } else {
+        BoxMakeWriter::new(std::io::stdout)
+    };
+    #[cfg(feature = "tui")]
+    let writer = if args.live().tui {
+        let tail = LOG_TAIL.get_or_init(tui::LogTail::default).clone();
+        BoxMakeWriter::new(move || tail.clone())
+    } else {
+        writer
+    };
+
+    let stdout_log = match args.log_format {
+        LogFormat::Text => tracing_subscriber::fmt::layer().with_ansi(false).with_writer(writer).boxed(),
+        LogFormat::Json => tracing_subscriber::fmt::layer().json().with_writer(writer).boxed(),
+    };
+    let stdout_log = stdout_log.with_filter(env_filter);
 
     let subscriber = Registry::default().with(stdout_log);
 
-    let file_log = if let Some(log_path) = &args.log_path {
-        let log_file = File::create(log_path).unwrap();
-        let file_log = tracing_subscriber::fmt::layer()
+    let (log_file, result) = match &args.log_path {
+        Some(log_path) => match File::create(log_path).with_path("create", log_path) {
+            Ok(log_file) => (Some(log_file), Ok(())),
+            Err(e) => (None, Err(e)),
+        },
+        None => (None, Ok(())),
+    };
+    let file_log = log_file.map(|log_file| {
+        tracing_subscriber::fmt::layer()
             .json()
             .with_writer(Mutex::new(log_file))
-            .with_filter(tracing::level_filters::LevelFilter::TRACE);
-        Some(file_log)
-    } else {
-        None
-    };
+            .with_filter(tracing::level_filters::LevelFilter::TRACE)
+    });
 
-    let subscriber = subscriber.with(file_log);
+    let subscriber = subscriber
+        .with(file_log)
+        .with(WARNINGS.get_or_init(WarningThrottle::new).clone());
 
     tracing::subscriber::set_global_default(subscriber).expect("unable to set up logging");
+    result
 }
 
-#[instrument(skip_all)]
-fn file_capture<E>(args: &Args, mut exporter: E, mut sniffer: GameSniffer) -> Option<E::Export>
-where
-    E: Exporter,
-{
-    let mut capture =
-        pcap::Capture::from_file(args.pcap.as_ref().unwrap()).expect("could not read pcap file");
-
-    capture.filter(PACKET_FILTER, false).unwrap();
-
-    let mut invalid = 0;
-
-    info!("capturing");
-    while let Ok(packet) = capture.next_packet() {
-        if let Some(GamePacket::Commands(commands)) = sniffer.receive_packet(packet.data.to_vec()) {
-            if commands.is_empty() {
-                invalid += 1;
-
-                if invalid >= 50 {
-                    error!("received 50 packets that could not be segmented");
-                    warn!("you probably started capturing when you were already in-game");
-                    warn!("the capture needs to start on the main menu screen before hyperdrive");
-                    return None;
-                }
-            } else {
-                invalid = 0.max(invalid - 1);
-                for command in commands {
-                    exporter.read_command(command);
-                }
-
-                if exporter.is_finished() {
-                    info!("retrieved all relevant packets, stop capturing");
-                    break;
-                }
-            }
-        }
+/// Logs how often warnings were suppressed during the capture that just ended
+fn report_suppressed_warnings() {
+    if let Some(warnings) = WARNINGS.get() {
+        warnings.report();
     }
-
-    Some(exporter.export())
 }
 
-#[instrument(skip_all)]
-fn live_capture<E>(args: &Args, mut exporter: E, mut sniffer: GameSniffer) -> Option<E::Export>
-where
-    E: Exporter,
-{
-    let (tx, rx) = mpsc::channel();
-    let mut join_handles = Vec::new();
-
-    // we need to specify a specific network device when using pcap to capture network packets.
-    // to lessen the burden on the user, we instead just capture *all* valid network devices
-    // by capturing each on a different thread and sending the captured packets to a mpsc channel
-    for device in Device::list()
-        .unwrap()
-        .into_iter()
-        .filter(|d| matches!(d.flags.connection_status, ConnectionStatus::Connected))
-        .filter(|d| !d.addresses.is_empty())
-        .filter(|d| !d.flags.is_loopback())
-    {
-        let tx = tx.clone();
-        let handle = std::thread::spawn(move || capture_device(device, tx));
-        join_handles.push(handle);
-    }
-
-    // we clone tx into every thread, but at the end the original tx still remains.
-    // rx.recv will continue to listen while at least one tx is still alive.
-    // we drop the original tx to make sure that there are no tx alive after all threads
-    // have dropped theirs
-    drop(tx);
-
-    let mut invalid = 0;
-    let mut warning_sent = false;
-
-    info!("instructions: go to main menu screen and go into train hyperdrive");
-    info!("listening with a timeout of {} seconds...", args.timeout);
-
-    'recv: loop {
-        match rx.recv_timeout(Duration::from_secs(args.timeout)) {
-            Ok(data) => {
-                match sniffer.receive_packet(data.to_vec()) {
-                    Some(GamePacket::Connection(c)) => {
-                        match c {
-                            ConnectionPacket::HandshakeEstablished => {
-                                info!("detected connection established");
-                            }
-                            ConnectionPacket::Disconnected => {
-                                // program is probably going to exit before this happens
-                                // info!("detected connection disconnected");
-                            }
-                            _ => {}
-                        }
-                    }
-                    Some(GamePacket::Commands(commands)) => {
-                        if commands.is_empty() {
-                            invalid += 1;
-
-                            if invalid >= 25 && !warning_sent {
-                                error!(
-                                    "received a large number of packets that could not be parsed"
-                                );
-                                warn!(
-                                    "you probably started capturing when you were already in-game"
-                                );
-                                warn!("please log out and log back in");
-                                warning_sent = true;
-                            }
-                        } else {
-                            invalid -= 10;
-
-                            for command in commands {
-                                if command.command_id == PlayerLoginScRsp {
-                                    info!("detected login");
-                                }
-
-                                if command.command_id == PlayerLoginFinishScRsp {
-                                    info!("detected login end, assume initialization is finished");
-                                    break 'recv;
-                                }
-
-                                exporter.read_command(command);
-                            }
-
-                            if exporter.is_finished() {
-                                info!("retrieved all relevant packets, stop listening");
-                                break 'recv;
-                            }
-                        }
-                    }
-                    _ => {}
-                }
-            }
-            Err(e) => {
-                warn!(%e);
-                break;
-            }
-        }
-    }
-
-    Some(exporter.export())
+/// Every message warned about since the start, with how often each was
+fn warning_totals() -> Vec<(String, usize)> {
+    WARNINGS.get().map(WarningThrottle::totals).unwrap_or_default()
 }
 
-#[instrument(skip_all, fields(device = device.desc))]
-fn capture_device(device: Device, tx: mpsc::Sender<Vec<u8>>) {
-    let mut capture = pcap::Capture::from_device(device)
-        .unwrap()
-        .immediate_mode(true)
-        .promisc(true)
-        .timeout(0) // explicitly disable timeout??
-        .open()
-        .unwrap();
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    capture.filter(PACKET_FILTER, true).unwrap();
-
-    debug!("listening");
+    fn parse(args: &[&str]) -> Result<Args, clap::Error> {
+        let matches = Args::command().try_get_matches_from(std::iter::once("archiver").chain(args.iter().copied()))?;
+        reject_capture_options(&matches)?;
+        Args::from_arg_matches(&matches)
+    }
 
-    let mut has_captured = false;
+    #[test]
+    fn arguments_are_consistent() {
+        Args::command().debug_assert();
+    }
 
-    loop {
-        match capture.next_packet() {
-            Ok(packet) => {
-                trace!("captured packet");
-                if let Err(e) = tx.send(packet.data.to_vec()) {
-                    debug!("channel closed: {e}");
-                    break;
-                }
-
-                has_captured = true;
-            }
-            Err(e) => {
-                // we only really care about capture errors on devices that we already know
-                // are relevant (have sent packets before) and send those errors on warn level.
-                //
-                // if a capture errors right after initialization or on a device that did
-                // not receive any relevant packets, error is less useful to the user,
-                // so we lower the logging level
-
-                if !has_captured {
-                    debug!(?e);
-                    break;
-                } else if matches!(e, Error::TimeoutExpired) {
-                    // somehow a timeout error can still happen even if i explicitly
-                    // disable the timeout?? why :sob:
-                    debug!(?e);
-                    continue;
-                } else {
-                    warn!(?e);
-                    break;
-                }
-            }
-        }
+    #[test]
+    fn commands_only_take_their_own_options() {
+        assert!(parse(&["diff", "old.json", "new.json", "--timeout", "5"]).is_err());
+        assert!(parse(&["verify", "export.json", "--require", "books"]).is_err());
+        assert!(parse(&["stats", "export.json", "--quiet-period", "60"]).is_err());
+        assert!(parse(&["--timeout", "5", "diff", "old.json", "new.json"]).is_err());
+        assert!(parse(&["replay", "recording.pcapng", "--interface", "eth0"]).is_err());
+
+        let args = parse(&["replay", "recording.pcapng", "out.json", "--require", "books", "-q"]).unwrap();
+        assert_eq!(args.output().output, PathBuf::from("out.json"));
+        assert_eq!(args.session().require, ["books"]);
+        assert!(args.quiet);
     }
 
-    debug!("stop listening");
+    #[test]
+    fn captures_without_a_command() {
+        let args = parse(&["out.json", "--timeout", "5", "--require", "books"]).unwrap();
+        assert_eq!(args.live().timeout, 5);
+        assert_eq!(args.output().output, PathBuf::from("out.json"));
+
+        let args = parse(&["capture", "--timeout", "5"]).unwrap();
+        assert_eq!(args.live().timeout, 5);
+    }
 }
@@ -0,0 +1,728 @@
+//! Data of an account collected from captured commands, independent of any export format.
+//!
+//! The [`Collector`] parses the game commands into an [`Account`], carrying everything known
+//! about its items. Export formats are conversions of an [`Account`], see
+//! [`export::format::Format`], so adding a format does not require parsing packets again.
+//!
+//! [`export::format::Format`]: crate::export::format::Format
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::num::NonZeroUsize;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use chrono::{Local, NaiveDate};
+use reliquary::network::GameCommand;
+use reliquary::network::gen::proto::GetBagScRsp::GetBagScRsp;
+use reliquary::network::gen::proto::GetPlayerDetailInfoScRsp::GetPlayerDetailInfoScRsp;
+use reliquary::network::gen::proto::GetQuestDataScRsp::GetQuestDataScRsp;
+use reliquary::network::gen::proto::Material::Material;
+use reliquary::network::gen::proto::PlayerGetTokenScRsp::PlayerGetTokenScRsp;
+use reliquary::network::gen::proto::PlayerLoginScRsp::PlayerLoginScRsp;
+use reliquary::network::gen::proto::PlayerSyncScNotify::PlayerSyncScNotify;
+use reliquary::network::gen::proto::Quest::Quest;
+use reliquary::network::gen::proto::QuestStatus::QuestStatus::{self, QUEST_CLOSE, QUEST_DOING, QUEST_FINISH};
+use reliquary::network::gen::proto::TakeQuestRewardScRsp::TakeQuestRewardScRsp;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info, warn};
+
+use crate::database::Database;
+use crate::export::command_ids::CommandIds;
+use crate::export::field_mappings::FieldMapping;
+use crate::export::router::{CommandRouter, Dispatch, Parsed};
+use crate::export::ExportObserver;
+
+/// Number of commands in a batch from which on they are parsed on several threads
+const PARALLEL_PARSE_MIN: usize = 64;
+/// How long after the last inventory or quest data packet it counts as complete. Large inventories
+/// and the quest data of accounts with many achievements are sent in several packets, which
+/// arrive right after each other
+const SETTLE_TIME: Duration = Duration::from_secs(3);
+
+/// First digit of the 9 digit uids of every server: 1 and 2 for the official chinese servers,
+/// 5 for the bilibili server, and 6 to 9 for America, Europe, Asia and TW/HK/MO
+const UID_REGIONS: [u32; 7] = [1, 2, 5, 6, 7, 8, 9];
+
+/// Whether `uid` looks like the uid of an account, 9 digits starting with the digit of a server,
+/// or 10 digits as given out by servers that ran out of 9 digit uids
+pub fn is_plausible_uid(uid: u32) -> bool {
+    match uid {
+        100_000_000..=999_999_999 => UID_REGIONS.contains(&(uid / 100_000_000)),
+        1_000_000_000.. => true,
+        _ => false,
+    }
+}
+
+/// Everything collected about the account logged in while capturing
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Account {
+    pub uid: Option<u32>,
+    /// Completed achievements by id
+    pub achievements: BTreeMap<u32, Achievement>,
+    /// Obtained books by id
+    pub books: BTreeMap<u32, Book>,
+    /// Ids that look like items, but are missing from the database
+    #[serde(default)]
+    pub unknown: UnknownIds,
+    /// Number of completed achievements the game shows on the profile of the account,
+    /// if the profile was opened while capturing
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reported_achievements: Option<u32>,
+}
+
+impl Account {
+    /// Completed achievements whose reward has not been claimed yet
+    pub fn unclaimed(&self) -> impl Iterator<Item = &Achievement> {
+        self.achievements.values().filter(|achievement| !achievement.reward_taken)
+    }
+
+    /// Stellar jade rewarded by the completed achievements
+    pub fn earned_jade(&self) -> u32 {
+        self.achievements.values().map(|achievement| achievement.jade).sum()
+    }
+}
+
+/// Ids seen in packets that share the leading digits of the known ids of a category, but are
+/// missing from the database. Usually items that were added to the game after the game data was loaded
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct UnknownIds {
+    pub achievements: BTreeSet<u32>,
+    pub books: BTreeSet<u32>,
+}
+
+impl UnknownIds {
+    pub fn is_empty(&self) -> bool {
+        self.achievements.is_empty() && self.books.is_empty()
+    }
+}
+
+/// A completed achievement
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Achievement {
+    pub id: u32,
+    /// Title of the achievement, if names were loaded into the database
+    pub name: Option<String>,
+    /// Amount of stellar jade rewarded for completing the achievement
+    pub jade: u32,
+    /// Whether the reward of the achievement was claimed
+    pub reward_taken: bool,
+    /// Date the achievement was first captured on
+    pub first_seen: NaiveDate,
+}
+
+/// An obtained book
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Book {
+    pub id: u32,
+    /// Title of the book, if names were loaded into the database
+    pub name: Option<String>,
+    /// Date the book was first captured on
+    pub first_seen: NaiveDate,
+}
+
+/// Which statuses of quests count as completed achievements
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompletedStatuses {
+    /// Achievements whose progress reached its target, but that the game did not mark as completed yet
+    pub in_progress: bool,
+    /// Completed achievements whose reward has not been claimed yet
+    pub unclaimed: bool,
+    /// Completed achievements whose reward was claimed
+    pub claimed: bool,
+}
+
+impl CompletedStatuses {
+    pub fn contains(&self, status: QuestStatus) -> bool {
+        match status {
+            QUEST_DOING => self.in_progress,
+            QUEST_FINISH => self.unclaimed,
+            QUEST_CLOSE => self.claimed,
+            _ => false,
+        }
+    }
+}
+
+impl Default for CompletedStatuses {
+    /// Achievements that were completed, whether their reward was claimed or not
+    fn default() -> Self {
+        CompletedStatuses {
+            in_progress: false,
+            unclaimed: true,
+            claimed: true,
+        }
+    }
+}
+
+/// Parses game commands into the [`Account`] logged in while capturing
+pub struct Collector {
+    database: Arc<Database>,
+    account: Account,
+    /// Accounts logged in before the current one
+    switched: Vec<Account>,
+    observers: Vec<Box<dyn ExportObserver>>,
+    router: Arc<CommandRouter<Collector>>,
+    /// Ids and field numbers the router was built with, see [`Collector::set_command_ids`]
+    command_ids: CommandIds,
+    field_mappings: Vec<FieldMapping>,
+    completed: CompletedStatuses,
+    parse_failures: u64,
+    /// Nickname of the account logged in, to recognize its profile by
+    nickname: Option<String>,
+    /// Uids of the profiles seen by nickname, see [`Collector::add_profile`]
+    profiles: HashMap<String, u32>,
+    /// Uid exported under whatever uid is detected, see [`Collector::set_uid_override`]
+    uid_override: Option<u32>,
+    /// When the last inventory packet of the account logged in was read
+    inventory_read_at: Option<Instant>,
+    /// When the last quest data packet of the account logged in was read
+    quests_read_at: Option<Instant>,
+}
+
+impl Collector {
+    pub fn new(database: impl Into<Arc<Database>>) -> Collector {
+        Collector {
+            database: database.into(),
+            account: Account::default(),
+            switched: vec![],
+            observers: vec![],
+            router: Arc::new(router(CommandIds::default(), vec![])),
+            command_ids: CommandIds::default(),
+            field_mappings: vec![],
+            completed: CompletedStatuses::default(),
+            parse_failures: 0,
+            nickname: None,
+            profiles: HashMap::new(),
+            uid_override: None,
+            inventory_read_at: None,
+            quests_read_at: None,
+        }
+    }
+
+    /// Recognizes commands by these ids instead of the ids of the released game
+    pub fn set_command_ids(&mut self, command_ids: CommandIds) {
+        self.command_ids = command_ids;
+        self.router = Arc::new(router(self.command_ids, self.field_mappings.clone()));
+    }
+
+    /// Parses commands that do not match the released protos with the field numbers of other
+    /// game versions, see [`FieldMapping`]
+    pub fn set_field_mappings(&mut self, field_mappings: Vec<FieldMapping>) {
+        self.field_mappings = field_mappings;
+        self.router = Arc::new(router(self.command_ids, self.field_mappings.clone()));
+    }
+
+    /// Counts achievements with these statuses as completed, instead of all completed ones
+    pub fn set_completed_statuses(&mut self, completed: CompletedStatuses) {
+        self.completed = completed;
+    }
+
+    /// Notifies `observer` of every newly detected uid, achievement and book
+    pub fn add_observer(&mut self, observer: impl ExportObserver + 'static) {
+        self.observers.push(Box::new(observer));
+    }
+
+    pub fn database(&self) -> &Database {
+        &self.database
+    }
+
+    /// Data of the account logged in, collected so far
+    pub fn account(&self) -> &Account {
+        &self.account
+    }
+
+    /// Whether the quest data of the account logged in was read, even if nothing was completed
+    pub fn has_read_quests(&self) -> bool {
+        self.quests_read_at.is_some()
+    }
+
+    /// Whether an inventory packet of the account logged in was read, even without any books
+    pub fn has_read_inventory(&self) -> bool {
+        self.inventory_read_at.is_some()
+    }
+
+    /// Whether the whole inventory was read, which takes several packets for large inventories.
+    /// Books synced while playing do not need the inventory, so restored accounts count as complete
+    pub fn is_inventory_complete(&self) -> bool {
+        self.inventory_read_at.map_or(true, |read_at| read_at.elapsed() >= SETTLE_TIME)
+    }
+
+    /// Whether the whole quest data was read, which takes several packets for accounts with many
+    /// achievements. Restored accounts count as complete like for [`Collector::is_inventory_complete`]
+    pub fn is_quest_data_complete(&self) -> bool {
+        self.quests_read_at.map_or(true, |read_at| read_at.elapsed() >= SETTLE_TIME)
+    }
+
+    /// Number of commands that could not be parsed completely
+    pub fn parse_failures(&self) -> u64 {
+        self.parse_failures
+    }
+
+    /// Replaces the data of the account logged in, without notifying the observers
+    pub fn restore(&mut self, account: Account) {
+        self.account = account;
+    }
+
+    /// Accounts that were switched away from while capturing, oldest first
+    pub fn take_switched_accounts(&mut self) -> Vec<Account> {
+        std::mem::take(&mut self.switched)
+    }
+
+    pub fn read_command(&mut self, command: &GameCommand) {
+        // the router is shared with the handlers it calls
+        let router = Arc::clone(&self.router);
+        let dispatch = router.dispatch(self, command);
+        self.count_failure(dispatch);
+    }
+
+    /// Reads `commands` in order. Large batches are parsed on several threads at once,
+    /// as parsing takes most of the time, and passed to the handlers one by one afterwards
+    pub fn read_commands(&mut self, commands: &[GameCommand]) {
+        let router = Arc::clone(&self.router);
+        let threads = std::thread::available_parallelism().map_or(1, NonZeroUsize::get);
+
+        let parsed: Vec<Parsed<Collector>> = if commands.len() < PARALLEL_PARSE_MIN || threads == 1 {
+            commands.iter().map(|command| router.parse(command)).collect()
+        } else {
+            let router = &*router;
+            std::thread::scope(|scope| {
+                let handles: Vec<_> = commands.chunks(commands.len().div_ceil(threads))
+                    .map(|chunk| scope.spawn(move || chunk.iter().map(|command| router.parse(command)).collect::<Vec<_>>()))
+                    .collect();
+                handles.into_iter().flat_map(|handle| handle.join().unwrap()).collect()
+            })
+        };
+
+        for parsed in parsed {
+            let dispatch = parsed.apply(self);
+            self.count_failure(dispatch);
+        }
+    }
+
+    fn count_failure(&mut self, dispatch: Dispatch) {
+        if matches!(dispatch, Dispatch::Salvaged | Dispatch::ParseFailed) {
+            self.parse_failures += 1;
+        }
+    }
+
+    /// Exports under `uid` whatever uid is detected, for captures that missed the login.
+    /// Switching accounts is not noticed anymore
+    pub fn set_uid_override(&mut self, uid: u32) {
+        self.uid_override = Some(uid);
+        self.set_uid(uid);
+    }
+
+    pub fn set_uid(&mut self, uid: u32) {
+        // sent before the account is known, e.g. by a failed login
+        if uid == 0 {
+            warn!("ignoring uid 0, the uid is taken from the next login");
+            return;
+        }
+        if !is_plausible_uid(uid) {
+            warn!(uid, "detected uid does not look like the uid of an account, check the uid of the export");
+        }
+        let uid = match self.uid_override {
+            Some(forced) if forced != uid => {
+                debug!(detected = uid, forced, "ignoring detected uid");
+                forced
+            }
+            _ => uid,
+        };
+        if self.account.uid.is_some_and(|old| old != uid) {
+            warn!(old = self.account.uid, new = uid, "detected login with a different uid, exporting it separately");
+            self.switched.push(std::mem::take(&mut self.account));
+            self.nickname = None;
+            self.inventory_read_at = None;
+            self.quests_read_at = None;
+        }
+        if self.account.uid != Some(uid) {
+            for observer in &mut self.observers {
+                observer.on_uid(uid);
+            }
+        }
+        self.account.uid = Some(uid);
+    }
+
+    /// Remembers the nickname of the account logged in, which the game sends on login and
+    /// syncs when it changes
+    pub fn set_nickname(&mut self, nickname: String) {
+        if nickname.is_empty() || self.nickname.as_ref() == Some(&nickname) {
+            return;
+        }
+        self.nickname = Some(nickname);
+        self.infer_uid();
+    }
+
+    /// Remembers the uid of a profile that was looked at, which may be the one of the account
+    /// logged in or the one of another player. The achievement count of the own profile is
+    /// kept to compare the collected achievements with
+    pub fn add_profile(&mut self, uid: u32, nickname: String, achievement_count: u32) {
+        if uid == 0 {
+            return;
+        }
+        // profiles of other players are opened as well, e.g. of friends
+        let is_own = self.account.uid == Some(uid) || self.nickname.as_ref() == Some(&nickname);
+        self.profiles.insert(nickname, uid);
+        self.infer_uid();
+
+        if !is_own || achievement_count == 0 {
+            return;
+        }
+        // the profile counts the completed achievements, which is only what is collected with
+        // the default statuses
+        if self.completed != CompletedStatuses::default() {
+            debug!(achievement_count, "not comparing with the achievement count of the profile, other statuses are collected");
+            return;
+        }
+        debug!(achievement_count, "detected achievement count of the profile");
+        self.account.reported_achievements = Some(achievement_count);
+    }
+
+    /// Takes the uid of the profile with the nickname of the account logged in, in case the
+    /// token with the uid was missed because capturing started after logging in
+    fn infer_uid(&mut self) {
+        if self.account.uid.is_some() {
+            return;
+        }
+        let Some(&uid) = self.nickname.as_ref().and_then(|nickname| self.profiles.get(nickname)) else {
+            return;
+        };
+
+        info!(uid, "login was not captured, took the uid from the profile of the player");
+        self.set_uid(uid);
+    }
+
+    /// Adds the books of an inventory packet. Large inventories are split across several packets,
+    /// so the books of every packet are added to the ones found before
+    pub fn add_inventory(&mut self, bag: GetBagScRsp) {
+        let num = self.add_books(&bag.material_list);
+        let total = self.account.books.len();
+        if self.inventory_read_at.is_some_and(|read_at| read_at.elapsed() < SETTLE_TIME) {
+            info!(num, total, "found books in the next part of the inventory");
+        } else {
+            info!(num, "found books");
+        }
+        self.inventory_read_at = Some(Instant::now());
+    }
+
+    pub fn add_achievements(&mut self, quest: GetQuestDataScRsp) {
+        // achievements are keyed by id, so the parts of split or re-sent quest data are merged
+        let num = self.add_quests(&quest.quest_list);
+        let total = self.account.achievements.len();
+        if self.quests_read_at.is_some_and(|read_at| read_at.elapsed() < SETTLE_TIME) {
+            info!(num, total, "found achievements in the next part of the quest data");
+        } else {
+            info!(num, "found achievements");
+        }
+        self.quests_read_at = Some(Instant::now());
+    }
+
+    /// Adds the items obtained or completed while capturing, which the game syncs as they change
+    pub fn add_sync(&mut self, sync: PlayerSyncScNotify) {
+        if let Some(basic_info) = sync.basic_info.as_ref() {
+            self.set_nickname(basic_info.nickname.clone());
+        }
+
+        let (books, achievements) = (self.account.books.len(), self.account.achievements.len());
+        self.add_books(&sync.material_list);
+        self.add_quests(&sync.quest_list);
+
+        let books = self.account.books.len() - books;
+        let achievements = self.account.achievements.len() - achievements;
+        if books > 0 || achievements > 0 {
+            info!(books, achievements, "found new items");
+        }
+    }
+
+    /// Marks the rewards of achievements as claimed, completing them if they were not yet.
+    /// Without [`CompletedStatuses::claimed`], the achievements do not count anymore instead
+    pub fn take_rewards(&mut self, rewards: TakeQuestRewardScRsp) {
+        let mut num = 0;
+        for &id in &rewards.succ_quest_id_list {
+            if !self.database.has_achievement(id) {
+                continue;
+            }
+
+            if self.completed.claimed {
+                self.add_achievement(id, true);
+            } else {
+                self.account.achievements.remove(&id);
+            }
+            num += 1;
+        }
+
+        if num > 0 {
+            info!(num, "claimed achievement rewards");
+        }
+    }
+
+    /// Adds the books among `materials`, returning how many books there are
+    fn add_books(&mut self, materials: &[Material]) -> usize {
+        let books: Vec<u32> = materials.iter()
+            .filter_map(|r| proto_book(&self.database, r))
+            .collect();
+
+        // other materials have ids with other leading digits
+        if let Some(prefix) = self.database.book_id_prefix() {
+            let unknown = materials.iter()
+                .map(|material| material.tid)
+                .filter(|id| prefix.matches(*id) && !self.database.has_book(*id));
+            self.account.unknown.books.extend(unknown);
+        }
+
+        let num = books.len();
+        for id in books {
+            // the same packet is sent again on every login
+            if self.account.books.contains_key(&id) {
+                continue;
+            }
+
+            let name = self.database.book(id).and_then(|b| b.name.clone());
+            for observer in &mut self.observers {
+                observer.on_book(id, name.as_deref());
+            }
+            self.account.books.insert(id, Book {
+                id,
+                name,
+                first_seen: Local::now().date_naive(),
+            });
+        }
+        num
+    }
+
+    /// Adds the completed achievements among `quests`, returning how many there are
+    fn add_quests(&mut self, quests: &[Quest]) -> usize {
+        let achievements: Vec<(u32, bool)> = quests.iter()
+            .filter_map(|r| proto_achievement(&self.database, self.completed, r))
+            .collect();
+
+        // achievements whose status does not count anymore, e.g. once their reward was claimed
+        // while only unclaimed achievements count
+        let uncounted: Vec<u32> = quests.iter()
+            .filter(|quest| proto_achievement(&self.database, self.completed, quest).is_none())
+            .map(|quest| quest.id)
+            .collect();
+        for id in uncounted {
+            self.account.achievements.remove(&id);
+        }
+
+        // other quests have ids with other leading digits. their target is not known,
+        // so quests in progress are left out
+        if let Some(prefix) = self.database.achievement_id_prefix() {
+            let unknown = quests.iter()
+                .filter(|quest| quest_status(quest).is_some_and(|status| status != QUEST_DOING && self.completed.contains(status)))
+                .map(|quest| quest.id)
+                .filter(|id| prefix.matches(*id) && !self.database.has_achievement(*id));
+            self.account.unknown.achievements.extend(unknown);
+        }
+
+        let num = achievements.len();
+        for (id, reward_taken) in achievements {
+            self.add_achievement(id, reward_taken);
+        }
+        num
+    }
+
+    fn add_achievement(&mut self, id: u32, reward_taken: bool) {
+        // the same packet is sent again on every login, only the status of
+        // an achievement changes once its reward is claimed
+        if let Some(achievement) = self.account.achievements.get_mut(&id) {
+            achievement.reward_taken = reward_taken;
+            return;
+        }
+
+        let name = self.database.achievement(id).and_then(|a| a.name.clone());
+        for observer in &mut self.observers {
+            observer.on_achievement(id, name.as_deref());
+        }
+        self.account.achievements.insert(id, Achievement {
+            id,
+            name,
+            jade: self.database.achievement_jade(id),
+            reward_taken,
+            first_seen: Local::now().date_naive(),
+        });
+    }
+}
+
+/// Routes the commands the collector reads to its methods
+fn router(ids: CommandIds, field_mappings: Vec<FieldMapping>) -> CommandRouter<Collector> {
+    CommandRouter::new()
+        .with_field_mappings(field_mappings)
+        .route(ids.player_get_token_sc_rsp, |collector: &mut Collector, cmd: PlayerGetTokenScRsp| {
+            debug!("detected uid");
+            collector.set_uid(cmd.uid);
+        })
+        .route(ids.player_login_sc_rsp, |collector: &mut Collector, cmd: PlayerLoginScRsp| {
+            if let Some(basic_info) = cmd.basic_info.into_option() {
+                debug!("detected nickname");
+                collector.set_nickname(basic_info.nickname);
+            }
+        })
+        .route(ids.get_player_detail_info_sc_rsp, |collector: &mut Collector, cmd: GetPlayerDetailInfoScRsp| {
+            if let Some(detail_info) = cmd.detail_info.into_option() {
+                debug!("detected player profile");
+                let achievement_count = detail_info.record_info.as_ref().map_or(0, |record_info| record_info.achievement_count);
+                collector.add_profile(detail_info.uid, detail_info.nickname, achievement_count);
+            }
+        })
+        .route(ids.get_bag_sc_rsp, |collector: &mut Collector, cmd: GetBagScRsp| {
+            debug!("detected inventory packet");
+            collector.add_inventory(cmd);
+        })
+        .route(ids.get_quest_data_sc_rsp, |collector: &mut Collector, cmd: GetQuestDataScRsp| {
+            debug!("detected quest packet");
+            collector.add_achievements(cmd);
+        })
+        .route(ids.player_sync_sc_notify, |collector: &mut Collector, cmd: PlayerSyncScNotify| {
+            debug!("detected sync packet");
+            collector.add_sync(cmd);
+        })
+        .route(ids.take_quest_reward_sc_rsp, |collector: &mut Collector, cmd: TakeQuestRewardScRsp| {
+            debug!("detected quest reward packet");
+            collector.take_rewards(cmd);
+        })
+}
+
+/// Id of the achievement and whether its reward was claimed, if the quest is an achievement
+/// with one of the `completed` statuses. Achievements in progress only count once their
+/// progress reached their target
+#[tracing::instrument(name = "achievement", skip_all, fields(id = proto.id))]
+fn proto_achievement(db: &Database, completed: CompletedStatuses, proto: &Quest) -> Option<(u32, bool)> {
+    let status = quest_status(proto)?;
+    let achievement = db.achievement(proto.id)?;
+    let full = status != QUEST_DOING || achievement.target.is_some_and(|target| proto.progress >= target);
+    if completed.contains(status) && full {
+        Some((proto.id, status == QUEST_CLOSE))
+    }
+    else {
+        None
+    }
+}
+
+/// Status of a quest, `None` if it is missing or unknown, e.g. a status added in a newer game
+/// version, which counts as not completed
+fn quest_status(proto: &Quest) -> Option<QuestStatus> {
+    match proto.status.enum_value() {
+        Ok(status) => Some(status),
+        Err(value) => {
+            debug!(id = proto.id, value, "quest has an unknown status, counting it as not completed");
+            None
+        }
+    }
+}
+
+/// Id of the book, if the material is a book
+#[tracing::instrument(name = "book", skip_all, fields(id = proto.tid))]
+fn proto_book(db: &Database, proto: &Material) -> Option<u32> {
+    if db.has_book(proto.tid) {
+        Some(proto.tid)
+    }
+    else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use protobuf::EnumOrUnknown;
+
+    use super::*;
+    use crate::database::AchievementInfo;
+
+    fn collector() -> Collector {
+        let achievement = |id| AchievementInfo {
+            id,
+            jade: 5,
+            target: Some(10),
+            series: None,
+            name: None,
+        };
+        let mut collector = Collector::new(Database::new(vec![achievement(4010101), achievement(4010102)], vec![], HashMap::new()));
+        collector.set_uid(100000001);
+        collector
+    }
+
+    fn quest(id: u32, status: QuestStatus, progress: u32) -> Quest {
+        Quest {
+            id,
+            status: EnumOrUnknown::new(status),
+            progress,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn accepts_uids_of_every_server() {
+        for uid in [100000001, 200000001, 500000001, 600000001, 700000001, 800000001, 900000001, 1000000001, u32::MAX] {
+            assert!(is_plausible_uid(uid), "{uid}");
+        }
+    }
+
+    #[test]
+    fn rejects_uids_of_no_server() {
+        for uid in [0, 1, 99999999, 300000001, 400000001] {
+            assert!(!is_plausible_uid(uid), "{uid}");
+        }
+    }
+
+    #[test]
+    fn ignores_uid_0() {
+        let mut collector = collector();
+        collector.set_uid(0);
+        assert_eq!(collector.account().uid, Some(100000001));
+        assert!(collector.take_switched_accounts().is_empty());
+    }
+
+    #[test]
+    fn takes_achievement_count_of_own_profile() {
+        let mut collector = collector();
+        collector.add_profile(100000002, "Friend".to_string(), 10);
+        assert_eq!(collector.account().reported_achievements, None);
+
+        collector.add_profile(100000001, "Trailblazer".to_string(), 20);
+        assert_eq!(collector.account().reported_achievements, Some(20));
+    }
+
+    #[test]
+    fn ignores_achievement_count_with_other_statuses() {
+        let mut collector = collector();
+        collector.set_completed_statuses(CompletedStatuses {
+            in_progress: true,
+            ..CompletedStatuses::default()
+        });
+        collector.add_profile(100000001, "Trailblazer".to_string(), 20);
+        assert_eq!(collector.account().reported_achievements, None);
+    }
+
+    #[test]
+    fn counts_achievements_in_progress_once_their_progress_is_full() {
+        let mut collector = collector();
+        collector.set_completed_statuses(CompletedStatuses {
+            in_progress: true,
+            ..CompletedStatuses::default()
+        });
+
+        collector.add_quests(&[quest(4010101, QUEST_DOING, 3), quest(4010102, QUEST_DOING, 10)]);
+        assert_eq!(collector.account().achievements.keys().copied().collect::<Vec<_>>(), [4010102]);
+        assert!(!collector.account().achievements[&4010102].reward_taken);
+    }
+
+    #[test]
+    fn drops_claimed_achievements_if_only_unclaimed_ones_count() {
+        let mut collector = collector();
+        collector.set_completed_statuses(CompletedStatuses {
+            claimed: false,
+            ..CompletedStatuses::default()
+        });
+
+        collector.add_quests(&[quest(4010101, QUEST_FINISH, 10), quest(4010102, QUEST_FINISH, 10)]);
+        collector.take_rewards(TakeQuestRewardScRsp {
+            succ_quest_id_list: vec![4010101],
+            ..Default::default()
+        });
+        assert_eq!(collector.account().achievements.keys().copied().collect::<Vec<_>>(), [4010102]);
+
+        // claimed while not capturing
+        collector.add_quests(&[quest(4010102, QUEST_CLOSE, 10)]);
+        assert!(collector.account().achievements.is_empty());
+    }
+}
@@ -0,0 +1,52 @@
+//! Desktop notifications once the export was written or the capture stalls, for users that
+//! switched to the game window and do not see the log.
+use std::path::PathBuf;
+
+use notify_rust::Notification;
+use tracing::warn;
+
+use completionist_archiver::error::ArchiverError;
+use completionist_archiver::export::Progress;
+use completionist_archiver::server::{CaptureState, SharedCaptureState};
+
+const TITLE: &str = "completionist archiver";
+
+/// Notifies about the files that were written once capturing stopped
+pub fn exported(written: &Result<Vec<PathBuf>, ArchiverError>, state: &SharedCaptureState) {
+    let body = match written.as_deref() {
+        Err(e) => format!("nothing was exported: {e}"),
+        Ok([]) => "nothing was exported, see the log for details".to_string(),
+        Ok([path]) => format!("wrote export to {}", path.display()),
+        Ok(paths) => format!("wrote {} exports, one per account", paths.len()),
+    };
+    let completion = completion(&state.lock().unwrap().progress);
+
+    show(&format!("{body}\n{completion}% of the data was collected"));
+}
+
+/// Notifies that nothing new was collected for a while, see [`crate::stall`]
+pub fn stalled(state: &CaptureState) {
+    let missing: Vec<&str> = state.progress.iter()
+        .filter(|progress| !progress.collected)
+        .map(|progress| progress.name)
+        .collect();
+    let missing = if missing.is_empty() { "nothing".to_string() } else { missing.join(", ") };
+    show(&format!(
+        "nothing new was collected for a minute, {}% of the data was collected. missing: {missing}",
+        completion(&state.progress)
+    ));
+}
+
+/// Percentage of the categories that were collected
+fn completion(progress: &[Progress]) -> usize {
+    if progress.is_empty() {
+        return 0;
+    }
+    progress.iter().filter(|progress| progress.collected).count() * 100 / progress.len()
+}
+
+fn show(body: &str) {
+    if let Err(e) = Notification::new().summary(TITLE).body(body).show() {
+        warn!(%e, "could not show notification");
+    }
+}
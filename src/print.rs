@@ -0,0 +1,131 @@
+//! What the commands that only read exports and the game data print to the terminal,
+//! in the language of the user.
+use std::ops::RangeInclusive;
+use std::path::Path;
+
+use completionist_archiver::database::Database;
+use completionist_archiver::error::ArchiverError;
+use completionist_archiver::export::fribbels::{self, Export};
+use completionist_archiver::export::stats::{ExportStats, Found, SeriesFound};
+
+use crate::i18n::{self, t};
+
+/// Lists the items that were added and removed between two exports, as Markdown if set
+pub fn diff(database: &Database, old: &Path, new: &Path, markdown: bool) -> Result<(), ArchiverError> {
+    let diff = Export::load(old)?.diff(&Export::load(new)?);
+    if diff.is_empty() {
+        println!("{}", t!("diff-unchanged"));
+        return Ok(());
+    }
+
+    let achievement = |id: u32| {
+        let name = database.achievement(id).and_then(|a| a.name.as_deref());
+        match database.achievement_jade(id) {
+            0 => item_label(id, name),
+            jade => format!("{} ({} {})", item_label(id, name), i18n::number(jade), t!("jade")),
+        }
+    };
+    let book = |id: u32| item_label(id, database.book(id).and_then(|b| b.name.as_deref()));
+    let earned = diff.earned_jade(database);
+
+    let categories: [(String, &fribbels::ItemsDiff, &dyn Fn(u32) -> String); 2] = [
+        (t!("achievements"), &diff.achievements, &achievement),
+        (t!("books"), &diff.books, &book),
+    ];
+    for (index, (category, items, label)) in categories.into_iter().enumerate() {
+        let heading = t!("diff-category", category = category, added = items.added.len(), removed = items.removed.len());
+        if markdown {
+            if index > 0 {
+                println!();
+            }
+            println!("### {heading}");
+            println!();
+            if index == 0 && earned > 0 {
+                println!("{}", t!("diff-earned", jade = i18n::number(earned)));
+                println!();
+            }
+            for &id in &items.added {
+                println!("- {}", label(id));
+            }
+            for &id in &items.removed {
+                println!("- ~~{}~~", label(id));
+            }
+        } else {
+            println!("{heading}");
+            if index == 0 && earned > 0 {
+                println!("    {}", t!("diff-earned", jade = i18n::number(earned)));
+            }
+            for &id in &items.added {
+                println!("    + {}", label(id));
+            }
+            for &id in &items.removed {
+                println!("    - {}", label(id));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Name of an item followed by its id, or only the id if the game data has no name for it
+fn item_label(id: u32, name: Option<&str>) -> String {
+    match name {
+        Some(name) => format!("{name} [{id}]"),
+        None => id.to_string(),
+    }
+}
+
+pub fn database(database: &Database) {
+    let range = |range: Option<RangeInclusive<u32>>| range.map_or("-".to_string(), |range| format!("{} to {}", range.start(), range.end()));
+
+    println!("{}: {}", t!("achievements"), i18n::number(database.achievement_ids().len() as u64));
+    println!("    {}: {}", t!("ids"), range(database.achievement_id_range()));
+    println!("    {}: {}", t!("jade"), i18n::number(database.total_achievement_jade()));
+    println!("{}: {}", t!("books"), i18n::number(database.book_ids().len() as u64));
+    println!("    {}: {}", t!("ids"), range(database.book_id_range()));
+    println!("{}: {}", t!("keys"), i18n::number(database.keys().len() as u64));
+}
+
+/// Prints the completion of an export overall and by series
+pub fn stats(database: &Database, path: &Path) -> Result<(), ArchiverError> {
+    let stats = ExportStats::new(&Export::load(path)?, database);
+
+    println!("{}: {}", t!("uid"), stats.uid.map_or("-".to_string(), |uid| uid.to_string()));
+    println!("{}: {}", t!("achievements"), found(stats.achievements));
+    println!("{}: {}", t!("books"), found(stats.books));
+    println!("{}: {}/{}", t!("jade"), i18n::number(stats.earned_jade), i18n::number(stats.total_jade));
+    println!(
+        "{}: {} ({})",
+        t!("unclaimed-jade"),
+        i18n::number(stats.unclaimed_jade),
+        t!("stats-unclaimed", count = stats.unclaimed)
+    );
+
+    println!();
+    println!("{}", t!("stats-achievement-series"));
+    for series in &stats.achievement_series {
+        println!("    {:<40}{}", series_label(series), found(series.found));
+    }
+
+    println!();
+    println!("{}", t!("stats-book-series"));
+    for series in &stats.book_series {
+        println!("    {:<40}{}", series_label(series), found(series.found));
+    }
+
+    Ok(())
+}
+
+/// Name of a series, or its id if the name is not known
+fn series_label(series: &SeriesFound) -> String {
+    match (series.series, &series.name) {
+        (_, Some(name)) => name.clone(),
+        (Some(series), None) => format!("#{series}"),
+        (None, None) => t!("stats-other"),
+    }
+}
+
+/// Number of found items out of the known ones, e.g. `12/40 (30%)`
+fn found(found: Found) -> String {
+    format!("{}/{} ({})", i18n::number(found.found as u64), i18n::number(found.known as u64), i18n::percent(found.found, found.known))
+}
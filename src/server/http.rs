@@ -0,0 +1,59 @@
+//! Small HTTP API serving the [`CaptureState`] of a running capture.
+//!
+//! - `GET /status` returns the progress of the capture
+//! - `GET /export` returns the export of the data collected so far
+//! - `GET /metrics` returns statistics of the capture for Prometheus
+use std::error::Error;
+use std::net::SocketAddr;
+
+use tiny_http::{Header, Method, Request, Response, Server, StatusCode};
+use tracing::{debug, info, instrument};
+
+use crate::server::{metrics, SharedCaptureState};
+
+/// Starts the server in the background, serving `state` until the process exits
+#[instrument(name = "http", skip(state))]
+pub fn spawn(address: SocketAddr, state: SharedCaptureState) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let server = Server::http(address)?;
+
+    std::thread::spawn(move || {
+        for request in server.incoming_requests() {
+            let response = respond(&request, &state);
+            if let Err(e) = request.respond(response) {
+                debug!(%e, "could not send response");
+            }
+        }
+    });
+
+    info!("listening on http://{address}");
+
+    Ok(())
+}
+
+fn respond(request: &Request, state: &SharedCaptureState) -> Response<std::io::Cursor<Vec<u8>>> {
+    if request.method() != &Method::Get {
+        return Response::from_string("method not allowed").with_status_code(StatusCode(405));
+    }
+
+    // ignore query parameters
+    let path = request.url().split('?').next().unwrap_or_default();
+
+    let state = state.lock().unwrap();
+    let json = match path {
+        "/status" => serde_json::to_string(&*state).unwrap(),
+        "/export" => match &state.export {
+            Some(export) => serde_json::to_string(export).unwrap(),
+            None => {
+                return Response::from_string("nothing captured yet").with_status_code(StatusCode(404));
+            }
+        },
+        "/metrics" => {
+            return Response::from_string(metrics::render(&state))
+                .with_header(Header::from_bytes(&b"Content-Type"[..], &b"text/plain; version=0.0.4"[..]).unwrap());
+        }
+        _ => return Response::from_string("not found").with_status_code(StatusCode(404)),
+    };
+
+    Response::from_string(json)
+        .with_header(Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..]).unwrap())
+}
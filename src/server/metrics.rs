@@ -0,0 +1,57 @@
+//! Rendering of the [`CaptureState`] in the Prometheus text format,
+//! to monitor long running captures.
+use std::fmt::Write;
+
+use crate::server::CaptureState;
+
+/// Renders the metrics of a capture, all prefixed with `completionist_`
+pub fn render(state: &CaptureState) -> String {
+    let mut metrics = Metrics::default();
+    let stats = &state.stats;
+
+    metrics.add("elapsed_seconds", "gauge", "Seconds since capturing started", &[(vec![], state.elapsed)]);
+    metrics.add("finished", "gauge", "Whether capturing has stopped", &[(vec![], state.finished as u64)]);
+
+    metrics.add("packets_total", "counter", "Packets captured, including other traffic", &[(vec![], stats.packets)]);
+    metrics.add("game_packets_total", "counter", "Packets sent from or to the game server", &[(vec![], stats.game_packets)]);
+    metrics.add("decrypted_packets_total", "counter", "Game packets that were decrypted", &[(vec![], stats.decrypted)]);
+    metrics.add("parse_errors_total", "counter", "Game packets that could not be decrypted", &[(vec![], stats.undecryptable)]);
+    metrics.add("connections_total", "counter", "Connections to the game server", &[(vec![], stats.connections)]);
+    metrics.add("retransmitted_segments_total", "counter", "Retransmitted KCP segments", &[(vec![], stats.kcp.retransmitted)]);
+    metrics.add("lost_segments_total", "counter", "KCP segments that were never seen", &[(vec![], stats.kcp.lost)]);
+
+    let commands: Vec<_> = stats.commands.iter()
+        .map(|(command_id, count)| (vec![("command_id", command_id.to_string())], *count))
+        .collect();
+    metrics.add("commands_total", "counter", "Parsed commands by command id", &commands);
+
+    let items: Vec<_> = state.progress.iter()
+        .filter_map(|progress| Some((vec![("category", progress.name.to_string())], progress.count? as u64)))
+        .collect();
+    metrics.add("items", "gauge", "Items collected by category, like achievements", &items);
+
+    metrics.text
+}
+
+#[derive(Default)]
+struct Metrics {
+    text: String,
+}
+
+impl Metrics {
+    fn add(&mut self, name: &str, kind: &str, help: &str, samples: &[(Vec<(&str, String)>, u64)]) {
+        let _ = writeln!(self.text, "# HELP completionist_{name} {help}");
+        let _ = writeln!(self.text, "# TYPE completionist_{name} {kind}");
+
+        for (labels, value) in samples {
+            let labels: Vec<String> = labels.iter()
+                .map(|(label, value)| format!("{label}=\"{value}\""))
+                .collect();
+            if labels.is_empty() {
+                let _ = writeln!(self.text, "completionist_{name} {value}");
+            } else {
+                let _ = writeln!(self.text, "completionist_{name}{{{}}} {value}", labels.join(","));
+            }
+        }
+    }
+}
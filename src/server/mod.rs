@@ -0,0 +1,28 @@
+//! Servers exposing the state of a running capture to other programs.
+use std::sync::{Arc, Mutex};
+
+use serde::Serialize;
+
+use crate::capture::stats::SessionStats;
+use crate::export::Progress;
+
+pub mod http;
+pub mod metrics;
+pub mod websocket;
+
+/// Snapshot of a running capture, updated by the capture loop and read by the servers
+#[derive(Serialize, Debug, Default)]
+pub struct CaptureState {
+    pub progress: Vec<Progress>,
+    /// Seconds since capturing started
+    pub elapsed: u64,
+    /// Whether capturing has stopped, the export will not change anymore
+    pub finished: bool,
+    pub stats: SessionStats,
+    /// Export of the data collected so far, `None` until the first packet was processed
+    #[serde(skip)]
+    pub export: Option<serde_json::Value>,
+}
+
+/// [`CaptureState`] shared between threads
+pub type SharedCaptureState = Arc<Mutex<CaptureState>>;
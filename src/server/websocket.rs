@@ -0,0 +1,59 @@
+//! WebSocket server broadcasting [`Event`]s to all connected clients as JSON,
+//! e.g. to drive stream overlays.
+use std::io;
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex, mpsc};
+
+use tracing::{debug, info, instrument};
+use tungstenite::{Message, WebSocket};
+
+use crate::export::Event;
+
+/// Starts the server in the background, events sent to the returned sender are broadcast
+/// to every connected client. The server stops once the sender is dropped.
+#[instrument(name = "websocket")]
+pub fn spawn(address: SocketAddr) -> io::Result<mpsc::Sender<Event>> {
+    let listener = TcpListener::bind(address)?;
+    let clients: Arc<Mutex<Vec<WebSocket<TcpStream>>>> = Arc::default();
+
+    let accepted = clients.clone();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(e) => {
+                    debug!(%e, "could not accept connection");
+                    continue;
+                }
+            };
+
+            match tungstenite::accept(stream) {
+                Ok(socket) => {
+                    info!("client connected");
+                    accepted.lock().unwrap().push(socket);
+                }
+                Err(_) => debug!("websocket handshake failed"),
+            }
+        }
+    });
+
+    let (tx, rx) = mpsc::channel::<Event>();
+    std::thread::spawn(move || {
+        for event in rx {
+            let json = serde_json::to_string(&event).unwrap();
+            clients.lock().unwrap().retain_mut(|socket| match socket.send(Message::Text(json.clone())) {
+                Ok(()) => true,
+                Err(e) => {
+                    debug!(%e, "client disconnected");
+                    false
+                }
+            });
+        }
+
+        debug!("stopped broadcasting events");
+    });
+
+    info!("listening on ws://{address}");
+
+    Ok(tx)
+}
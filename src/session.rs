@@ -0,0 +1,799 @@
+//! Capture sessions, where the other modules come together.
+//!
+//! A [`Session`] feeds captured packets from any [`PacketSource`] through the sniffer into an
+//! [`Exporter`], taking care of finding the game server, reconnects, retransmitted packets,
+//! refreshing outdated keys and deciding when enough data was collected:
+//!
+//! ```no_run
+//! use completionist_archiver::capture::file::CaptureFile;
+//! use completionist_archiver::database::{Database, DatabaseOptions, HttpTimeouts};
+//! use completionist_archiver::export::fribbels::OptimizerExporter;
+//! use completionist_archiver::session::Session;
+//! # use std::path::Path;
+//! # use std::time::Duration;
+//! # fn main() -> completionist_archiver::error::Result<()> {
+//! let options = DatabaseOptions {
+//!     timeouts: HttpTimeouts { connect: Duration::from_secs(10), read: Duration::from_secs(30) },
+//!     names: false,
+//! };
+//! let database = Database::new_from_online(&options)?;
+//! let keys = database.keys().clone();
+//!
+//! let packets = CaptureFile::open(Path::new("recording.pcapng"))?;
+//! let outcome = Session::new(OptimizerExporter::new(database), keys).run(packets);
+//! # Ok(())
+//! # }
+//! ```
+use std::collections::HashMap;
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Local};
+use reliquary::network::{ConnectionPacket, GameCommand, GamePacket, GameSniffer};
+use serde::Serialize;
+use tokio::runtime::Runtime;
+use tokio::sync::oneshot::error::TryRecvError;
+use tokio::sync::{mpsc, oneshot};
+use tracing::{debug, error, info, instrument, warn};
+
+use crate::capture::kcp::KcpFilter;
+use crate::capture::server::{log_server, ConnectionTracker, ServerFilter};
+use crate::capture::source::{Captured, PacketSource};
+use crate::capture::stats::SessionStats;
+use crate::database::{Database, HttpTimeouts};
+use crate::export::command_ids::CommandIds;
+use crate::export::dump::DumpWriter;
+use crate::export::{Exporter, FinishCriteria, Progress};
+use crate::server::SharedCaptureState;
+
+/// How often the capture progress is logged if it did not change
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(30);
+/// How often the finish criteria are checked while no commands arrive, as the inventory and the
+/// quest data only count as complete a while after their last packet, which may be the last traffic
+const FINISH_CHECK_INTERVAL: Duration = Duration::from_millis(500);
+/// How many packets and batches of commands may wait for the next stage, before the previous
+/// stage waits for it to catch up
+const CHANNEL_CAPACITY: usize = 1024;
+/// Number of packets after the handshake of a connection that have to fail to decrypt, while the
+/// token of the login could not be decrypted either, before the keys count as missing
+const MISSING_KEYS_AFTER: usize = 200;
+
+/// Optional behavior of a [`Session`]
+#[derive(Default)]
+pub struct SessionOptions {
+    pub finish: FinishCriteria,
+    /// Fetch newer keys with these timeouts once when packets cannot be decrypted
+    pub key_refresh: Option<HttpTimeouts>,
+    /// Keep capturing across logins until there are no packets left, instead of stopping
+    /// once the game finished logging in or enough data was collected
+    pub keep_going: bool,
+    /// State to publish the progress and collected data to while capturing
+    pub shared_state: Option<SharedCaptureState>,
+    /// Dump to write every command to
+    pub dump: Option<DumpWriter>,
+    pub command_ids: CommandIds,
+    /// Flag to stop capturing early once it is set, e.g. from a ctrl-c handler
+    pub stop: Option<&'static AtomicBool>,
+    /// Writer to flush the exporter to whenever commands were read, see [`Exporter::flush`]
+    pub stream: Option<Box<dyn Write + Send>>,
+}
+
+/// What a [`Session`] collected
+pub struct SessionOutcome<X> {
+    /// One export per account logged in while capturing, the current account last
+    pub exports: Vec<X>,
+    pub report: SessionReport,
+}
+
+/// Why a [`Session`] stopped capturing
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FinishReason {
+    /// Enough data was collected, see [`SessionOptions::finish`]
+    Finished,
+    /// The game finished logging in
+    LoginFinished,
+    /// Capturing was stopped early using [`SessionOptions::stop`]
+    Stopped,
+    /// There were no packets left, e.g. at the end of a capture file or when a live capture
+    /// timed out
+    SourceExhausted,
+    /// None of the packets after the handshake of a connection could be decrypted, not even
+    /// the token of the login, so the keys of the game version are missing
+    MissingKeys,
+}
+
+/// Summary of a [`Session`], for automation and for triaging captures that went wrong
+#[derive(Serialize, Debug, Clone)]
+pub struct SessionReport {
+    /// When capturing started and stopped, written as ISO 8601 with the local offset
+    pub started_at: DateTime<Local>,
+    pub finished_at: DateTime<Local>,
+    pub finish_reason: FinishReason,
+    /// Progress of every category of data when capturing stopped
+    pub progress: Vec<Progress>,
+    /// Descriptions of the data that was not collected
+    pub missing: Vec<&'static str>,
+    /// Commands the exporter could not parse completely
+    pub parse_failures: u64,
+    /// What happened to the packets, including how long the session took
+    pub stats: SessionStats,
+}
+
+/// Capture of a single game client into an exporter
+pub struct Session<E: Exporter> {
+    exporter: E,
+    keys: HashMap<u32, Vec<u8>>,
+    options: SessionOptions,
+    on_update: Option<Box<dyn FnMut(&E) + Send>>,
+}
+
+impl<E> Session<E>
+where
+    E: Exporter + Send + 'static,
+    E::Export: Serialize,
+{
+    /// Session reading into `exporter`, decrypting packets with `keys`
+    /// (see [`Database::keys`])
+    pub fn new(exporter: E, keys: HashMap<u32, Vec<u8>>) -> Session<E> {
+        Session {
+            exporter,
+            keys,
+            options: SessionOptions::default(),
+            on_update: None,
+        }
+    }
+
+    pub fn with_options(mut self, options: SessionOptions) -> Session<E> {
+        self.options = options;
+        self
+    }
+
+    /// Calls `on_update` with the exporter whenever commands were read into it,
+    /// on the thread the commands are read on
+    pub fn on_update(mut self, on_update: impl FnMut(&E) + Send + 'static) -> Session<E> {
+        self.on_update = Some(Box::new(on_update));
+        self
+    }
+
+    /// Feeds captured packets through the sniffer into the exporter until the exporter is finished,
+    /// the game finished logging in or there are no packets left. With
+    /// [`SessionOptions::keep_going`], capturing only stops once there are no packets left.
+    ///
+    /// Live captures, replayed capture files and injected commands all go through here,
+    /// so they behave the same. Commands that were decrypted before skip the sniffer.
+    ///
+    /// Packets are taken from `source` on a thread of their own and passed through channels to
+    /// tasks decrypting them and reading the commands into the exporter, so bursts of packets
+    /// are not held up by the exporter parsing the commands and publishing its progress.
+    /// The tasks of all sessions share one runtime. Once the exporter is finished, this returns
+    /// without waiting for the source to hand out another packet.
+    /// The game reconnecting mid-session is handled by starting over with a fresh sniffer,
+    /// while commands of all connections are read into the same exporter.
+    #[instrument(skip_all)]
+    pub fn run(self, mut source: impl PacketSource + Send + 'static) -> SessionOutcome<E::Export> {
+        let Session {
+            exporter,
+            keys,
+            options,
+            on_update,
+        } = self;
+        let SessionOptions {
+            finish,
+            key_refresh,
+            keep_going,
+            shared_state,
+            dump,
+            command_ids,
+            stop,
+            stream,
+        } = options;
+
+        let runtime = runtime();
+        let start = Instant::now();
+        let started_at = Local::now();
+
+        let (packets_tx, packets_rx) = mpsc::channel(CHANNEL_CAPACITY);
+        let (commands_tx, commands_rx) = mpsc::channel(CHANNEL_CAPACITY);
+
+        let decrypt_stage = DecryptStage {
+            sniffer: GameSniffer::new().set_initial_keys(keys.clone()),
+            keys,
+            key_refresh,
+            keep_going,
+            quiet_period: finish.quiet_period.is_some(),
+            shared_state: shared_state.clone(),
+            dump,
+            command_ids,
+            start,
+            stats: SessionStats::default(),
+            kcp: KcpFilter::new(),
+        };
+        let decrypting = runtime.spawn(decrypt_stage.run(packets_rx, commands_tx));
+
+        let export_stage = ExportStage {
+            exporter,
+            on_update,
+            finish,
+            keep_going,
+            status: Status::new(shared_state.clone()),
+            stream,
+        };
+        let exporting = runtime.spawn(export_stage.run(commands_rx));
+
+        // the source waits for packets, so it is left behind once the stages stopped capturing.
+        // it is dropped as soon as it hands out another packet
+        runtime.spawn_blocking(move || {
+            while let Some(captured) = source.next_captured() {
+                if stop.is_some_and(|stop| stop.load(Ordering::Relaxed)) {
+                    info!("stopped capturing early");
+                    break;
+                }
+
+                // the decrypt stage only stops receiving once it stopped capturing
+                if packets_tx.blocking_send(captured).is_err() {
+                    break;
+                }
+            }
+            // dropping the sender lets the stages process the remaining packets
+        });
+
+        let (decrypted, exported) = runtime.block_on(async { (decrypting.await, exporting.await) });
+        let Decrypted { mut stats, kcp, stopped } = decrypted.unwrap();
+        let Exported { mut exporter, finished } = exported.unwrap();
+
+        let finish_reason = if let Some(stopped) = stopped {
+            stopped
+        } else if finished {
+            info!("retrieved all relevant packets, stop capturing");
+            FinishReason::Finished
+        } else if stop.is_some_and(|stop| stop.load(Ordering::Relaxed)) {
+            // live sources stop on their own as well
+            FinishReason::Stopped
+        } else {
+            FinishReason::SourceExhausted
+        };
+
+        stats.set_duration(start.elapsed());
+        stats.kcp = kcp.stats();
+
+        if let Some(shared_state) = shared_state {
+            let mut state = shared_state.lock().unwrap();
+            state.export = serde_json::to_value(exporter.export_partial()).ok();
+            state.finished = true;
+            state.stats = stats.clone();
+        }
+        stats.log();
+        if stats.kcp.lost > 0 {
+            warn!("{} packets were lost while capturing, some data may be missing", stats.kcp.lost);
+        }
+
+        let missing = exporter.missing();
+        if !missing.is_empty() {
+            warn!("capture ended before all data was collected, exporting partial data");
+            for missing in &missing {
+                warn!("missing: {missing}");
+            }
+        }
+
+        let report = SessionReport {
+            started_at,
+            finished_at: Local::now(),
+            finish_reason,
+            progress: exporter.progress(),
+            missing,
+            parse_failures: exporter.parse_failures(),
+            stats,
+        };
+
+        let mut exports = exporter.take_switched_accounts();
+        exports.push(exporter.export());
+        SessionOutcome { exports, report }
+    }
+}
+
+/// What the [`DecryptStage`] of a [`Session`] found out about the packets
+struct Decrypted {
+    stats: SessionStats,
+    kcp: KcpFilter,
+    /// Why the stage stopped capturing, `None` if it ran out of packets
+    stopped: Option<FinishReason>,
+}
+
+/// Decrypts the captured packets of a [`Session`] into batches of commands, as a task of its own
+struct DecryptStage {
+    sniffer: GameSniffer,
+    keys: HashMap<u32, Vec<u8>>,
+    key_refresh: Option<HttpTimeouts>,
+    keep_going: bool,
+    /// Whether commands sent after logging in are waited for, see [`FinishCriteria::quiet_period`]
+    quiet_period: bool,
+    shared_state: Option<SharedCaptureState>,
+    dump: Option<DumpWriter>,
+    command_ids: CommandIds,
+    start: Instant,
+    stats: SessionStats,
+    kcp: KcpFilter,
+}
+
+impl DecryptStage {
+    /// Decrypts packets until there are none left or capturing has to stop, e.g. as the export
+    /// stage is finished and stopped receiving
+    async fn run(mut self, mut packets: mpsc::Receiver<Captured>, commands_tx: mpsc::Sender<Vec<GameCommand>>) -> Decrypted {
+        let mut servers = ServerFilter::new();
+        let mut server = None;
+        let mut invalid = 0;
+        let mut warning_sent = false;
+        let mut new_keys: Option<oneshot::Receiver<HashMap<u32, Vec<u8>>>> = None;
+        // packets that could not be decrypted since the last handshake, as long as none could.
+        // once the token of the login is decrypted, failures are problems with the session key
+        let mut failed_since_handshake: Option<usize> = None;
+        let mut stopped = None;
+
+        loop {
+            let captured = tokio::select! {
+                captured = packets.recv() => match captured {
+                    Some(captured) => captured,
+                    None => break,
+                },
+                // the export stage is finished, which is seen without waiting for the next packet
+                _ = commands_tx.closed() => break,
+            };
+
+            let commands = match captured {
+                Captured::Command(command) => vec![command],
+                Captured::Frame(data) => {
+                    match new_keys.as_mut().map(|rx| rx.try_recv()) {
+                        Some(Ok(refreshed)) => {
+                            info!("loaded new keys, please log out and log back in");
+                            self.keys = refreshed;
+                            self.sniffer = GameSniffer::new().set_initial_keys(self.keys.clone());
+                            new_keys = None;
+                            invalid = 0;
+                            warning_sent = false;
+                            // the login has to be captured again with the new keys
+                            failed_since_handshake = None;
+                        }
+                        // the refresh gave up without finding newer keys
+                        Some(Err(TryRecvError::Closed)) => new_keys = None,
+                        _ => {}
+                    }
+
+                    self.stats.packets += 1;
+                    let Some(address) = servers.filter(&data) else {
+                        continue;
+                    };
+                    self.stats.game_packets += 1;
+                    if server != Some(address) {
+                        log_server(address);
+                        server = Some(address);
+                    }
+                    if !self.kcp.accept(&data, address) {
+                        continue;
+                    }
+
+                    match self.sniffer.receive_packet(data) {
+                        Some(GamePacket::Connection(c)) => {
+                            match c {
+                                ConnectionPacket::HandshakeEstablished => {
+                                    self.stats.connections += 1;
+                                    if self.stats.connections > 1 {
+                                        info!("detected reconnect, continuing capture");
+                                    } else {
+                                        info!("detected connection established");
+                                    }
+                                    invalid = 0;
+                                    warning_sent = false;
+                                    failed_since_handshake = Some(0);
+                                }
+                                ConnectionPacket::Disconnected => {
+                                    info!("detected disconnect, waiting for the game to reconnect");
+                                    // drop all state of the old connection, a reconnect uses a new session key
+                                    self.sniffer = GameSniffer::new().set_initial_keys(self.keys.clone());
+                                    self.kcp.reset();
+                                }
+                                _ => {}
+                            }
+                            continue;
+                        }
+                        Some(GamePacket::Commands(commands)) if commands.is_empty() => {
+                            self.stats.undecryptable += 1;
+                            invalid += 1;
+
+                            if invalid >= 25 && !warning_sent {
+                                error!("received a large number of packets that could not be parsed");
+                                warn!("you probably started capturing when you were already in-game");
+                                warn!("please log out and log back in");
+                                warning_sent = true;
+
+                                if let Some(timeouts) = self.key_refresh.take() {
+                                    info!("looking for newer keys in the background");
+                                    new_keys = Some(spawn_key_refresh(timeouts, self.keys.clone()));
+                                }
+                            }
+
+                            // the keys of this game version are missing, as not even the token of
+                            // the login could be decrypted. a refresh still looking for newer keys
+                            // would only find them after the login, which has to be captured again
+                            failed_since_handshake = failed_since_handshake.map(|failed| failed + 1);
+                            if failed_since_handshake.is_some_and(|failed| failed >= MISSING_KEYS_AFTER) {
+                                error!("none of the packets since the connection was established could be decrypted, the keys of this game version are missing");
+                                failed_since_handshake = None;
+                                if !self.keep_going {
+                                    stopped = Some(FinishReason::MissingKeys);
+                                    break;
+                                }
+                            }
+                            continue;
+                        }
+                        Some(GamePacket::Commands(commands)) => {
+                            failed_since_handshake = None;
+                            invalid = 0.max(invalid - 10);
+                            self.stats.decrypted += 1;
+                            commands
+                        }
+                        _ => continue,
+                    }
+                }
+            };
+
+            let mut batch = Vec::with_capacity(commands.len());
+            let mut login_finished = false;
+            for command in commands {
+                self.stats.add_command(command.command_id);
+
+                if let Some(writer) = &mut self.dump {
+                    if let Err(e) = writer.write(&command) {
+                        error!(%e, "could not write to debug dump, stop dumping");
+                        self.dump = None;
+                    }
+                }
+
+                if command.command_id == self.command_ids.player_login_sc_rsp {
+                    info!("detected login");
+                }
+
+                if command.command_id == self.command_ids.player_login_finish_sc_rsp {
+                    info!("detected login end, assume initialization is finished");
+                    // with a quiet period, data sent after logging in is waited for as well
+                    if !self.keep_going && !self.quiet_period {
+                        login_finished = true;
+                        break;
+                    }
+                }
+
+                batch.push(command);
+            }
+
+            if let Some(shared_state) = &self.shared_state {
+                self.stats.set_duration(self.start.elapsed());
+                self.stats.kcp = self.kcp.stats();
+                shared_state.lock().unwrap().stats = self.stats.clone();
+            }
+
+            // the export stage only stops receiving once it is finished
+            if commands_tx.send(batch).await.is_err() {
+                break;
+            }
+            if login_finished {
+                stopped = Some(FinishReason::LoginFinished);
+                break;
+            }
+        }
+
+        Decrypted {
+            stats: self.stats,
+            kcp: self.kcp,
+            stopped,
+        }
+    }
+}
+
+/// The exporter of a [`Session`], once the [`ExportStage`] stopped reading commands
+struct Exported<E> {
+    exporter: E,
+    /// Whether enough data was collected, rather than running out of commands
+    finished: bool,
+}
+
+/// Reads the decrypted commands of a [`Session`] into its exporter, as a task of its own
+struct ExportStage<E> {
+    exporter: E,
+    on_update: Option<Box<dyn FnMut(&E) + Send>>,
+    finish: FinishCriteria,
+    keep_going: bool,
+    status: Status,
+    stream: Option<Box<dyn Write + Send>>,
+}
+
+impl<E> ExportStage<E>
+where
+    E: Exporter,
+    E::Export: Serialize,
+{
+    /// Reads batches of commands until there are none left or enough data was collected,
+    /// returning the exporter
+    async fn run(mut self, mut commands: mpsc::Receiver<Vec<GameCommand>>) -> Exported<E> {
+        let mut last_progress = self.exporter.progress();
+        let mut last_change = Instant::now();
+        let mut finished = false;
+
+        loop {
+            match tokio::time::timeout(FINISH_CHECK_INTERVAL, commands.recv()).await {
+                // parsing large batches takes a while, which must not hold up the decrypt stage
+                Ok(Some(batch)) => tokio::task::block_in_place(|| {
+                    self.exporter.read_commands(&batch);
+
+                    self.status.update(&self.exporter);
+
+                    let progress = self.exporter.progress();
+                    if progress != last_progress {
+                        last_progress = progress;
+                        last_change = Instant::now();
+                    }
+
+                    if let Some(on_update) = &mut self.on_update {
+                        on_update(&self.exporter);
+                    }
+                    self.flush();
+                }),
+                Ok(None) => break,
+                // checked again without new commands, e.g. for the quiet period to elapse
+                Err(_) => {}
+            }
+
+            if !self.keep_going && self.finish.is_met(&self.exporter, last_change.elapsed()) {
+                finished = true;
+                break;
+            }
+        }
+
+        Exported {
+            exporter: self.exporter,
+            finished,
+        }
+    }
+
+    fn flush(&mut self) {
+        if let Some(stream) = &mut self.stream {
+            if let Err(e) = self.exporter.flush(stream) {
+                error!(%e, "could not write to stream, stop streaming");
+                self.stream = None;
+            }
+        }
+    }
+}
+
+/// Like [`Session::run`], but with a separate exporter created by `create` for every game client,
+/// for multiple game clients running at the same time.
+///
+/// Every client is processed on its own thread, with the stages of all clients sharing the runtime
+/// of the sessions. Capturing stops once all clients are finished
+/// or there are no packets left, returning the exports of all clients.
+/// The shared state, dump and stream of the options are not used, as they would mix up the clients.
+#[instrument(skip_all)]
+pub fn run_instances<E, F>(
+    create: F,
+    keys: &HashMap<u32, Vec<u8>>,
+    packets: impl Iterator<Item = Vec<u8>>,
+    options: SessionOptions,
+) -> Vec<E::Export>
+where
+    F: Fn() -> E,
+    E: Exporter + Send + 'static,
+    E::Export: Serialize + Send,
+{
+    let mut connections = ConnectionTracker::new();
+
+    std::thread::scope(|scope| {
+        let mut clients = HashMap::new();
+        let mut handles = vec![];
+
+        for frame in packets {
+            let Some(connection) = connections.track(&frame) else {
+                continue;
+            };
+
+            let client = clients.entry(connection.client).or_insert_with(|| {
+                info!(instance = handles.len() + 1, "detected game client {}", connection.client);
+
+                let options = SessionOptions {
+                    finish: options.finish.clone(),
+                    key_refresh: options.key_refresh,
+                    command_ids: options.command_ids,
+                    stop: options.stop,
+                    ..Default::default()
+                };
+                let exporter = create();
+                let keys = keys.clone();
+
+                let (tx, rx) = std::sync::mpsc::channel();
+                handles.push(scope.spawn(move || {
+                    Session::new(exporter, keys).with_options(options).run(rx.into_iter()).exports
+                }));
+                tx
+            });
+
+            // a client that is finished already stopped receiving
+            let _ = client.send(frame);
+
+            if handles.iter().all(|handle| handle.is_finished()) {
+                info!("all game clients are finished, stop capturing");
+                break;
+            }
+        }
+
+        // lets the clients that are still capturing finish up
+        drop(clients);
+
+        handles.into_iter()
+            .flat_map(|handle| handle.join().unwrap())
+            .collect()
+    })
+}
+
+/// Logs the capture progress whenever it changes, and regularly while packets keep arriving.
+/// Also publishes the progress to the shared state, if there is one.
+struct Status {
+    start: Instant,
+    last_logged: Instant,
+    last_progress: Vec<Progress>,
+    shared_state: Option<SharedCaptureState>,
+}
+
+impl Status {
+    fn new(shared_state: Option<SharedCaptureState>) -> Status {
+        Status {
+            start: Instant::now(),
+            last_logged: Instant::now(),
+            last_progress: vec![],
+            shared_state,
+        }
+    }
+
+    fn update<E>(&mut self, exporter: &E)
+    where
+        E: Exporter,
+        E::Export: Serialize,
+    {
+        let progress = exporter.progress();
+
+        if let Some(shared_state) = &self.shared_state {
+            let mut state = shared_state.lock().unwrap();
+            state.progress = progress.clone();
+            state.elapsed = self.start.elapsed().as_secs();
+            state.export = serde_json::to_value(exporter.export_partial()).ok();
+        }
+
+        if progress == self.last_progress && self.last_logged.elapsed() < PROGRESS_INTERVAL {
+            return;
+        }
+
+        let elapsed = self.start.elapsed().as_secs();
+        let categories: Vec<String> = progress.iter().map(|p| p.to_string()).collect();
+        info!(
+            "progress: {} | {:02}:{:02} elapsed",
+            categories.join(" | "),
+            elapsed / 60,
+            elapsed % 60
+        );
+
+        self.last_logged = Instant::now();
+        self.last_progress = progress;
+    }
+}
+
+/// Runtime the stages of every [`Session`] run on, started with the first session
+fn runtime() -> &'static Runtime {
+    static RUNTIME: OnceLock<Runtime> = OnceLock::new();
+
+    RUNTIME.get_or_init(|| {
+        tokio::runtime::Builder::new_multi_thread()
+            .enable_time()
+            .build()
+            .expect("unable to start the capture runtime")
+    })
+}
+
+/// Fetches keys in the background, backing off between attempts, until keys that differ from
+/// `known_keys` are published. Gives up after the last attempt, closing the receiver.
+/// Has to be called from a task of the [`runtime`], and stops once the receiver is dropped
+#[instrument(skip_all)]
+fn spawn_key_refresh(timeouts: HttpTimeouts, known_keys: HashMap<u32, Vec<u8>>) -> oneshot::Receiver<HashMap<u32, Vec<u8>>> {
+    const BACKOFF_SECS: [u64; 5] = [0, 30, 60, 120, 240];
+
+    let (mut tx, rx) = oneshot::channel();
+
+    tokio::spawn(async move {
+        for delay in BACKOFF_SECS {
+            tokio::select! {
+                _ = tokio::time::sleep(Duration::from_secs(delay)) => {}
+                // the session ended
+                _ = tx.closed() => return,
+            }
+
+            // the requests block, which must not hold up the stages of the session
+            let fetched = tokio::task::spawn_blocking(move || Database::fetch_online_keys(timeouts)).await;
+            match fetched.ok().flatten() {
+                Some(keys) if keys != known_keys => {
+                    debug!(num = keys.len(), "found new keys");
+                    let _ = tx.send(keys);
+                    return;
+                }
+                Some(_) => debug!("no new keys published yet"),
+                None => {}
+            }
+        }
+
+        warn!("no new keys were published, the archiver might need to be updated");
+    });
+
+    rx
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::SocketAddrV4;
+
+    use crate::capture::udp_frame;
+    use crate::export::fribbels::OptimizerExporter;
+
+    const CMD_PUSH: u8 = 81;
+    const CONV: u32 = 1;
+
+    fn client() -> SocketAddrV4 {
+        "192.168.1.2:50000".parse().unwrap()
+    }
+
+    fn server() -> SocketAddrV4 {
+        "10.0.0.1:23301".parse().unwrap()
+    }
+
+    fn handshake((start, end): ([u8; 4], [u8; 4])) -> Vec<u8> {
+        [&start[..], &CONV.to_be_bytes(), &[0; 8], &end].concat()
+    }
+
+    /// Frame of the server pushing data that no key decrypts
+    fn push(sn: u32) -> Vec<u8> {
+        let data = [0x5a; 64];
+        let mut segment = CONV.to_le_bytes().to_vec();
+        segment.extend_from_slice(&[0; 4]);
+        segment.extend_from_slice(&[CMD_PUSH, 0, 0, 0]);
+        segment.extend_from_slice(&[0; 4]);
+        segment.extend_from_slice(&sn.to_le_bytes());
+        segment.extend_from_slice(&0u32.to_le_bytes());
+        segment.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        segment.extend_from_slice(&data);
+        udp_frame(server(), client(), &segment)
+    }
+
+    fn run(packets: Vec<Vec<u8>>) -> SessionReport {
+        let exporter = OptimizerExporter::new(Database::new(vec![], vec![], HashMap::new()));
+        let wrong_keys = HashMap::from([(0, vec![0x42; 4096])]);
+        Session::new(exporter, wrong_keys).run(packets.into_iter()).report
+    }
+
+    #[test]
+    fn stops_if_nothing_after_the_handshake_decrypts() {
+        let mut packets = vec![
+            udp_frame(client(), server(), &handshake(([0x00, 0x00, 0x00, 0xff], [0xff, 0xff, 0xff, 0xff]))),
+            udp_frame(server(), client(), &handshake(([0x00, 0x00, 0x01, 0x45], [0x14, 0x51, 0x45, 0x45]))),
+        ];
+        packets.extend((0..MISSING_KEYS_AFTER as u32 + 50).map(push));
+
+        let report = run(packets);
+        assert_eq!(report.finish_reason, FinishReason::MissingKeys);
+        assert_eq!(report.stats.decrypted, 0);
+    }
+
+    #[test]
+    fn keeps_capturing_without_a_handshake() {
+        // capturing started in-game, so logging in again might still help
+        let packets = (0..MISSING_KEYS_AFTER as u32 + 50).map(push).collect();
+
+        assert_eq!(run(packets).finish_reason, FinishReason::SourceExhausted);
+    }
+}
@@ -0,0 +1,116 @@
+//! Setup asking for the most important options on the first launch, and writing them to the
+//! config file, so users that start the archiver by double clicking it do not need to know
+//! about command line options.
+use std::fs;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::PathBuf;
+
+use pcap::Device;
+
+use completionist_archiver::error::{ArchiverError, PathContext};
+use completionist_archiver::export::registry::ExporterRegistry;
+
+use crate::config::{self, Config};
+use crate::i18n::t;
+use crate::source::is_default_device;
+
+/// Whether the archiver was started for the first time without any options from a terminal
+pub fn is_first_run() -> bool {
+    std::env::args_os().len() == 1
+        && !std::env::vars_os().any(|(name, _)| name.to_string_lossy().starts_with("ARCHIVER_"))
+        && io::stdin().is_terminal()
+        && config::default_path().is_some_and(|path| !path.exists())
+}
+
+/// Asks for the options and writes them to the config file
+pub fn run() -> Result<(), ArchiverError> {
+    let path = config::default_path()
+        .ok_or(ArchiverError::Unsupported("there is no config directory on this platform"))?;
+
+    println!("{}", t!("setup-welcome"));
+    println!("{}", t!("setup-saved-to", path = path.display().to_string()));
+    println!();
+
+    let config = Config {
+        interface: ask_interfaces()?,
+        output: Some(ask_output()?),
+        format: ask_formats()?,
+        ..Default::default()
+    };
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).with_path("create", dir)?;
+    }
+    let content = toml::to_string(&config).map_err(|e| ArchiverError::InvalidData(format!("could not write config: {e}")))?;
+    fs::write(&path, content).with_path("write", &path)?;
+
+    println!();
+    println!("{}", t!("setup-saved", path = path.display().to_string()));
+    Ok(())
+}
+
+fn ask_interfaces() -> Result<Option<Vec<String>>, ArchiverError> {
+    // without npcap the interfaces cannot be listed, which is reported once capturing starts
+    let Ok(devices) = Device::list() else {
+        return Ok(None);
+    };
+
+    println!("{}", t!("setup-interfaces"));
+    for (i, device) in devices.iter().enumerate() {
+        let default = if is_default_device(device) { format!(" {}", t!("setup-captured-by-default")) } else { String::new() };
+        println!("  {}. {}{default}", i + 1, device.desc.as_deref().unwrap_or(&device.name));
+    }
+
+    let selected = ask_numbers(&t!("setup-ask-interfaces"), &t!("setup-all-connected"), devices.len())?;
+    Ok(selected.map(|selected| selected.into_iter().map(|i| devices[i].name.clone()).collect()))
+}
+
+fn ask_output() -> Result<PathBuf, ArchiverError> {
+    let dir = ask(&t!("setup-ask-output"), &t!("setup-current-folder"))?;
+    let dir = dir.map(PathBuf::from).unwrap_or_default();
+    // one file per account and day, so earlier exports are kept
+    Ok(dir.join("archive_{uid}_{date}.json"))
+}
+
+fn ask_formats() -> Result<Option<Vec<String>>, ArchiverError> {
+    let registry = ExporterRegistry::with_builtin();
+    let names: Vec<&str> = registry.names().collect();
+
+    println!("{}", t!("setup-formats"));
+    for (i, name) in names.iter().enumerate() {
+        println!("  {}. {name}: {}", i + 1, registry.description(name).unwrap_or_default());
+    }
+
+    let selected = ask_numbers(&t!("setup-ask-formats"), "fribbels", names.len())?;
+    Ok(selected.map(|selected| selected.into_iter().map(|i| names[i].to_string()).collect()))
+}
+
+/// Asks for numbers of a list with `len` entries until valid ones are entered,
+/// returning their indices, or `None` to keep the default
+fn ask_numbers(question: &str, default: &str, len: usize) -> Result<Option<Vec<usize>>, ArchiverError> {
+    loop {
+        let Some(answer) = ask(question, default)? else {
+            return Ok(None);
+        };
+
+        let numbers: Option<Vec<usize>> = answer.split(',')
+            .map(|number| number.trim().parse::<usize>().ok().filter(|number| (1..=len).contains(number)))
+            .map(|number| number.map(|number| number - 1))
+            .collect();
+        match numbers {
+            Some(numbers) => return Ok(Some(numbers)),
+            None => println!("{}", t!("setup-invalid-numbers", len = len)),
+        }
+    }
+}
+
+/// Asks a question, returning the trimmed answer or `None` if it was left empty
+fn ask(question: &str, default: &str) -> Result<Option<String>, ArchiverError> {
+    print!("{question} [{default}]: ");
+    io::stdout().flush()?;
+
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer)?;
+    let answer = answer.trim();
+    Ok((!answer.is_empty()).then(|| answer.to_string()))
+}
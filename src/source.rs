@@ -0,0 +1,444 @@
+//! Where the packets of a capture come from: the network interfaces of this machine, another
+//! machine with `--remote`, or a recording.
+#[cfg(feature = "gui")]
+use std::collections::HashMap;
+use std::net::{SocketAddr, TcpStream};
+use std::path::Path;
+use std::sync::atomic::Ordering;
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+use pcap::{ConnectionStatus, Device, Error};
+#[cfg(feature = "gui")]
+use serde::Serialize;
+use tracing::{debug, error, info, instrument, trace, warn};
+
+use completionist_archiver::capture::file::{CaptureFile, CaptureFileWriter};
+use completionist_archiver::capture::process::{is_game_running, GameExitWatch};
+use completionist_archiver::capture::server::{log_server, ServerFilter};
+use completionist_archiver::error::ArchiverError;
+#[cfg(feature = "gui")]
+use completionist_archiver::export::Exporter;
+#[cfg(feature = "gui")]
+use completionist_archiver::session::Session;
+use completionist_archiver::session::SessionOptions;
+
+#[cfg(feature = "gui")]
+use crate::report_suppressed_warnings;
+#[cfg(all(windows, feature = "windivert"))]
+use crate::report_error;
+use crate::{Args, Backend, LiveArgs, CAPTURING, STOP_REQUESTED};
+
+/// All UDP traffic is captured, as the server port depends on the region.
+/// [`live_packets`] keeps the traffic of the game server
+const PACKET_FILTER: &str = "udp";
+/// How often an idle live capture checks whether it should stop
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+/// How long to wait for game traffic before checking whether it is missing
+const MISSING_TRAFFIC_HINT_AFTER: Duration = Duration::from_secs(30);
+/// Words in the names of interfaces that are usually tunnels of VPNs and game boosters
+const TUNNEL_KEYWORDS: [&str; 12] = [
+    "vpn", "tap", "tun", "wintun", "wireguard", "nordlynx", "openvpn", "exitlag", "zerotier", "tailscale", "hamachi", "booster",
+];
+
+/// Replays the packets of a recording, at the pace they were recorded if `speed` is given
+pub fn file_source(path: &Path, speed: Option<f64>) -> Result<CaptureFile, ArchiverError> {
+    let mut packets = CaptureFile::open(path)?;
+
+    if let Some(speed) = speed {
+        packets.set_speed(speed);
+    }
+
+    info!("replaying packets from {}", path.display());
+
+    Ok(packets)
+}
+
+/// Starts capturing live packets, setting the options that only apply to live captures
+pub fn live_source(args: &Args, options: &mut SessionOptions) -> Result<impl Iterator<Item = Vec<u8>> + Send, ArchiverError> {
+    let live = args.live();
+    let packets = live_packets(live)?.map(|(_, frame)| frame);
+
+    if live.watch {
+        info!("instructions: log into the game whenever you like, the output is updated on every login");
+        info!("watching for game sessions...");
+    } else if live.wait {
+        info!("instructions: start the game and go into train hyperdrive");
+        info!("waiting for the game to connect...");
+    } else {
+        info!("instructions: go to main menu screen and go into train hyperdrive");
+        info!("listening with a timeout of {} seconds...", live.timeout);
+    }
+    info!("press enter or ctrl-c to stop early and export what was collected so far");
+
+    options.keep_going = live.watch;
+
+    // keys for a new game version are often published a few minutes after the patch goes live,
+    // so if packets cannot be decrypted we look for new keys once during the session
+    options.key_refresh = Some(args.http_timeouts());
+
+    Ok(packets)
+}
+
+#[cfg(feature = "gui")]
+#[instrument(skip_all)]
+pub fn live_capture<E>(
+    args: &Args,
+    exporter: E,
+    keys: HashMap<u32, Vec<u8>>,
+    mut options: SessionOptions,
+) -> Result<Vec<E::Export>, ArchiverError>
+where
+    E: Exporter + Send + 'static,
+    E::Export: Serialize,
+{
+    let packets = live_source(args, &mut options)?;
+    let exports = Session::new(exporter, keys).with_options(options).run(packets).exports;
+    report_suppressed_warnings();
+    Ok(exports)
+}
+
+/// Records the game traffic to a pcapng file without decrypting it
+#[instrument(skip_all)]
+pub fn raw_capture(args: &Args, path: &Path) -> Result<(), ArchiverError> {
+    let live = args.live();
+    let packets = live_packets(live)?;
+    let mut writer = CaptureFileWriter::create(path)?;
+
+    if live.wait {
+        info!("instructions: start the game and go into train hyperdrive");
+        info!("waiting for the game to connect...");
+    } else {
+        info!("instructions: go to main menu screen and go into train hyperdrive");
+        info!("recording with a timeout of {} seconds...", live.timeout);
+    }
+    info!("press enter or ctrl-c to stop recording");
+
+    CAPTURING.store(true, Ordering::Relaxed);
+
+    let mut server = None;
+    let mut recorded = 0;
+    for (address, frame) in packets {
+        if STOP_REQUESTED.load(Ordering::Relaxed) {
+            break;
+        }
+
+        if server != Some(address) {
+            log_server(address);
+            server = Some(address);
+        }
+
+        if let Err(e) = writer.write(&frame) {
+            error!(%e, "could not write to recording file");
+            break;
+        }
+        recorded += 1;
+    }
+
+    CAPTURING.store(false, Ordering::Relaxed);
+
+    info!(recorded, "wrote recording to {}", path.display());
+    Ok(())
+}
+
+/// Captures packets on the selected devices until the timeout expires,
+/// all devices stopped capturing or the user asked to stop.
+///
+/// Only the frames sent from or to the game server are handed out, with the address of the server.
+fn live_packets(live: &LiveArgs) -> Result<impl Iterator<Item = (SocketAddr, Vec<u8>)> + Send, ArchiverError> {
+    let (tx, rx) = mpsc::channel();
+
+    match (&live.remote, live.backend) {
+        (Some(address), _) => spawn_remote_capture(address.clone(), tx.clone()),
+        (None, Backend::Pcap) => {
+            let devices = select_devices(&live.interface)?;
+
+            // we need to specify a specific network device when using pcap to capture network packets.
+            // to lessen the burden on the user, we instead just capture *all* valid network devices
+            // by capturing each on a different thread and sending the captured packets to a mpsc channel
+            for device in devices {
+                let tx = tx.clone();
+                std::thread::spawn(move || {
+                    let name = device.name.clone();
+                    if let Err(e) = capture_device(device, tx) {
+                        warn!(%e, "could not capture on interface {name}");
+                    }
+                });
+            }
+        }
+        (None, Backend::WinDivert) => spawn_windivert_capture(&live.interface, tx.clone())?,
+    }
+
+    // we clone tx into every thread, but at the end the original tx still remains.
+    // rx.recv will continue to listen while at least one tx is still alive.
+    // we drop the original tx to make sure that there are no tx alive after all threads
+    // have dropped theirs
+    drop(tx);
+
+    let timeout = Duration::from_secs(live.timeout);
+    // when waiting for the game, the timeout only starts once the first packet arrives.
+    // there is no timeout at all in watch mode
+    let mut deadline = (!live.wait && !live.watch).then(|| Instant::now() + timeout);
+    let mut servers = ServerFilter::new();
+    // traffic from another machine cannot be missing because of a tunnel on this one
+    let mut missing_traffic_hint = live.remote.is_none().then(|| Instant::now() + MISSING_TRAFFIC_HINT_AFTER);
+    // in watch mode the game is expected to be restarted
+    let mut game_exit = (live.remote.is_none() && !live.watch && !live.keep_after_exit).then(GameExitWatch::new);
+    // the packets are handed out on the thread of the session
+    let live = live.clone();
+    let packets = std::iter::from_fn(move || loop {
+        if STOP_REQUESTED.load(Ordering::Relaxed) {
+            info!("stopped capturing early");
+            return None;
+        }
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            warn!("capture timed out after {} seconds", live.timeout);
+            return None;
+        }
+        if game_exit.as_mut().is_some_and(|game_exit| game_exit.has_exited()) {
+            info!("the game was closed, stop capturing");
+            return None;
+        }
+        if missing_traffic_hint.is_some_and(|hint| Instant::now() >= hint) {
+            // look again later if the game was not started yet
+            missing_traffic_hint = (!hint_missing_traffic(&live)).then(|| Instant::now() + MISSING_TRAFFIC_HINT_AFTER);
+        }
+
+        // wake up regularly to notice stop requests while no packets arrive
+        let wait = match deadline {
+            Some(deadline) => deadline.saturating_duration_since(Instant::now()).min(POLL_INTERVAL),
+            None => POLL_INTERVAL,
+        };
+
+        match rx.recv_timeout(wait) {
+            Ok(data) => {
+                let Some(server) = servers.filter(&data) else {
+                    continue;
+                };
+                missing_traffic_hint = None;
+                if deadline.is_none() && !live.watch {
+                    info!("detected game traffic, listening with a timeout of {} seconds...", live.timeout);
+                    deadline = Some(Instant::now() + timeout);
+                }
+                return Some((server, data));
+            }
+            Err(mpsc::RecvTimeoutError::Timeout) => {}
+            Err(mpsc::RecvTimeoutError::Disconnected) => {
+                warn!("all capture devices stopped capturing");
+                return None;
+            }
+        }
+    });
+
+    Ok(packets)
+}
+
+/// Passes on the frames read from the TCP connection to `address`, in pcap or pcapng format,
+/// see `--remote`
+fn spawn_remote_capture(address: String, tx: mpsc::Sender<Vec<u8>>) {
+    std::thread::spawn(move || {
+        info!("connecting to {address}");
+        let stream = match TcpStream::connect(&address) {
+            Ok(stream) => stream,
+            Err(e) => {
+                error!(%e, "could not connect to {address}");
+                return;
+            }
+        };
+
+        let packets = match CaptureFile::from_reader(stream) {
+            Ok(packets) => packets,
+            Err(e) => {
+                error!(%e, "could not read packets from {address}");
+                return;
+            }
+        };
+
+        debug!("listening");
+        for frame in packets {
+            if let Err(e) = tx.send(frame) {
+                debug!("channel closed: {e}");
+                break;
+            }
+        }
+        debug!("stop listening");
+    });
+}
+
+#[cfg(all(windows, feature = "windivert"))]
+fn spawn_windivert_capture(interfaces: &[String], tx: mpsc::Sender<Vec<u8>>) -> Result<(), ArchiverError> {
+    if !interfaces.is_empty() {
+        warn!("windivert always captures on all interfaces, ignoring --interface");
+    }
+
+    std::thread::spawn(move || {
+        if let Err(e) = completionist_archiver::capture::windivert::capture(tx) {
+            report_error(&e);
+        }
+    });
+
+    Ok(())
+}
+
+#[cfg(not(all(windows, feature = "windivert")))]
+fn spawn_windivert_capture(_interfaces: &[String], _tx: mpsc::Sender<Vec<u8>>) -> Result<(), ArchiverError> {
+    Err(ArchiverError::Unsupported("the windivert backend is only available on windows, built with the windivert feature"))
+}
+
+/// Explains why no game traffic arrives although the game is running, which is usually
+/// because a VPN or game booster routes the game traffic through a tunnel that is not captured.
+///
+/// Returns whether the game is running and the hint was given.
+fn hint_missing_traffic(live: &LiveArgs) -> bool {
+    if !is_game_running() {
+        return false;
+    }
+
+    warn!("the game is running, but no game traffic was seen yet");
+    warn!("if you already entered train hyperdrive or logged in, the game traffic is not captured");
+    warn!("this usually means a VPN or a game booster like ExitLag routes the game traffic through a tunnel");
+
+    if live.backend == Backend::WinDivert {
+        warn!("try disabling the VPN or game booster, or capture with the default backend");
+        return true;
+    }
+
+    let Ok(devices) = Device::list() else {
+        return true;
+    };
+
+    let captured = |device: &Device| {
+        if live.interface.is_empty() {
+            is_default_device(device)
+        } else {
+            live.interface.iter().any(|i| &device.name == i || device.desc.as_ref() == Some(i))
+        }
+    };
+
+    let tunnels: Vec<&Device> = devices.iter()
+        .filter(|device| is_tunnel_device(device) && !captured(device))
+        .collect();
+
+    if tunnels.is_empty() {
+        warn!("try disabling the VPN or game booster, or pick the interface to capture with --interface");
+        warn!("run the interfaces command to list available interfaces");
+        return true;
+    }
+
+    for device in tunnels {
+        warn!(
+            "found tunnel interface {} ({}) that is not captured, try --interface \"{}\"",
+            device.name,
+            device.desc.as_deref().unwrap_or("no description"),
+            device.name
+        );
+    }
+
+    true
+}
+
+fn is_tunnel_device(device: &Device) -> bool {
+    let name = format!("{} {}", device.name, device.desc.as_deref().unwrap_or_default()).to_lowercase();
+    TUNNEL_KEYWORDS.iter().any(|keyword| name.contains(keyword))
+}
+
+/// Whether a device is captured on when no interfaces were selected explicitly
+pub fn is_default_device(device: &Device) -> bool {
+    matches!(device.flags.connection_status, ConnectionStatus::Connected)
+        && !device.addresses.is_empty()
+        && !device.flags.is_loopback()
+}
+
+/// Devices matching the given interface names or descriptions,
+/// or all default devices if no interfaces are given
+pub fn select_devices(interfaces: &[String]) -> Result<Vec<Device>, ArchiverError> {
+    let devices = Device::list()?;
+
+    if interfaces.is_empty() {
+        return Ok(devices.into_iter().filter(is_default_device).collect());
+    }
+
+    let mut selected = Vec::new();
+    for interface in interfaces {
+        match devices
+            .iter()
+            .find(|d| &d.name == interface || d.desc.as_ref() == Some(interface))
+        {
+            Some(device) => selected.push(device.clone()),
+            None => return Err(ArchiverError::UnknownInterface(interface.clone())),
+        }
+    }
+
+    Ok(selected)
+}
+
+/// Prints the network interfaces that can be captured on
+pub fn list_interfaces() -> Result<(), ArchiverError> {
+    let devices = Device::list()?;
+
+    for device in devices {
+        let addresses: Vec<String> = device.addresses.iter().map(|a| a.addr.to_string()).collect();
+        println!(
+            "{}{}",
+            device.name,
+            if is_default_device(&device) { " (captured by default)" } else { "" }
+        );
+        println!("    description: {}", device.desc.as_deref().unwrap_or("-"));
+        println!("    addresses:   {}", addresses.join(", "));
+    }
+
+    Ok(())
+}
+
+#[instrument(skip_all, fields(device = device.desc))]
+fn capture_device(device: Device, tx: mpsc::Sender<Vec<u8>>) -> Result<(), pcap::Error> {
+    let mut capture = pcap::Capture::from_device(device)?
+        .immediate_mode(true)
+        .promisc(true)
+        .timeout(0) // explicitly disable timeout??
+        .open()?;
+
+    capture.filter(PACKET_FILTER, true)?;
+
+    debug!("listening");
+
+    let mut has_captured = false;
+
+    loop {
+        match capture.next_packet() {
+            Ok(packet) => {
+                trace!("captured packet");
+                if let Err(e) = tx.send(packet.data.to_vec()) {
+                    debug!("channel closed: {e}");
+                    break;
+                }
+
+                has_captured = true;
+            }
+            Err(e) => {
+                // we only really care about capture errors on devices that we already know
+                // are relevant (have sent packets before) and send those errors on warn level.
+                //
+                // if a capture errors right after initialization or on a device that did
+                // not receive any relevant packets, error is less useful to the user,
+                // so we lower the logging level
+
+                if !has_captured {
+                    debug!(?e);
+                    break;
+                } else if matches!(e, Error::TimeoutExpired) {
+                    // somehow a timeout error can still happen even if i explicitly
+                    // disable the timeout?? why :sob:
+                    debug!(?e);
+                    continue;
+                } else {
+                    warn!(?e);
+                    break;
+                }
+            }
+        }
+    }
+
+    debug!("stop listening");
+    Ok(())
+}
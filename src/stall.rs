@@ -0,0 +1,41 @@
+//! Noticing captures that stopped collecting anything new, which usually means that the user
+//! has to do something in the game, like opening the inventory.
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+
+use completionist_archiver::server::{CaptureState, SharedCaptureState};
+
+use crate::CAPTURING;
+
+/// How long nothing new has to be collected while capturing before the capture counts as stalled
+const STALLED_AFTER: Duration = Duration::from_secs(60);
+/// How often the progress is checked
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Watches the progress published to `state` on a thread of its own while capturing,
+/// calling `on_stall` whenever nothing new was collected for a while
+pub fn spawn_watch(state: SharedCaptureState, mut on_stall: impl FnMut(&CaptureState) + Send + 'static) {
+    std::thread::spawn(move || {
+        let mut last_progress = vec![];
+        let mut last_change = Instant::now();
+        let mut stalled = false;
+
+        loop {
+            std::thread::sleep(POLL_INTERVAL);
+
+            let state = state.lock().unwrap();
+            if state.finished || !CAPTURING.load(Ordering::Relaxed) {
+                break;
+            }
+
+            if state.progress != last_progress {
+                last_progress = state.progress.clone();
+                last_change = Instant::now();
+                stalled = false;
+            } else if !stalled && last_change.elapsed() >= STALLED_AFTER {
+                on_stall(&state);
+                stalled = true;
+            }
+        }
+    });
+}
@@ -0,0 +1,216 @@
+//! Summary of a capture printed once the exports are written, with what was found of every
+//! account and where it was written, as the log buries it.
+//!
+//! With `--run-summary` it is also written next to the exports, together with the warnings of
+//! the run and the game data it used, so that archived exports still explain themselves later.
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Local};
+use owo_colors::{OwoColorize, Stream};
+use serde::Serialize;
+use serde_json::Value;
+
+use completionist_archiver::database::Database;
+use completionist_archiver::error::{ArchiverError, PathContext};
+use completionist_archiver::export::fribbels;
+use completionist_archiver::session::SessionReport;
+
+use crate::i18n::{self, t};
+
+/// Width of the labels, so that the values line up
+const LABEL_WIDTH: usize = 16;
+
+/// What was captured of one account
+#[derive(Serialize, Debug)]
+struct AccountSummary {
+    uid: Option<u32>,
+    achievements: Option<usize>,
+    books: Option<usize>,
+    /// Jade of the completed achievements whose reward was not claimed yet
+    unclaimed_jade: Option<u32>,
+}
+
+/// Game data the capture was checked against, which changes with every game version
+#[derive(Serialize, Debug)]
+struct DatabaseSummary {
+    achievements: usize,
+    books: usize,
+    jade: u32,
+    keys: usize,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Summary {
+    accounts: Vec<AccountSummary>,
+    #[serde(skip)]
+    known_achievements: usize,
+    #[serde(skip)]
+    known_books: usize,
+    /// Descriptions of the data that was not captured
+    missing: Vec<&'static str>,
+    written: Vec<PathBuf>,
+    /// Whether the exports were only shown with --dry-run instead of written
+    dry_run: bool,
+    /// When capturing finished, shown so that screenshots of the summary are dated
+    finished_at: DateTime<Local>,
+    database: DatabaseSummary,
+    /// Messages warned about while running, with how often each was
+    warnings: Vec<(String, usize)>,
+}
+
+/// How a value of the summary is colored in the terminal
+#[derive(Clone, Copy)]
+enum Tone {
+    Plain,
+    Bold,
+    Complete,
+    Partial,
+    Path,
+    Missing,
+}
+
+impl Summary {
+    /// Summary of the exports of a capture, read from the fribbels exports if there are any,
+    /// or from the progress of the session otherwise
+    pub fn new(exports: &[Value], formats: &[String], database: &Database, report: Option<&SessionReport>) -> Summary {
+        let mut accounts: Vec<AccountSummary> = formats.iter()
+            .position(|format| format == "fribbels")
+            .into_iter()
+            .flat_map(|index| exports.iter().filter_map(move |export| {
+                // the export of several formats contains the export of every format in order
+                if formats.len() == 1 { Some(export) } else { export.get(index) }
+            }))
+            .filter_map(|export| serde_json::from_value::<fribbels::Export>(export.clone()).ok())
+            .map(|export| AccountSummary {
+                uid: export.metadata.uid,
+                achievements: Some(export.achievements().len()),
+                books: Some(export.books().len()),
+                unclaimed_jade: Some(export.metadata.unclaimed.iter().map(|&id| database.achievement_jade(id)).sum()),
+            })
+            .collect();
+
+        if let Some(report) = report.filter(|_| accounts.is_empty()) {
+            let count = |name: &str| report.progress.iter().find(|progress| progress.name == name).and_then(|progress| progress.count);
+            accounts.push(AccountSummary {
+                uid: None,
+                achievements: count("achievements"),
+                books: count("books"),
+                unclaimed_jade: None,
+            });
+        }
+
+        Summary {
+            accounts,
+            known_achievements: database.achievement_ids().len(),
+            known_books: database.book_ids().len(),
+            missing: report.map(|report| report.missing.clone()).unwrap_or_default(),
+            written: vec![],
+            dry_run: false,
+            finished_at: Local::now(),
+            database: DatabaseSummary {
+                achievements: database.achievement_ids().len(),
+                books: database.book_ids().len(),
+                jade: database.total_achievement_jade(),
+                keys: database.keys().len(),
+            },
+            warnings: vec![],
+        }
+    }
+
+    pub fn with_written(mut self, written: &[PathBuf], dry_run: bool) -> Summary {
+        self.written = written.to_vec();
+        self.dry_run = dry_run;
+        self
+    }
+
+    pub fn with_warnings(mut self, warnings: Vec<(String, usize)>) -> Summary {
+        self.warnings = warnings;
+        self
+    }
+
+    pub fn print(&self) {
+        println!();
+        println!("{}", format!("── {} ──", t!("summary")).if_supports_color(Stream::Stdout, |text| text.bold()));
+        for (label, value, tone) in self.rows() {
+            let value = match tone {
+                Tone::Plain => value,
+                Tone::Bold => value.if_supports_color(Stream::Stdout, |text| text.bold()).to_string(),
+                Tone::Complete => value.if_supports_color(Stream::Stdout, |text| text.green()).to_string(),
+                Tone::Partial | Tone::Missing => value.if_supports_color(Stream::Stdout, |text| text.yellow()).to_string(),
+                Tone::Path => value.if_supports_color(Stream::Stdout, |text| text.cyan()).to_string(),
+            };
+            println!("{label:<LABEL_WIDTH$}{value}");
+        }
+        println!();
+    }
+
+    /// Writes the summary with the warnings and the game data to `path`, as JSON if it ends
+    /// in .json and as text otherwise
+    pub fn write(&self, path: &Path) -> Result<(), ArchiverError> {
+        if path.extension().is_some_and(|extension| extension == "json") {
+            let file = std::fs::File::create(path).with_path("create", path)?;
+            return serde_json::to_writer_pretty(file, self).with_path("write", path);
+        }
+
+        let mut text = format!("── {} ──\n", t!("summary"));
+        for (label, value, _) in self.rows() {
+            let _ = writeln!(text, "{label:<LABEL_WIDTH$}{value}");
+        }
+
+        let _ = writeln!(text, "\n── {} ──", t!("run-summary-database"));
+        let database = [
+            (t!("achievements"), i18n::number(self.database.achievements as u64)),
+            (t!("books"), i18n::number(self.database.books as u64)),
+            (t!("jade"), i18n::number(self.database.jade)),
+            (t!("keys"), i18n::number(self.database.keys as u64)),
+        ];
+        for (label, value) in database {
+            let _ = writeln!(text, "{label:<LABEL_WIDTH$}{value}");
+        }
+
+        let _ = writeln!(text, "\n── {} ──", t!("run-summary-warnings"));
+        if self.warnings.is_empty() {
+            let _ = writeln!(text, "-");
+        }
+        for (message, count) in &self.warnings {
+            let _ = writeln!(text, "{count:>4}× {message}");
+        }
+
+        std::fs::write(path, text).with_path("write", path)
+    }
+
+    /// Labels and values of the summary in the order they are shown
+    fn rows(&self) -> Vec<(String, String, Tone)> {
+        let mut rows = vec![(t!("finished-at"), i18n::date_time(&self.finished_at), Tone::Plain)];
+
+        for account in &self.accounts {
+            rows.push((t!("uid"), account.uid.map_or("-".to_string(), |uid| uid.to_string()), Tone::Bold));
+            let (text, tone) = found(account.achievements, self.known_achievements);
+            rows.push((t!("achievements"), text, tone));
+            let (text, tone) = found(account.books, self.known_books);
+            rows.push((t!("books-obtained"), text, tone));
+            if let Some(jade) = account.unclaimed_jade {
+                rows.push((t!("unclaimed-jade"), i18n::number(jade), Tone::Plain));
+            }
+        }
+
+        let written = if self.dry_run { t!("not-written") } else { t!("written") };
+        for path in &self.written {
+            rows.push((written.clone(), path.display().to_string(), Tone::Path));
+        }
+        for missing in &self.missing {
+            rows.push((t!("missing"), missing.to_string(), Tone::Missing));
+        }
+        rows
+    }
+}
+
+/// Number of found items out of the known ones, complete once all were found
+fn found(found: Option<usize>, known: usize) -> (String, Tone) {
+    let Some(found) = found else {
+        return ("-".to_string(), Tone::Plain);
+    };
+    let text = format!("{}/{} ({})", i18n::number(found as u64), i18n::number(known as u64), i18n::percent(found, known));
+    (text, if found >= known { Tone::Complete } else { Tone::Partial })
+}
@@ -0,0 +1,131 @@
+//! Tray icon mode on Windows, capturing in the background without a console window.
+//!
+//! The progress of the capture is shown in the tooltip of the icon,
+//! and a notification pops up once the export was written.
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+use tracing::{error, warn};
+use tray_icon::menu::{Menu, MenuEvent, MenuItem};
+use tray_icon::{Icon, TrayIcon, TrayIconBuilder};
+use windows_sys::Win32::System::Console::FreeConsole;
+use windows_sys::Win32::UI::WindowsAndMessaging::{DispatchMessageW, PeekMessageW, TranslateMessage, MSG, PM_REMOVE};
+
+use completionist_archiver::database::Database;
+use completionist_archiver::error::ArchiverError;
+use completionist_archiver::server::SharedCaptureState;
+
+use crate::commands::{capture_and_export, reveal};
+use crate::{notify, Args, CAPTURING, STOP_REQUESTED};
+
+const TITLE: &str = "completionist archiver";
+/// How often the tooltip is updated
+const UPDATE_INTERVAL: Duration = Duration::from_millis(100);
+/// Windows cuts tooltips off after this many characters
+const TOOLTIP_LEN: usize = 127;
+
+/// Loads the database and captures in the background while showing a tray icon,
+/// until the export was written or the capture was stopped from the tray menu
+pub fn run(args: &Args) -> Result<(), ArchiverError> {
+    // everything is logged to the log file instead, if there is one
+    unsafe {
+        FreeConsole();
+    }
+
+    let state = SharedCaptureState::default();
+    let quit = MenuItem::new("Stop capturing", true, None);
+
+    let tray = match build_tray(&quit) {
+        Ok(tray) => tray,
+        Err(e) => {
+            // capturing without a way to stop it would not be any better
+            error!(%e, "could not create tray icon");
+            return Ok(());
+        }
+    };
+
+    let captured = std::thread::scope(|scope| {
+        let capture = scope.spawn(|| {
+            let database = Database::new_from_online_with_deadline(
+                &args.database_options(),
+                Duration::from_secs(args.database_timeout),
+            )?;
+            capture_and_export(args, database, Some(state.clone()), None)
+        });
+
+        while !capture.is_finished() {
+            pump_messages();
+
+            if MenuEvent::receiver().try_recv().is_ok_and(|event| &event.id == quit.id()) {
+                if !CAPTURING.load(Ordering::Relaxed) {
+                    std::process::exit(130);
+                }
+                STOP_REQUESTED.store(true, Ordering::Relaxed);
+            }
+
+            if let Err(e) = tray.set_tooltip(Some(tooltip(&state))) {
+                warn!(%e, "could not update tray tooltip");
+            }
+
+            std::thread::sleep(UPDATE_INTERVAL);
+        }
+
+        capture.join().unwrap()
+    });
+
+    let (written, problem) = match captured {
+        Ok(captured) => (Ok(captured.written), captured.problem),
+        Err(e) => (Err(e), None),
+    };
+    notify::exported(&written, &state);
+    if args.session().open {
+        if let Ok(written) = &written {
+            reveal(written);
+        }
+    }
+    // the problem decides the exit code, like without the tray
+    written.and_then(|_| problem.map_or(Ok(()), Err))
+}
+
+fn build_tray(quit: &MenuItem) -> Result<TrayIcon, Box<dyn std::error::Error>> {
+    let menu = Menu::new();
+    menu.append(quit)?;
+
+    // plain square in the color of stellar jade, so no icon file has to be shipped
+    let icon = [0x4f, 0xc3, 0xf7, 0xff].repeat(16 * 16);
+
+    let tray = TrayIconBuilder::new()
+        .with_menu(Box::new(menu))
+        .with_tooltip(format!("{TITLE}: loading database"))
+        .with_icon(Icon::from_rgba(icon, 16, 16)?)
+        .build()?;
+
+    Ok(tray)
+}
+
+/// Handles the pending window messages of this thread, which the tray icon relies on
+fn pump_messages() {
+    unsafe {
+        let mut msg: MSG = std::mem::zeroed();
+        while PeekMessageW(&mut msg, 0, 0, 0, PM_REMOVE) != 0 {
+            TranslateMessage(&msg);
+            DispatchMessageW(&msg);
+        }
+    }
+}
+
+fn tooltip(state: &SharedCaptureState) -> String {
+    let state = state.lock().unwrap();
+
+    let mut tooltip = if state.progress.is_empty() {
+        format!("{TITLE}: waiting for the game")
+    } else {
+        let categories: Vec<String> = state.progress.iter().map(|p| p.to_string()).collect();
+        format!("{TITLE}\n{}\n{:02}:{:02} elapsed", categories.join("\n"), state.elapsed / 60, state.elapsed % 60)
+    };
+
+    if let Some((index, _)) = tooltip.char_indices().nth(TOOLTIP_LEN) {
+        tooltip.truncate(index);
+    }
+    tooltip
+}
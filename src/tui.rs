@@ -0,0 +1,239 @@
+//! Terminal dashboard showing the progress of the capture, the items detected last and the end
+//! of the log, instead of scrolling log output.
+use std::collections::VecDeque;
+use std::io;
+use std::path::PathBuf;
+use std::sync::atomic::Ordering;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread::ScopedJoinHandle;
+use std::time::Duration;
+
+use ratatui::crossterm::event::{self, Event as TerminalEvent, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, List, Paragraph, Wrap};
+use ratatui::{DefaultTerminal, Frame};
+use tracing::error;
+
+use completionist_archiver::database::Database;
+use completionist_archiver::error::ArchiverError;
+use completionist_archiver::export::Event;
+use completionist_archiver::server::SharedCaptureState;
+
+use crate::commands::capture_and_export;
+use crate::{Args, CAPTURING, STOP_REQUESTED};
+
+/// How often the dashboard is redrawn to show new progress
+const REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+/// Number of detected items that are kept to be shown
+const RECENT_LEN: usize = 100;
+/// Number of log lines that are kept to be shown
+const LOG_LEN: usize = 500;
+
+/// End of the log, written to by the log layer instead of the terminal while the dashboard is shown
+#[derive(Clone, Default)]
+pub struct LogTail {
+    lines: Arc<Mutex<VecDeque<String>>>,
+}
+
+impl io::Write for LogTail {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut lines = self.lines.lock().unwrap();
+        for line in String::from_utf8_lossy(buf).lines().filter(|line| !line.is_empty()) {
+            if lines.len() == LOG_LEN {
+                lines.pop_front();
+            }
+            lines.push_back(line.to_string());
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Loads the database and captures while showing the dashboard, until the capture finished
+/// and the dashboard was closed
+pub fn run(args: &Args, log: LogTail) -> Result<(), ArchiverError> {
+    let state = SharedCaptureState::default();
+    let (events_tx, events_rx) = mpsc::channel();
+    let mut dashboard = Dashboard {
+        state: state.clone(),
+        log,
+        events: events_rx,
+        recent: VecDeque::new(),
+        result: None,
+    };
+
+    let mut terminal = ratatui::init();
+    let result = std::thread::scope(|scope| {
+        let capture = scope.spawn(move || {
+            let database = Database::new_from_online_with_deadline(
+                &args.database_options(),
+                Duration::from_secs(args.database_timeout),
+            )?;
+            capture_and_export(args, database, Some(state), Some(events_tx)).map(|captured| captured.written)
+        });
+
+        event_loop(&mut terminal, &mut dashboard, capture)
+    });
+    ratatui::restore();
+
+    result.map(|_| ())
+}
+
+/// Draws the dashboard and handles key presses until it is closed after the capture finished,
+/// returning the result of the capture
+fn event_loop(
+    terminal: &mut DefaultTerminal,
+    dashboard: &mut Dashboard,
+    capture: ScopedJoinHandle<'_, Result<Vec<PathBuf>, ArchiverError>>,
+) -> Result<Vec<PathBuf>, ArchiverError> {
+    let mut capture = Some(capture);
+
+    loop {
+        if capture.as_ref().is_some_and(|capture| capture.is_finished()) {
+            dashboard.result = capture.take().map(|capture| capture.join().unwrap());
+        }
+        dashboard.receive_events();
+
+        let input = terminal.draw(|frame| dashboard.draw(frame)).and_then(|_| read_key());
+        let key = match input {
+            Ok(key) => key,
+            Err(e) => {
+                error!(%e, "could not show the dashboard");
+                // the capture cannot be followed anymore, so it is stopped and exported right away
+                STOP_REQUESTED.store(true, Ordering::Relaxed);
+                return match capture {
+                    Some(capture) => capture.join().unwrap(),
+                    None => Err(e.into()),
+                };
+            }
+        };
+
+        match key {
+            Some(Key::Finish) => STOP_REQUESTED.store(true, Ordering::Relaxed),
+            Some(Key::Quit) if capture.is_none() => {
+                return dashboard.result.take().expect("the result is kept until quitting");
+            }
+            Some(Key::Quit) | Some(Key::Abort) => {
+                // same as pressing ctrl-c twice, nothing is exported
+                ratatui::restore();
+                std::process::exit(130);
+            }
+            None => {}
+        }
+    }
+}
+
+enum Key {
+    /// Stop capturing and export what was collected
+    Finish,
+    /// Close the dashboard once the capture finished, abort it before
+    Quit,
+    /// Exit without exporting
+    Abort,
+}
+
+/// Waits for a key press until the dashboard has to be redrawn
+fn read_key() -> io::Result<Option<Key>> {
+    if !event::poll(REDRAW_INTERVAL)? {
+        return Ok(None);
+    }
+    let TerminalEvent::Key(key) = event::read()? else {
+        return Ok(None);
+    };
+    if key.kind != KeyEventKind::Press {
+        return Ok(None);
+    }
+
+    Ok(match key.code {
+        KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => Some(Key::Abort),
+        KeyCode::Char('f') | KeyCode::Enter => Some(Key::Finish),
+        KeyCode::Char('q') | KeyCode::Esc => Some(Key::Quit),
+        _ => None,
+    })
+}
+
+struct Dashboard {
+    state: SharedCaptureState,
+    log: LogTail,
+    events: mpsc::Receiver<Event>,
+    /// Items detected last, newest first
+    recent: VecDeque<String>,
+    /// Result of the capture once it finished
+    result: Option<Result<Vec<PathBuf>, ArchiverError>>,
+}
+
+impl Dashboard {
+    fn receive_events(&mut self) {
+        for event in self.events.try_iter() {
+            let line = match event {
+                Event::Uid { uid } => format!("uid {uid}"),
+                Event::Achievement { id, name } => format!("achievement {}", name.unwrap_or_else(|| id.to_string())),
+                Event::Book { id, name } => format!("book {}", name.unwrap_or_else(|| id.to_string())),
+            };
+            if self.recent.len() == RECENT_LEN {
+                self.recent.pop_back();
+            }
+            self.recent.push_front(line);
+        }
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        let [status, middle, log, keys] = Layout::vertical([
+            Constraint::Length(3),
+            Constraint::Percentage(50),
+            Constraint::Fill(1),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+        let [progress, recent] = Layout::horizontal([Constraint::Length(30), Constraint::Fill(1)]).areas(middle);
+
+        let state = self.state.lock().unwrap();
+
+        let status_text = match &self.result {
+            Some(Ok(written)) => {
+                let written: Vec<String> = written.iter().map(|path| path.display().to_string()).collect();
+                format!("capture finished, wrote {}", written.join(", "))
+            }
+            Some(Err(e)) => format!("capture failed: {e}"),
+            None if CAPTURING.load(Ordering::Relaxed) => format!(
+                "capturing, {:02}:{:02} elapsed. go to the main menu and enter train hyperdrive",
+                state.elapsed / 60,
+                state.elapsed % 60
+            ),
+            None => "loading the game data...".to_string(),
+        };
+        frame.render_widget(
+            Paragraph::new(status_text).wrap(Wrap { trim: true }).block(Block::bordered().title("completionist archiver")),
+            status,
+        );
+
+        let progress_lines: Vec<Line> = state.progress.iter().map(|progress| Line::from(progress.to_string())).collect();
+        frame.render_widget(Paragraph::new(progress_lines).block(Block::bordered().title("progress")), progress);
+        drop(state);
+
+        frame.render_widget(
+            List::new(self.recent.iter().map(String::as_str)).block(Block::bordered().title("detected")),
+            recent,
+        );
+
+        // only the lines that fit are shown, the newest at the bottom
+        let lines = self.log.lines.lock().unwrap();
+        let shown = lines.len().saturating_sub(log.height.saturating_sub(2) as usize);
+        frame.render_widget(
+            List::new(lines.iter().skip(shown).map(String::as_str)).block(Block::bordered().title("log")),
+            log,
+        );
+        drop(lines);
+
+        let help = if self.result.is_some() {
+            "q: quit"
+        } else {
+            "f/enter: finish and export   q: abort without exporting"
+        };
+        frame.render_widget(Paragraph::new(help), keys);
+    }
+}
@@ -0,0 +1,209 @@
+//! Updating the archiver to the latest GitHub release, as the archiver has to be updated after
+//! every game patch, and checking on startup whether it is outdated.
+//!
+//! Every release has a binary named `completionist-archiver-{os}-{arch}` for each platform
+//! (with `.exe` on windows), next to a `.sha256` file with its checksum.
+use std::cmp::Ordering;
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+
+use chrono::{DateTime, Utc};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use tracing::{debug, info, warn};
+use ureq::Agent;
+
+use completionist_archiver::database::HttpTimeouts;
+use completionist_archiver::error::{ArchiverError, PathContext};
+
+const RELEASES_URL: &str = "https://api.github.com/repos/hashblen/completionist-archiver/releases";
+/// Last change of the keys, which are updated for every game version
+const KEYS_COMMITS_URL: &str = "https://api.github.com/repos/tamilpp25/Iridium-SR/commits?path=data/Keys.json&per_page=1";
+/// Binaries larger than this are not downloaded
+const MAX_BINARY_SIZE: u64 = 100 * 1024 * 1024;
+
+#[derive(Deserialize, Debug)]
+struct Release {
+    tag_name: String,
+    published_at: Option<DateTime<Utc>>,
+    assets: Vec<Asset>,
+}
+
+impl Release {
+    fn version(&self) -> &str {
+        self.tag_name.trim_start_matches('v')
+    }
+
+    fn is_newer(&self) -> bool {
+        compare_versions(self.version(), env!("CARGO_PKG_VERSION")) == Ordering::Greater
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct Asset {
+    name: String,
+    browser_download_url: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct Commit {
+    commit: CommitDetails,
+}
+
+#[derive(Deserialize, Debug)]
+struct CommitDetails {
+    committer: Signature,
+}
+
+#[derive(Deserialize, Debug)]
+struct Signature {
+    date: DateTime<Utc>,
+}
+
+/// Checks on a thread of its own whether there is a newer release, or whether the game was
+/// updated since this version was released, warning if so. Failing to check is only logged
+/// as debug, as the archiver works offline from a capture file
+pub fn spawn_check(timeouts: HttpTimeouts) {
+    std::thread::spawn(move || {
+        if let Err(e) = check(timeouts) {
+            debug!(%e, "could not check for updates");
+        }
+    });
+}
+
+fn check(timeouts: HttpTimeouts) -> Result<(), ArchiverError> {
+    let agent = timeouts.agent();
+    let current = env!("CARGO_PKG_VERSION");
+
+    let latest: Release = get_json(&agent, &format!("{RELEASES_URL}/latest"))?;
+    if latest.is_newer() {
+        warn!("version {} is available, this is {current}. run the self-update command to update", latest.version());
+        return Ok(());
+    }
+
+    // the keys are updated for every game version, which might need a new release
+    let release: Release = get_json(&agent, &format!("{RELEASES_URL}/tags/v{current}"))?;
+    let commits: Vec<Commit> = get_json(&agent, KEYS_COMMITS_URL)?;
+    let keys_updated = commits.first().map(|commit| commit.commit.committer.date);
+    debug!(?keys_updated, released = ?release.published_at, "checked for updates");
+
+    if let (Some(keys_updated), Some(released)) = (keys_updated, release.published_at) {
+        if keys_updated > released {
+            warn!(
+                "the game was updated on {} after this version was released, if nothing is captured \
+                 wait for a new version of the archiver",
+                keys_updated.format("%Y-%m-%d")
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Replaces the running binary with the one of the latest release if it is newer,
+/// or only reports whether there is one if `check` is set
+pub fn run(timeouts: HttpTimeouts, check: bool) -> Result<(), ArchiverError> {
+    let agent = timeouts.agent();
+    let release: Release = get_json(&agent, &format!("{RELEASES_URL}/latest"))?;
+
+    let current = env!("CARGO_PKG_VERSION");
+    let latest = release.version();
+    if !release.is_newer() {
+        info!("already up to date, {current} is the latest version");
+        return Ok(());
+    }
+    info!("version {latest} is available, this is {current}");
+    if check {
+        return Ok(());
+    }
+
+    let name = format!("completionist-archiver-{}-{}{}", std::env::consts::OS, std::env::consts::ARCH, std::env::consts::EXE_SUFFIX);
+    let asset = |name: &str| release.assets.iter().find(|asset| asset.name == name).ok_or_else(|| {
+        ArchiverError::InvalidData(format!("release {} has no {name}, please download it manually", release.tag_name))
+    });
+    let binary = asset(&name)?;
+    let checksum = asset(&format!("{name}.sha256"))?;
+
+    let expected = String::from_utf8_lossy(&get_bytes(&agent, &checksum.browser_download_url)?)
+        .split_whitespace()
+        .next()
+        .unwrap_or_default()
+        .to_lowercase();
+    let bytes = get_bytes(&agent, &binary.browser_download_url)?;
+    let actual = format!("{:x}", Sha256::digest(&bytes));
+    if actual != expected {
+        return Err(ArchiverError::InvalidData(format!(
+            "checksum of {name} is {actual}, but the release lists {expected}"
+        )));
+    }
+    debug!(checksum = actual, "verified download");
+
+    replace_binary(&bytes)?;
+    info!("updated to version {latest}, it is used the next time the archiver is started");
+    Ok(())
+}
+
+/// Writes the new binary next to the running one and swaps them. The running binary is only
+/// renamed, as it cannot be deleted on windows while it is running
+fn replace_binary(bytes: &[u8]) -> Result<(), ArchiverError> {
+    let current = std::env::current_exe()?;
+    let new = current.with_extension("new");
+    let old = current.with_extension("old");
+
+    // left over from the previous update
+    if old.exists() {
+        if let Err(e) = fs::remove_file(&old) {
+            warn!(%e, "could not remove {}", old.display());
+        }
+    }
+
+    fs::write(&new, bytes).with_path("write", &new)?;
+    make_executable(&new)?;
+    fs::rename(&current, &old).with_path("move", &current)?;
+    if let Err(e) = fs::rename(&new, &current) {
+        // put the running binary back, so there still is one
+        let _ = fs::rename(&old, &current);
+        return Err(e).with_path("move", &new);
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn make_executable(path: &Path) -> Result<(), ArchiverError> {
+    use std::os::unix::fs::PermissionsExt;
+
+    fs::set_permissions(path, fs::Permissions::from_mode(0o755)).with_path("set permissions of", path)
+}
+
+#[cfg(not(unix))]
+fn make_executable(_path: &Path) -> Result<(), ArchiverError> {
+    Ok(())
+}
+
+/// Compares versions like `0.1.6` number by number
+fn compare_versions(a: &str, b: &str) -> Ordering {
+    let numbers = |version: &str| -> Vec<u64> { version.split('.').map(|number| number.parse().unwrap_or(0)).collect() };
+    numbers(a).cmp(&numbers(b))
+}
+
+fn get_json<T: DeserializeOwned>(agent: &Agent, url: &str) -> Result<T, ArchiverError> {
+    call(agent, url)?.into_json().map_err(|e| ArchiverError::Resource {
+        url: url.to_string(),
+        reason: e.to_string(),
+    })
+}
+
+fn get_bytes(agent: &Agent, url: &str) -> Result<Vec<u8>, ArchiverError> {
+    let mut bytes = vec![];
+    call(agent, url)?.into_reader().take(MAX_BINARY_SIZE).read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn call(agent: &Agent, url: &str) -> Result<ureq::Response, ArchiverError> {
+    debug!(url, "requesting from github");
+    agent.get(url).call().map_err(|e| ArchiverError::Download {
+        url: url.to_string(),
+        source: Box::new(e),
+    })
+}
@@ -0,0 +1,103 @@
+//! Comparison of the collected data with the database, to notice captures that missed data
+//! and game data that is out of date.
+use std::fmt::{Display, Formatter};
+
+use serde::Serialize;
+use tracing::{info, warn};
+
+use crate::database::Database;
+use crate::model::Account;
+
+/// How many completed achievements the collected ones may differ from the number the game shows
+/// on the profile. The profile only shows the count of when it was opened, so achievements
+/// completed while capturing after that are collected but not counted on it. A capture that
+/// missed the quest data lacks far more than a couple
+const MAX_REPORTED_DIFFERENCE: usize = 2;
+
+/// How many items of a category were collected, compared to the database
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Coverage {
+    /// Number of known items that were collected
+    pub found: usize,
+    /// Number of items known to the database
+    pub known: usize,
+    /// Ids that look like items of the category, but are missing from the database
+    pub unknown: Vec<u32>,
+}
+
+impl Display for Coverage {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{} known", self.found, self.known)?;
+        if !self.unknown.is_empty() {
+            write!(f, ", {} unknown ids seen", self.unknown.len())?;
+        }
+        Ok(())
+    }
+}
+
+/// Coverage of every category of an account, written before exporting it
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct ValidationReport {
+    pub uid: Option<u32>,
+    pub achievements: Coverage,
+    pub books: Coverage,
+    /// Number of completed achievements the game shows on the profile, if it was opened
+    pub reported_achievements: Option<u32>,
+}
+
+impl ValidationReport {
+    pub fn new(account: &Account, database: &Database) -> ValidationReport {
+        ValidationReport {
+            uid: account.uid,
+            achievements: Coverage {
+                found: account.achievements.len(),
+                known: database.achievement_ids().len(),
+                unknown: account.unknown.achievements.iter().copied().collect(),
+            },
+            books: Coverage {
+                found: account.books.len(),
+                known: database.book_ids().len(),
+                unknown: account.unknown.books.iter().copied().collect(),
+            },
+            reported_achievements: account.reported_achievements,
+        }
+    }
+
+    /// Whether ids missing from the database were seen, which usually means that the game
+    /// was updated since the game data was published
+    pub fn is_database_outdated(&self) -> bool {
+        !self.achievements.unknown.is_empty() || !self.books.unknown.is_empty()
+    }
+
+    /// Whether fewer or more achievements were collected than the game shows on the profile,
+    /// counting the ones missing from the database as well
+    pub fn is_count_off(&self) -> bool {
+        self.reported_achievements.is_some_and(|reported| {
+            let collected = self.achievements.found + self.achievements.unknown.len();
+            (reported as usize).abs_diff(collected) > MAX_REPORTED_DIFFERENCE
+        })
+    }
+
+    /// Logs the coverage, warning about the ids missing from the database
+    pub fn log(&self) {
+        info!(uid = self.uid, "achievements: {}, books: {}", self.achievements, self.books);
+
+        if !self.achievements.unknown.is_empty() {
+            warn!(ids = ?self.achievements.unknown, "achievements missing from the database are only exported under unknown_ids");
+        }
+        if !self.books.unknown.is_empty() {
+            warn!(ids = ?self.books.unknown, "books missing from the database are only exported under unknown_ids");
+        }
+        if self.is_database_outdated() {
+            warn!("the game data is probably out of date, convert the export once it is updated to add the unknown ids to it");
+        }
+        if self.is_count_off() {
+            warn!(
+                reported = self.reported_achievements,
+                collected = self.achievements.found + self.achievements.unknown.len(),
+                "the game shows a different number of completed achievements than was collected, the export is probably incomplete. \
+                capture again and open the achievements screen once the game finished loading"
+            );
+        }
+    }
+}
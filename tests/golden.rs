@@ -0,0 +1,237 @@
+//! Feeds the fixtures in `tests/fixtures` through every registered exporter and compares the
+//! exports with the golden files in `tests/golden`, named `<fixture>.<format>.json`. The
+//! validation of the collected accounts is compared with `<fixture>.validation.json`.
+//!
+//! The exports listed in each file in `tests/merges`, oldest first, are merged into one and
+//! compared with `<case>.merged.json`.
+//!
+//! Each export of an older version in `tests/upgrades` is upgraded to the latest version and
+//! compared with `<case>.upgraded.json`.
+//!
+//! After an intended change of an export format, regenerate the golden files with
+//! `UPDATE_GOLDEN=1 cargo test --test golden` and review the diff.
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono::Local;
+use serde_json::{json, Value};
+
+use completionist_archiver::database::{AchievementInfo, BookInfo, Database};
+use completionist_archiver::export::fixture;
+use completionist_archiver::export::fribbels::Export;
+use completionist_archiver::export::merge;
+use completionist_archiver::export::verify;
+use completionist_archiver::export::registry::{ExporterContext, ExporterRegistry};
+use completionist_archiver::model::Collector;
+use completionist_archiver::validation::ValidationReport;
+
+/// Stands in for values that change between runs, like the date items were first seen on
+const TODAY: &str = "<today>";
+const BUILD: &str = "<build>";
+
+fn database() -> Database {
+    database_knowing(&[])
+}
+
+/// Game data of the fixtures that knows the achievements `added` as well, worth 5 jade each,
+/// like game data published after the exports were captured
+fn database_knowing(added: &[u32]) -> Database {
+    let achievement = |id, jade| AchievementInfo {
+        id,
+        jade,
+        target: Some(1),
+        series: None,
+        name: None,
+    };
+    let book = |id| BookInfo {
+        id,
+        series: None,
+        world: None,
+        name: None,
+    };
+
+    Database::new(
+        vec![
+            achievement(4010101, 5),
+            achievement(4010102, 10),
+            achievement(4010103, 20),
+            achievement(4010104, 5),
+        ]
+        .into_iter()
+        .chain(added.iter().map(|&id| achievement(id, 5)))
+        .collect(),
+        vec![book(140001), book(140002), book(140003), book(140004)],
+        HashMap::new(),
+    )
+}
+
+/// Exports of all accounts of a fixture, the account logged in last being last
+fn export(format: &str, fixture: &Path) -> Value {
+    let registry = ExporterRegistry::with_builtin();
+    let mut exporter = registry.create(format, &ExporterContext::new(database())).unwrap();
+
+    for command in fixture::load(fixture).unwrap() {
+        exporter.read_command(&command);
+    }
+
+    let mut exports = exporter.take_switched_accounts_json();
+    exports.push(exporter.export_json());
+
+    let mut exports = Value::Array(exports);
+    normalize(&mut exports, &Local::now().date_naive().to_string());
+    exports
+}
+
+/// Validation of all accounts of a fixture, in the order of [`export`]
+fn validate(fixture: &Path) -> Value {
+    let mut collector = Collector::new(database());
+    for command in fixture::load(fixture).unwrap() {
+        collector.read_command(&command);
+    }
+
+    let mut accounts = collector.take_switched_accounts();
+    accounts.push(collector.account().clone());
+    accounts
+        .iter()
+        .map(|account| {
+            let report = ValidationReport::new(account, collector.database());
+            json!({ "report": report, "count_off": report.is_count_off() })
+        })
+        .collect()
+}
+
+fn normalize(value: &mut Value, today: &str) {
+    match value {
+        // dates in the rows of text formats as well
+        Value::String(string) => *string = string.replace(today, TODAY),
+        Value::Array(values) => values.iter_mut().for_each(|value| normalize(value, today)),
+        Value::Object(object) => {
+            for (key, value) in object.iter_mut() {
+                if key == "build" {
+                    *value = Value::String(BUILD.to_string());
+                } else {
+                    normalize(value, today);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Merge of the exports of a case like the `merge` command does, or the exports of another
+/// account that kept them from being merged
+fn merge(case: &Path) -> Value {
+    match merge::merge(load_case(case), &database()) {
+        Ok(merged) => json!({ "merged": merged }),
+        Err(conflict) => json!({ "conflict": conflict }),
+    }
+}
+
+/// Exports of a case in `tests/merges`, oldest first
+fn load_case(case: &Path) -> Vec<Export> {
+    serde_json::from_str(&fs::read_to_string(case).unwrap()).unwrap()
+}
+
+/// Export of an older version upgraded to the latest version, like the `convert` command does
+fn upgrade(case: &Path) -> Value {
+    let upgraded = Export::load(case).unwrap().upgrade(&database());
+    let mut upgraded = serde_json::to_value(upgraded).unwrap();
+    normalize(&mut upgraded, &Local::now().date_naive().to_string());
+    upgraded
+}
+
+/// JSON files in `tests/<dir>`, sorted by name
+fn cases(dir: &str) -> Vec<PathBuf> {
+    let mut cases: Vec<PathBuf> = fs::read_dir(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join(dir))
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "json"))
+        .collect();
+    cases.sort();
+    cases
+}
+
+/// Compares `actual` with the golden file `<name>.json`, or overwrites it when updating
+fn compare(name: &str, actual: &Value, mismatches: &mut Vec<String>) {
+    let golden = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden").join(format!("{name}.json"));
+
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::write(&golden, serde_json::to_string_pretty(actual).unwrap() + "\n").unwrap();
+        return;
+    }
+
+    let expected: Value = match fs::read_to_string(&golden) {
+        Ok(expected) => serde_json::from_str(&expected).unwrap(),
+        Err(_) => {
+            mismatches.push(format!("{} is missing", golden.display()));
+            return;
+        }
+    };
+    if *actual != expected {
+        mismatches.push(format!(
+            "{} differs, got:\n{}",
+            golden.display(),
+            serde_json::to_string_pretty(actual).unwrap()
+        ));
+    }
+}
+
+fn assert_no_mismatches(mismatches: &[String]) {
+    assert!(
+        mismatches.is_empty(),
+        "{}\n\nrun with UPDATE_GOLDEN=1 to regenerate the golden files if the change is intended",
+        mismatches.join("\n\n")
+    );
+}
+
+#[test]
+fn exports_match_golden_files() {
+    let registry = ExporterRegistry::with_builtin();
+
+    let mut mismatches = vec![];
+    for fixture in cases("fixtures") {
+        let name = fixture.file_stem().unwrap().to_string_lossy();
+
+        let exports = registry.names().map(|format| (format, export(format, &fixture)));
+        for (kind, actual) in exports.chain([("validation", validate(&fixture))]) {
+            compare(&format!("{name}.{kind}"), &actual, &mut mismatches);
+        }
+    }
+    assert_no_mismatches(&mismatches);
+}
+
+#[test]
+fn merges_match_golden_files() {
+    let mut mismatches = vec![];
+    for case in cases("merges") {
+        let name = case.file_stem().unwrap().to_string_lossy();
+        compare(&format!("{name}.merged"), &merge(&case), &mut mismatches);
+    }
+    assert_no_mismatches(&mismatches);
+}
+
+#[test]
+fn upgrades_match_golden_files() {
+    let mut mismatches = vec![];
+    for case in cases("upgrades") {
+        let name = case.file_stem().unwrap().to_string_lossy();
+        compare(&format!("{name}.upgraded"), &upgrade(&case), &mut mismatches);
+    }
+    assert_no_mismatches(&mismatches);
+}
+
+#[test]
+fn merged_exports_verify() {
+    // the unknown achievement of the overlapping exports is known by now, and rewards jade
+    let database = database_knowing(&[4010105]);
+    let case = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/merges/overlapping.json");
+    let merged = merge::merge(load_case(&case), &database).unwrap();
+    assert_eq!(merged.metadata.jade.earned, 40);
+
+    let path = std::env::temp_dir().join(format!("archiver-merged-{}.json", std::process::id()));
+    fs::write(&path, serde_json::to_string_pretty(&merged).unwrap()).unwrap();
+    let verified = verify::verify(&database, &path);
+    fs::remove_file(&path).unwrap();
+    verified.unwrap();
+}